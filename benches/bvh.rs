@@ -0,0 +1,124 @@
+//! Time to find the nearest ellipsoid along a grid of view rays, testing every ellipsoid
+//! against walking the BVH over their bounding boxes. Run with `cargo bench --bench bvh`.
+
+extern crate nalgebra as na;
+
+use std::time::{Duration, Instant};
+
+use na::{Point3, Vector3};
+
+use ellipsoid_ray_casting::{Bvh, Ellipse};
+
+
+const RAYS_PER_SIDE: usize = 64;
+
+/// Above this many ellipsoids only the BVH is timed, testing all of them takes too long.
+const MAX_BRUTE_FORCE: usize = 10_000;
+
+
+/// Ellipsoids of random size and turn scattered through a cube growing with their number,
+/// so the density stays the same.
+fn random_ellipses(count: usize) -> Vec<Ellipse> {
+    // Linear congruential generator, good enough for placing objects.
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut random = move || {
+        state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        (state >> 40) as f32 / (1u64 << 24) as f32
+    };
+
+    let side = (count as f32).cbrt() * 2.0;
+
+    (0..count)
+        .map(|_| {
+            let position = Point3::new(random() - 0.5, random() - 0.5, random()) * side;
+            let mut ellipse = Ellipse::new(0.2 + random() * 0.5, 0.2 + random() * 0.5, 0.2 + random() * 0.5, &position, 0);
+            ellipse.rotation = Vector3::new(random(), random(), random()) * std::f32::consts::PI;
+            ellipse.recalculate();
+            ellipse
+        })
+        .collect()
+}
+
+
+fn ray_origins(ellipses: &[Ellipse]) -> Vec<Point3<f32>> {
+    let side = (ellipses.len() as f32).cbrt() * 2.0;
+
+    (0..RAYS_PER_SIDE * RAYS_PER_SIDE)
+        .map(|i| {
+            let (column, row) = (i % RAYS_PER_SIDE, i / RAYS_PER_SIDE);
+            let x = ((column as f32 + 0.5) / RAYS_PER_SIDE as f32 - 0.5) * side;
+            let y = ((row as f32 + 0.5) / RAYS_PER_SIDE as f32 - 0.5) * side;
+            Point3::new(x, y, -1.0)
+        })
+        .collect()
+}
+
+
+fn nearest_brute_force(ellipses: &[Ellipse], origin: &Point3<f32>) -> Option<f32> {
+    ellipses.iter()
+        .filter_map(|ellipse| ellipse.ray_span(origin, &Vector3::z()))
+        .map(|span| span.start)
+        .filter(|t| *t >= 0.0)
+        .min_by(f32::total_cmp)
+}
+
+
+fn nearest_bvh(bvh: &Bvh, ellipses: &[Ellipse], origin: &Point3<f32>) -> Option<f32> {
+    let mut nearest: Option<f32> = None;
+
+    bvh.traverse(origin, &Vector3::z(), 0.0, f32::INFINITY, |item| {
+        let t = ellipses[item].ray_span(origin, &Vector3::z())
+            .map(|span| span.start)
+            .filter(|t| *t >= 0.0)?;
+
+        nearest = Some(nearest.map_or(t, |nearest| nearest.min(t)));
+        Some(t)
+    });
+
+    nearest
+}
+
+
+fn time<T>(run: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = run();
+    (result, start.elapsed())
+}
+
+
+fn main() {
+    println!("{:>8} {:>12} {:>12} {:>12} {:>12}", "objects", "build ms", "refit ms", "bvh ms", "all ms");
+
+    for count in [100, 1_000, 10_000, 50_000, 100_000] {
+        let mut ellipses = random_ellipses(count);
+        let origins = ray_origins(&ellipses);
+
+        let (mut bvh, build) = time(|| Bvh::new(ellipses.iter().map(|ellipse| Some(ellipse.bounding_box())).collect()));
+
+        // Nudging every ellipsoid keeps the tree and only refits its boxes.
+        for ellipse in &mut ellipses {
+            ellipse.position.x += 0.01;
+            ellipse.recalculate();
+        }
+        let ((), refit) = time(|| bvh.update(ellipses.iter().map(|ellipse| Some(ellipse.bounding_box())).collect()));
+
+        let (with_bvh, traversal) = time(|| origins.iter().map(|origin| nearest_bvh(&bvh, &ellipses, origin)).collect::<Vec<_>>());
+
+        let brute_force = if count <= MAX_BRUTE_FORCE {
+            let (all, duration) = time(|| origins.iter().map(|origin| nearest_brute_force(&ellipses, origin)).collect::<Vec<_>>());
+            assert_eq!(all, with_bvh, "the BVH missed the nearest ellipsoid");
+            format!("{:12.2}", duration.as_secs_f64() * 1e3)
+        } else {
+            format!("{:>12}", "-")
+        };
+
+        println!(
+            "{:>8} {:12.2} {:12.2} {:12.2} {}",
+            count,
+            build.as_secs_f64() * 1e3,
+            refit.as_secs_f64() * 1e3,
+            traversal.as_secs_f64() * 1e3,
+            brute_force
+        );
+    }
+}
@@ -1,26 +1,182 @@
 extern crate nalgebra as na;
 
-use na::{Point2, Point3, Vector3};
+use std::io;
+use std::path::Path;
+
+use math::optics;
+use na::{Matrix3, Point2, Point3, Rotation3, UnitVector3, Vector3};
 use objects::{
     Camera,
     Canvas,
-    Ellipse,
-    Color,
-    ellipse::HitRecord,
+    ellipse::{HitRecord, Surface},
+    svg::{self, SvgDocument},
     CAMERA_CENTER
 };
 use winit::window::Window;
 
 mod math;
 mod objects;
+pub mod scene_file;
+
+
+pub use objects::{Aabb, Background, ClipPlane, Color, Colormap, Ellipse, Environment, EnvironmentError, Light, Material, ParseColorError, ShadowMode, Texture, ToneMapping, ellipse::CutMode};
+pub use objects::collision::Contact;
+pub use objects::bvh::Bvh;
+pub use objects::csg::{CsgNode, CsgOperation};
+pub use objects::curve::{save_curves, Polyline};
+pub use objects::gaussian_splat::{GaussianSplat, SplatCloud, SplatError, SplatView};
+pub use objects::mesh::{Mesh, MeshFormat, Tessellation};
+pub use objects::point_cloud::{FitMethod, PointCloud, PointCloudError};
+pub use objects::primitive::{Hittable, Primitive, PrimitiveKind};
+pub use objects::sdf::{ParseSdfError, SdfNode};
+pub use objects::svg::SvgOptions;
+pub use objects::tensor_field::{Glyph, GlyphColoring, GlyphOptions, TensorField, TensorFieldError};
+pub use scene_file::SceneFileError;
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ShadingMode {
+    Light,
+    GaussianCurvature,
+    MeanCurvature,
+    /// Translucent rendering attenuating the background by the material along the view ray.
+    XRay,
+    /// The loaded Gaussian splats composited over the background, in place of the objects.
+    Splats,
+}
+
+
+/// Secondary rays ignore surfaces closer than this to their origin.
+const RAY_BIAS: f32 = 1e-3;
+
+
+/// Shadow rays ignore occluders closer than this fraction of the way to the light.
+const SHADOW_BIAS: f32 = 1e-4;
+
+
+/// Vertical field of view of the environment shown behind the parallel projection.
+const BACKDROP_FIELD_OF_VIEW: f32 = std::f32::consts::FRAC_PI_3;
+
+
+/// Color of objects overlapping another one while overlaps are highlighted.
+const OVERLAP_COLOR: (u8, u8, u8) = (255, 48, 48);
+
+
+// Colors of the default material library, given to newly added ellipsoids in turn.
+const OBJECT_COLORS: [(&str, (u8, u8, u8)); 4] = [
+    ("yellow", (239, 245, 66)),
+    ("blue", (66, 164, 245)),
+    ("red", (245, 93, 66)),
+    ("green", (96, 219, 110)),
+];
+
+
+/// Object of the scene, an ellipsoid or one of the other primitives.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ObjectId {
+    /// Index in `Scene::ellipses`.
+    Ellipse(usize),
+    /// Index in `Scene::primitives`.
+    Primitive(usize),
+    /// Index in `Scene::glyphs`.
+    Glyph(usize),
+}
+
+
+/// Description of what is visible under a pixel.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct PickResult {
+    pub position: Point3<f32>,
+    /// Distance from the camera along the view direction.
+    pub depth: f32,
+    /// Normal of the visible surface, facing the camera.
+    pub normal: UnitVector3<f32>,
+    pub object: ObjectId,
+    /// Surface coordinates, see `Ellipse::surface_uv`.
+    pub uv: (f32, f32),
+}
+
+
+/// Surface point met by a ray.
+struct Hit {
+    object: ObjectId,
+    pos: Point3<f32>,
+    surface: Surface,
+    /// Whether the ray passes into the object there.
+    entering: bool,
+}
 
 
 pub struct Scene {
     camera: Camera,
-    pub ellipse: Ellipse,
+    pub ellipses: Vec<Ellipse>,
+    /// Objects other than ellipsoids, always shown on their own.
+    primitives: Vec<Primitive>,
+    /// Material library, objects refer to materials by index.
+    materials: Vec<Material>,
+    /// What is rendered, CSG trees over `ellipses`.
+    shapes: Vec<CsgNode>,
+    /// Hierarchy over the boxes of `shapes`, then `primitives`, then `glyphs`, brought up to
+    /// date at the start of every `update`.
+    bvh: Bvh,
+    selected: usize,
     pub canvas: Canvas,
     
-    brightness: f32,
+    background: Background,
+    /// Surroundings replacing the background, seen in reflections and lighting the objects.
+    environment: Option<Environment>,
+    /// Turn of the environment around the vertical axis in radians.
+    environment_rotation: f32,
+    environment_intensity: f32,
+    cut_mode: CutMode,
+
+    /// Reflection and refraction bounces followed from a visible surface.
+    max_depth: u32,
+    /// Lights shading the objects, without any the view direction is used as a headlight.
+    lights: Vec<Light>,
+    shadow_mode: ShadowMode,
+    /// Intensity of the ambient light, scaled by the ambient coefficient of every material.
+    ambient: f32,
+    exposure: f32,
+    tone_mapping: ToneMapping,
+
+    shading_mode: ShadingMode,
+    colormap: Colormap,
+    curvature_range: (f32, f32),
+    observed_curvature_range: Option<(f32, f32)>,
+
+    /// Loaded point cloud, drawn over the objects as depth tested splats.
+    points: Vec<Point3<f32>>,
+    show_points: bool,
+    /// Radius of the splats in pixels.
+    point_size: f32,
+    point_color: Color,
+
+    splats: Option<SplatCloud>,
+    splat_view: SplatView,
+    /// Center of the loaded splat cloud, the point it turns around.
+    splat_center: Point3<f32>,
+    /// Scale fitting the splat cloud to the view.
+    splat_scale: f32,
+
+    /// Loaded tensor field, shown as one ellipsoid glyph per sample passing the thresholds.
+    tensor_field: Option<TensorField>,
+    glyph_options: GlyphOptions,
+    glyphs: Vec<Glyph>,
+    /// Shared by every glyph, the color comes from the glyph itself.
+    glyph_material: Material,
+
+    /// Whether objects overlapping others are drawn in `OVERLAP_COLOR`.
+    highlight_overlaps: bool,
+    /// Relations of every pair of objects, updated while overlaps are highlighted.
+    contacts: Vec<(usize, usize, Contact)>,
+
+    /// Curves where surfaces of objects cross, drawn as an overlay.
+    show_intersections: bool,
+    intersection_curves: Vec<Polyline>,
+    /// Width of the curves in pixels.
+    curve_width: f32,
+    curve_color: Color,
 
     cur_block_size: u32,
     max_block_size: u32,
@@ -31,13 +187,50 @@ impl Scene {
     pub fn new(window: &Window) -> Scene {
         Scene {
             camera: Camera::new(5.0, 5.0),
-            ellipse: Ellipse::new(
+            ellipses: vec![Ellipse::new(
                 2.0, 1.0, 3.0,
                 &Point3::new(0.0_f32, 0.0, 0.0),
-                Color::from_rgb(239, 245, 66),
-            ),
+                0,
+            )],
+            primitives: Vec::new(),
+            materials: default_materials(),
+            shapes: vec![CsgNode::Object(0)],
+            bvh: Bvh::default(),
+            selected: 0,
             canvas: Canvas::new(window),
-            brightness: 2.0,
+            background: Background::default(),
+            environment: None,
+            environment_rotation: 0.0,
+            environment_intensity: 1.0,
+            cut_mode: CutMode::Solid,
+            max_depth: 4,
+            lights: Vec::new(),
+            shadow_mode: ShadowMode::Hard,
+            ambient: 1.0,
+            exposure: 0.0,
+            tone_mapping: ToneMapping::Clamp,
+            shading_mode: ShadingMode::Light,
+            colormap: Colormap::Viridis,
+            curvature_range: (0.0, 1.0),
+            observed_curvature_range: None,
+            points: Vec::new(),
+            show_points: true,
+            point_size: 2.0,
+            point_color: Color::from_rgb(230, 40, 40),
+            splats: None,
+            splat_view: SplatView::default(),
+            splat_center: Point3::origin(),
+            splat_scale: 1.0,
+            tensor_field: None,
+            glyph_options: GlyphOptions::default(),
+            glyphs: Vec::new(),
+            glyph_material: Material::new("glyph", Color::from_rgb(255, 255, 255)),
+            highlight_overlaps: false,
+            contacts: Vec::new(),
+            show_intersections: false,
+            intersection_curves: Vec::new(),
+            curve_width: 2.0,
+            curve_color: Color::from_rgb(255, 255, 255),
             cur_block_size: 81,
             max_block_size: 81
         }
@@ -48,17 +241,39 @@ impl Scene {
     }
 
     pub fn update(&mut self) {
-        self.ellipse.recalculate();
+        for ellipse in &mut self.ellipses {
+            ellipse.recalculate();
+        }
+
+        self.bvh.update(self.item_bounds());
+
+        // Splats are drawn at full resolution in one pass, the image stays until a change.
+        if self.shading_mode == ShadingMode::Splats {
+            if self.cur_block_size > 1 {
+                self.draw_splats();
+                self.cur_block_size = 1;
+            }
+
+            return;
+        }
+
+        if self.highlight_overlaps && self.cur_block_size == self.max_block_size {
+            self.update_contacts();
+        }
 
-        let miss_color: Color = Color::from_rgb(120, 120, 120);
+        if self.show_intersections && self.cur_block_size == self.max_block_size {
+            self.intersection_curves = self.all_intersection_curves();
+        }
 
         let points_x = u32::div_ceil(self.canvas.get_width(), self.cur_block_size);
         let points_y = u32::div_ceil(self.canvas.get_height(), self.cur_block_size);
 
-        let delta_x = Vector3::x() * (self.camera.viewport_width * self.cur_block_size as f32 / self.canvas.get_width() as f32);
-        let delta_y = -Vector3::y() * (self.camera.viewport_height * self.cur_block_size as f32 / self.canvas.get_height() as f32);
+        let block_size = self.cur_block_size as f32;
 
-        let start_pos = self.camera.upper_left_corner() + (delta_x + delta_y) / 2.0;
+        let near_z = self.camera.near_z();
+        let far_z = self.camera.far_z();
+
+        let mut observed_range: Option<(f32, f32)> = None;
 
         for column in 0..points_x {
             for row in 0..points_y {
@@ -66,15 +281,54 @@ impl Scene {
                     continue;
                 }
 
-                let hit_point = start_pos + (row as f32 * delta_y) + (column as f32 * delta_x); 
+                let hit_point = self.camera.viewport_point(
+                    (column as f32 + 0.5) * block_size,
+                    (row as f32 + 0.5) * block_size,
+                    self.canvas.get_width(),
+                    self.canvas.get_height()
+                );
+
+                let v = (row as f32 + 0.5) * self.cur_block_size as f32 / self.canvas.get_height() as f32;
 
-                let color = match self.ellipse.hit(hit_point.x, hit_point.y) {
-                    HitRecord::Hit { z } => {
-                        self.color_calculate(&Point3::new(hit_point.x, hit_point.y, z))
-                    }
+                let color = if self.shading_mode == ShadingMode::XRay {
+                    self.xray_color(hit_point.x, hit_point.y, near_z, far_z, v)
+                } else {
+                    match self.nearest_hit(hit_point.x, hit_point.y, near_z, far_z) {
+                        Some((object, z, surface)) => {
+                            let pos = Point3::new(hit_point.x, hit_point.y, z);
+
+                            let curvature = match (object, surface) {
+                                (_, Surface::Cap(_)) | (ObjectId::Primitive(_), _) => None,
+                                (ObjectId::Ellipse(index), _) => self.curvature_value(&self.ellipses[index], &pos),
+                                (ObjectId::Glyph(index), _) => self.curvature_value(&self.glyphs[index].ellipse, &pos),
+                            };
+
+                            match curvature {
+                                Some(value) => {
+                                    observed_range = Some(match observed_range {
+                                        Some((min, max)) => (f32::min(min, value), f32::max(max, value)),
+                                        None => (value, value)
+                                    });
 
-                    HitRecord::Miss => {
-                        miss_color
+                                    self.colormap.map(value, self.curvature_range.0, self.curvature_range.1)
+                                }
+
+                                None => {
+                                    let hit = Hit { object, pos, surface, entering: !matches!(surface, Surface::Inner) };
+                                    let color = self.shade(&hit, &Vector3::z(), &(CAMERA_CENTER - pos), self.max_depth);
+
+                                    self.tone_mapping.apply(color, self.exposure)
+                                }
+                            }
+                        }
+
+                        None => {
+                            let half_height = 0.5 * self.camera.viewport_height;
+                            let direction = Vector3::new(hit_point.x, hit_point.y, half_height / (0.5 * BACKDROP_FIELD_OF_VIEW).tan());
+
+                            self.environment_radiance(&direction)
+                                .map_or(self.background.color(v), |color| self.tone_mapping.apply(color, self.exposure))
+                        }
                     }
                 };
 
@@ -86,15 +340,542 @@ impl Scene {
             }
         }
 
+        if observed_range.is_some() {
+            self.observed_curvature_range = observed_range;
+        }
+
+        if self.show_points {
+            self.draw_points(near_z, far_z);
+        }
+
+        if self.show_intersections {
+            self.draw_intersection_curves(near_z, far_z);
+        }
+
         if self.cur_block_size > 1 {
             self.cur_block_size /= 3;
         }
     }
 
 
-    fn color_calculate(&self, pos: &Point3<f32>) -> Color {
-        let coef = (CAMERA_CENTER - pos).normalize().dot(&self.ellipse.normal(pos)).powf(self.brightness);
-        self.ellipse.color * coef
+    /// Closest visible hit among all shapes, primitives and glyphs. Plain objects follow the cut mode,
+    /// CSG solids are always filled.
+    fn nearest_hit(&self, x: f32, y: f32, near_z: f32, far_z: f32) -> Option<(ObjectId, f32, Surface)> {
+        let origin = Point3::new(x, y, 0.0);
+
+        nearest_item_hit(&self.bvh, &origin, near_z, far_z, |item| {
+            match self.shapes.get(item) {
+                Some(CsgNode::Object(index)) => match self.ellipses[*index].hit_clipped(x, y, near_z, far_z, self.cut_mode) {
+                    HitRecord::Hit { z, surface } => Some((ObjectId::Ellipse(*index), z, surface)),
+                    HitRecord::Miss => None
+                },
+                Some(shape) => shape.spans(&self.ellipses, x, y, near_z, far_z).first()
+                    .map(|span| (ObjectId::Ellipse(span.entry.0), span.start, span.entry.1)),
+                // Primitives and glyphs are not cut, the inside of a solid shows through the near plane.
+                None => {
+                    let object = self.item_object(item)?;
+                    self.hittable(object).ray_spans(&origin, &Vector3::z()).into_iter()
+                        .flat_map(|span| [(span.start, span.entry), (span.end, span.exit)])
+                        .find(|(z, _)| near_z <= *z && *z <= far_z)
+                        .map(|(z, surface)| (object, z, surface))
+                }
+            }
+        })
+    }
+
+
+    /// Draws the points inside the clipping range that are not hidden behind an object.
+    fn draw_points(&mut self, near_z: f32, far_z: f32) {
+        let width = self.canvas.get_width();
+        let height = self.canvas.get_height();
+
+        for point in &self.points {
+            if point.z < near_z || point.z > far_z {
+                continue;
+            }
+
+            if self.is_occluded(point, near_z, far_z) {
+                continue;
+            }
+
+            let (x, y) = self.camera.pixel_position(point, width, height);
+            self.canvas.draw_disc(Point2::new(x, y), self.point_size, self.point_color);
+        }
+    }
+
+
+    /// Composites the splat cloud over the background.
+    fn draw_splats(&mut self) {
+        let width = self.canvas.get_width();
+        let height = self.canvas.get_height();
+
+        let pixels = match &self.splats {
+            Some(cloud) => cloud.composite(&self.splat_view.transform(&self.splat_center, self.splat_scale), &self.camera, width, height, self.camera.near_z(), self.camera.far_z()),
+            None => vec![(Color::from_rgb_f(0.0, 0.0, 0.0), 1.0); (width * height) as usize],
+        };
+
+        for row in 0..height {
+            let backlight = self.background.color((row as f32 + 0.5) / height as f32);
+
+            for column in 0..width {
+                let (color, transmittance) = pixels[(row * width + column) as usize];
+
+                let color = if backlight.alpha() > 0.0 {
+                    color + backlight * transmittance
+                } else if transmittance < 1.0 {
+                    (color * (1.0 / (1.0 - transmittance))).with_alpha(1.0 - transmittance)
+                } else {
+                    Color::transparent()
+                };
+
+                self.canvas.set_pixel(color, row, column);
+            }
+        }
+    }
+
+
+    /// Whether an object hides `point`. X-ray rendering has no opaque surfaces.
+    fn is_occluded(&self, point: &Point3<f32>, near_z: f32, far_z: f32) -> bool {
+        const DEPTH_BIAS: f32 = 1e-3;
+
+        if self.shading_mode == ShadingMode::XRay {
+            return false;
+        }
+
+        self.nearest_hit(point.x, point.y, near_z, far_z)
+            .is_some_and(|(_, z, _)| z < point.z - DEPTH_BIAS)
+    }
+
+
+    /// Draws the intersection curves where they are not hidden. Pixels skipped by the
+    /// current pass already show the curve from the previous one and are left alone.
+    fn draw_intersection_curves(&mut self, near_z: f32, far_z: f32) {
+        let width = self.canvas.get_width();
+        let height = self.canvas.get_height();
+        let block_size = self.cur_block_size;
+        let skips_centers = self.cur_block_size != self.max_block_size;
+
+        let mut samples = Vec::new();
+
+        for curve in &self.intersection_curves {
+            for (start, end) in curve.segments() {
+                let (start_x, start_y) = self.camera.pixel_position(&start, width, height);
+                let (end_x, end_y) = self.camera.pixel_position(&end, width, height);
+
+                for (x, y, t, coverage) in self.canvas.line_coverage(Point2::new(start_x, start_y), Point2::new(end_x, end_y), self.curve_width) {
+                    let skipped = skips_centers && (x / block_size) % 3 == 1 && (y / block_size) % 3 == 1;
+                    let point = start + (end - start) * t;
+
+                    if !skipped && point.z >= near_z && point.z <= far_z && !self.is_occluded(&point, near_z, far_z) {
+                        samples.push((x, y, coverage));
+                    }
+                }
+            }
+        }
+
+        for (x, y, coverage) in samples {
+            self.canvas.blend_pixel(self.curve_color.with_alpha(coverage), y, x);
+        }
+    }
+
+
+    /// Background at relative height `v` seen through all objects, following the Beer-Lambert law.
+    /// Optical depths of overlapping objects add up.
+    fn xray_color(&self, x: f32, y: f32, near_z: f32, far_z: f32, v: f32) -> Color {
+        let origin = Point3::new(x, y, 0.0);
+
+        let optical_depth = xray_optical_depth(&self.bvh, &origin, near_z, far_z, |item| match self.shapes.get(item) {
+            Some(shape) => shape.spans(&self.ellipses, x, y, near_z, far_z).iter()
+                .map(|span| {
+                    let ellipse = &self.ellipses[span.entry.0];
+                    ellipse.absorption * (ellipse.density * (span.end - span.start))
+                })
+                .fold(Color::from_rgb_f(0.0, 0.0, 0.0), |sum, depth| sum + depth),
+            None => match self.item_object(item) {
+                Some(ObjectId::Primitive(index)) => {
+                    let primitive = &self.primitives[index];
+                    spans_optical_depth(primitive, primitive.density, primitive.absorption, &origin, near_z, far_z)
+                }
+                Some(ObjectId::Glyph(index)) => {
+                    let ellipse = &self.glyphs[index].ellipse;
+                    spans_optical_depth(ellipse, ellipse.density, ellipse.absorption, &origin, near_z, far_z)
+                }
+                _ => Color::from_rgb_f(0.0, 0.0, 0.0),
+            },
+        });
+
+        xray_backlight(self.background.color(v), optical_depth)
+    }
+
+
+    /// Curvature shown by the current shading mode, `None` when the mode does not show curvature.
+    fn curvature_value(&self, ellipse: &Ellipse, pos: &Point3<f32>) -> Option<f32> {
+        match self.shading_mode {
+            ShadingMode::GaussianCurvature => Some(ellipse.curvature(pos).gaussian),
+            ShadingMode::MeanCurvature => Some(ellipse.curvature(pos).mean),
+            ShadingMode::Light | ShadingMode::XRay | ShadingMode::Splats => None,
+        }
+    }
+
+
+    /// Object, position, normal and surface coordinates visible at pixel `(pixel_x, pixel_y)`,
+    /// using the same camera mapping as `update`.
+    pub fn pick(&self, pixel_x: f32, pixel_y: f32) -> Option<PickResult> {
+        if self.shading_mode == ShadingMode::Splats {
+            return None;
+        }
+
+        let point = self.camera.viewport_point(pixel_x, pixel_y, self.canvas.get_width(), self.canvas.get_height());
+        let (object, z, surface) = self.nearest_hit(point.x, point.y, self.camera.near_z(), self.camera.far_z())?;
+
+        let hittable = self.hittable(object);
+        let position = Point3::new(point.x, point.y, z);
+
+        Some(PickResult {
+            position,
+            depth: z - CAMERA_CENTER.z,
+            normal: surface_normal(hittable, &position, surface),
+            object,
+            uv: hittable.surface_uv(&position),
+        })
+    }
+
+
+    /// Color of the surface at `hit` seen along `direction`, adding the light along the
+    /// reflected and refracted rays for up to `depth` more bounces.
+    fn shade(&self, hit: &Hit, direction: &Vector3<f32>, to_viewer: &Vector3<f32>, depth: u32) -> Color {
+        let material = self.material(hit.object);
+        let local = self.color_calculate(hit.object, &hit.pos, hit.surface, to_viewer);
+
+        let reflectivity = material.reflectivity.clamp(0.0, 1.0);
+        let transparency = 1.0 - material.opacity.clamp(0.0, 1.0);
+
+        if depth == 0 || (reflectivity == 0.0 && transparency == 0.0) {
+            return local;
+        }
+
+        let direction = direction.normalize();
+        let normal = surface_normal(self.hittable(hit.object), &hit.pos, hit.surface).into_inner();
+        let cos_i = -direction.dot(&normal);
+
+        let (n1, n2) = if hit.entering { (1.0, material.refractive_index) } else { (material.refractive_index, 1.0) };
+
+        let mirror = if reflectivity > 0.0 { optics::schlick(reflectivity, cos_i) } else { 0.0 };
+        let (fresnel, refracted) = if transparency > 0.0 {
+            match optics::refract(&direction, &normal, n1 / n2) {
+                Some(refracted) => (optics::fresnel(cos_i, n1, n2), Some(refracted)),
+                None => (1.0, None),
+            }
+        } else {
+            (0.0, None)
+        };
+
+        let opacity = 1.0 - transparency;
+        let reflected_weight = opacity * mirror + transparency * fresnel;
+        let refracted_weight = transparency * (1.0 - fresnel);
+
+        let mut color = local * (opacity * (1.0 - mirror));
+
+        if reflected_weight > 0.0 {
+            color = color + self.trace(&hit.pos, &optics::reflect(&direction, &normal), depth - 1) * reflected_weight;
+        }
+
+        if let Some(refracted) = refracted {
+            color = color + self.trace(&hit.pos, &refracted, depth - 1) * refracted_weight;
+        }
+
+        color
+    }
+
+
+    /// Color seen along the ray from `origin` in `direction`, the background when it hits nothing.
+    fn trace(&self, origin: &Point3<f32>, direction: &Vector3<f32>, depth: u32) -> Color {
+        let direction = direction.normalize();
+
+        match self.ray_hit(origin, &direction) {
+            Some(hit) => self.shade(&hit, &direction, &-direction, depth),
+            None => self.environment_radiance(&direction).unwrap_or(self.background.color(0.5 * (1.0 - direction.y))),
+        }
+    }
+
+
+    /// First surface met by the ray `origin + t * direction` with `t > RAY_BIAS`. Secondary
+    /// rays see every shape as a solid and ignore the near and far clipping distances.
+    fn ray_hit(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Option<Hit> {
+        let mut nearest: Option<(f32, ObjectId, Surface, bool)> = None;
+
+        self.bvh.traverse(origin, direction, RAY_BIAS, f32::INFINITY, |item| {
+            let hit = match self.shapes.get(item) {
+                Some(shape) => shape.ray_spans(&self.ellipses, origin, direction).into_iter()
+                    .find_map(|span| front_point(span.start, span.end, span.entry, span.exit))
+                    .map(|(t, (index, surface), entering)| (t, ObjectId::Ellipse(index), surface, entering)),
+                None => {
+                    let object = self.item_object(item)?;
+                    self.hittable(object).ray_spans(origin, direction).into_iter()
+                        .find_map(|span| front_point(span.start, span.end, span.entry, span.exit))
+                        .map(|(t, surface, entering)| (t, object, surface, entering))
+                }
+            }?;
+
+            if nearest.is_none_or(|(t, ..)| hit.0 < t) {
+                nearest = Some(hit);
+            }
+
+            Some(hit.0)
+        });
+
+        nearest.map(|(t, object, surface, entering)| Hit { object, pos: origin + direction * t, surface, entering })
+    }
+
+
+
+    /// Phong shading of the surface by the ambient light and every light, or by a headlight
+    /// at the camera when the scene has no lights.
+    fn color_calculate(&self, object: ObjectId, pos: &Point3<f32>, surface: Surface, to_viewer: &Vector3<f32>) -> Color {
+        let material = self.material(object);
+        let normal = surface_normal(self.hittable(object), pos, surface);
+        let base = self.object_color(object, pos);
+
+        let mut color = material.emissive + base * (material.ambient * self.ambient);
+
+        if let Some(environment) = &self.environment {
+            let reflected = optics::reflect(&-to_viewer.normalize(), &normal);
+            let diffuse = environment.diffuse(&self.environment_direction(&normal));
+            let glossy = environment.glossy(&self.environment_direction(&reflected), material.shininess);
+
+            color = color + (base * diffuse * material.diffuse + glossy * material.specular) * self.environment_intensity;
+        }
+
+        if self.lights.is_empty() {
+            return color + material.direct_light(base, &normal, to_viewer, to_viewer);
+        }
+
+        self.lights.iter().fold(color, |result, light| {
+            let to_light = light.position - pos;
+
+            if normal.dot(&to_light) <= 0.0 {
+                return result;
+            }
+
+            let direct = material.direct_light(base, &normal, &to_light, to_viewer);
+            result + direct * light.color * (light.intensity * self.light_visibility(object, pos, light))
+        })
+    }
+
+
+    /// Fraction of shadow rays from `pos` on `object` reaching the light unblocked
+    /// by other objects.
+    fn light_visibility(&self, object: ObjectId, pos: &Point3<f32>, light: &Light) -> f32 {
+        let samples = match self.shadow_mode {
+            ShadowMode::Off => return 1.0,
+            ShadowMode::Hard => light.sample_points(pos, 1, 0.0),
+            // Turning the pattern from point to point trades banding for noise.
+            ShadowMode::Soft { samples } => light.sample_points(pos, samples, pattern_rotation(pos)),
+        };
+
+        let unblocked = samples.iter()
+            .filter(|target| {
+                let direction = *target - pos;
+                let mut blocked = false;
+
+                // Once blocked, the empty range left skips the rest of the tree.
+                self.bvh.traverse(pos, &direction, SHADOW_BIAS, 1.0, |item| {
+                    blocked = blocked || self.blocks_shadow_ray(item, object, pos, &direction);
+
+                    blocked.then_some(f32::NEG_INFINITY)
+                });
+
+                !blocked
+            })
+            .count();
+
+        unblocked as f32 / samples.len() as f32
+    }
+
+
+    /// Light of the environment arriving from world `direction`, `None` without an environment.
+    fn environment_radiance(&self, direction: &Vector3<f32>) -> Option<Color> {
+        self.environment.as_ref()
+            .map(|environment| environment.radiance(&self.environment_direction(direction)) * self.environment_intensity)
+    }
+
+
+    /// World `direction` in the frame of the turned environment.
+    fn environment_direction(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        Rotation3::from_axis_angle(&Vector3::y_axis(), -self.environment_rotation) * direction
+    }
+
+
+    fn hittable(&self, object: ObjectId) -> &dyn Hittable {
+        match object {
+            ObjectId::Ellipse(index) => &self.ellipses[index],
+            ObjectId::Primitive(index) => &self.primitives[index],
+            ObjectId::Glyph(index) => &self.glyphs[index].ellipse,
+        }
+    }
+
+
+    /// Boxes of the items of the BVH: every shape, then every primitive, then every glyph.
+    fn item_bounds(&self) -> Vec<Option<Aabb>> {
+        self.shapes.iter().map(|shape| Some(shape.bounds(&self.ellipses)))
+            .chain(self.primitives.iter().map(|primitive| primitive.bounds()))
+            .chain(self.glyphs.iter().map(|glyph| glyph.ellipse.bounds()))
+            .collect()
+    }
+
+
+    /// Whether the solid of BVH item `item` lies on the ray `pos + t * direction` with `t` in
+    /// `(SHADOW_BIAS, 1)`. Shapes are tested as a whole, so removed parts cast no shadow. On
+    /// the solid holding `object` the span starting at the shading point is skipped.
+    fn blocks_shadow_ray(&self, item: usize, object: ObjectId, pos: &Point3<f32>, direction: &Vector3<f32>) -> bool {
+        let (spans, own): (Vec<(f32, f32)>, bool) = match self.shapes.get(item) {
+            Some(shape) => (
+                shape.ray_spans(&self.ellipses, pos, direction).iter().map(|span| (span.start, span.end)).collect(),
+                matches!(object, ObjectId::Ellipse(index) if shape.objects().contains(&index)),
+            ),
+            None => match self.item_object(item) {
+                Some(other) => (
+                    self.hittable(other).ray_spans(pos, direction).iter().map(|span| (span.start, span.end)).collect(),
+                    other == object,
+                ),
+                None => return false,
+            },
+        };
+
+        spans.iter().any(|(start, end)| *end > SHADOW_BIAS && *start < 1.0 && !(own && *start <= SHADOW_BIAS))
+    }
+
+
+    /// Primitive or glyph of BVH item `item` past the shapes, `None` when the tree is older
+    /// than the scene.
+    fn item_object(&self, item: usize) -> Option<ObjectId> {
+        let index = item.checked_sub(self.shapes.len())?;
+
+        if index < self.primitives.len() {
+            Some(ObjectId::Primitive(index))
+        } else {
+            let index = index - self.primitives.len();
+            (index < self.glyphs.len()).then_some(ObjectId::Glyph(index))
+        }
+    }
+
+
+    fn material(&self, object: ObjectId) -> &Material {
+        let index = match object {
+            ObjectId::Ellipse(index) => self.ellipses[index].material,
+            ObjectId::Primitive(index) => self.primitives[index].material,
+            ObjectId::Glyph(_) => return &self.glyph_material,
+        };
+
+        &self.materials[index.min(self.materials.len() - 1)]
+    }
+
+
+    /// Base color of `object` at `pos`, before lighting.
+    fn object_color(&self, object: ObjectId, pos: &Point3<f32>) -> Color {
+        let overlapping = self.highlight_overlaps && self.contacts.iter()
+            .any(|(first, second, contact)| {
+                contact.overlapping && (object == ObjectId::Ellipse(*first) || object == ObjectId::Ellipse(*second))
+            });
+
+        if overlapping {
+            let (r, g, b) = OVERLAP_COLOR;
+            Color::from_rgb(r, g, b)
+        } else if let ObjectId::Glyph(index) = object {
+            self.glyphs[index].color
+        } else {
+            let (u, v) = self.hittable(object).surface_uv(pos);
+            self.material(object).base_color(u, v)
+        }
+    }
+
+
+    /// Intersection curves of every pair of objects.
+    fn all_intersection_curves(&self) -> Vec<Polyline> {
+        const RESOLUTION: usize = 64;
+
+        let mut result = Vec::new();
+
+        for (i, first) in self.ellipses.iter().enumerate() {
+            for second in self.ellipses.iter().skip(i + 1) {
+                if first.overlaps(second) {
+                    result.extend(first.intersection_curves(second, RESOLUTION));
+                }
+            }
+        }
+
+        result
+    }
+
+
+    fn update_contacts(&mut self) {
+        self.contacts.clear();
+
+        for (i, first) in self.ellipses.iter().enumerate() {
+            for (j, second) in self.ellipses.iter().enumerate().skip(i + 1) {
+                self.contacts.push((i, j, first.contact(second)));
+            }
+        }
+    }
+
+
+    /// Renders all passes down to single pixel blocks and saves the result as PNG.
+    pub fn save_png(&mut self, path: &Path) -> Result<(), png::EncodingError> {
+        self.render_all_passes();
+
+        self.canvas.save_png(path)
+    }
+
+
+    /// Saves the outlines and other curves selected in `options` as SVG, over the fully
+    /// rendered image when it is included.
+    pub fn export_svg(&mut self, path: &Path, options: &SvgOptions) -> io::Result<()> {
+        // Points exactly on a clipping plane belong to the cut.
+        const PLANE_TOLERANCE: f32 = 1e-3;
+
+        let mut document = SvgDocument::new(self.canvas.get_width(), self.canvas.get_height());
+
+        if options.raster {
+            self.render_all_passes();
+
+            let mut png = Vec::new();
+            self.canvas.write_png(&mut png).map_err(io::Error::other)?;
+            document.add_image(&png);
+        }
+
+        let near_z = self.camera.near_z();
+        let far_z = self.camera.far_z();
+        let near_plane = (self.camera.near > 0.0).then(|| ClipPlane::new(&-Vector3::z(), -near_z));
+
+        // Parts of operands cut away by their shape are left out like clipped ones.
+        let visible = |shape: &CsgNode, index: usize, point: &Point3<f32>| {
+            point.z >= near_z - PLANE_TOLERANCE
+                && point.z <= far_z + PLANE_TOLERANCE
+                && self.ellipses[index].clip_planes.iter().all(|plane| plane.signed_distance(point) <= PLANE_TOLERANCE)
+                && shape.surface_contains(&self.ellipses, index, point)
+                && !(options.hidden_line_removal && self.is_occluded(point, near_z, far_z))
+        };
+
+        svg::draw_curves(&mut document, &self.ellipses, &self.shapes, &self.camera, options, near_plane, visible);
+
+        document.save(path)
+    }
+
+
+    fn render_all_passes(&mut self) {
+        self.reset_blocks_size();
+
+        while self.cur_block_size > 1 {
+            self.update();
+        }
+        self.update();
+    }
+
+
+    /// Saves the ellipsoids and quadric primitives as one triangle mesh, the format follows the
+    /// file extension. See `export_mesh` for what is left out.
+    pub fn export_mesh(&self, path: &Path, tessellation: Tessellation) -> io::Result<()> {
+        export_mesh(path, &self.ellipses, &self.primitives, tessellation)
     }
 
 
@@ -109,44 +890,489 @@ impl Scene {
     }
 
 
-    pub fn rotate_ellipse(&mut self, x: f32, y:f32, z: f32) {
-        self.ellipse.rotation.x += x;
-        self.ellipse.rotation.y += y;
-        self.ellipse.rotation.z += z;
+    /// Adds a unit sphere next to the selected object and selects it.
+    pub fn add_ellipse(&mut self) {
+        let position = self.ellipses.get(self.selected)
+            .map_or(Point3::origin(), |ellipse| ellipse.position + Vector3::x());
+
+        let material = self.ellipses.len() % self.materials.len();
 
+        self.ellipses.push(Ellipse::new(1.0, 1.0, 1.0, &position, material));
+        self.selected = self.ellipses.len() - 1;
+        self.shapes.push(CsgNode::Object(self.selected));
         self.reset_blocks_size();
     }
 
-    pub fn set_ellipsoid_a(&mut self, a: f32) {
-        self.ellipse.set_a(a);
+    /// Replaces the point cloud, returns the number of points read.
+    pub fn load_points(&mut self, path: &Path) -> Result<usize, PointCloudError> {
+        self.points = PointCloud::load(path)?.points;
         self.reset_blocks_size();
+
+        Ok(self.points.len())
     }
 
-    pub fn set_ellipsoid_b(&mut self, b: f32) {
-        self.ellipse.set_b(b);
-        self.reset_blocks_size();
+    /// Replaces the splat cloud and fits it to the view, returns the number of splats read.
+    pub fn load_splats(&mut self, path: &Path) -> Result<usize, SplatError> {
+        let cloud = SplatCloud::load(path)?;
+        let count = cloud.splats.len();
+
+        self.splats = Some(cloud);
+        self.reset_splat_view();
+
+        Ok(count)
     }
 
-    pub fn set_ellipsoid_c(&mut self, c: f32) {
-        self.ellipse.set_c(c);
+    /// Scales and moves the splat cloud so most of it fills the middle of the view, unturned.
+    pub fn reset_splat_view(&mut self) {
+        let (center, radius) = self.splats.as_ref().map_or((Point3::origin(), 1.0), SplatCloud::bounding_sphere);
+
+        self.splat_center = center;
+        self.splat_scale = 0.4 * self.camera.viewport_width.min(self.camera.viewport_height) / radius;
+        self.splat_view = SplatView::default();
         self.reset_blocks_size();
     }
 
-    pub fn set_ellipsoid_scale(&mut self, scale: f32) {
-        self.ellipse.set_scale(scale);
-        self.reset_blocks_size();
+    pub fn splat_view(&self) -> SplatView {
+        self.splat_view
     }
 
-    pub fn move_ellipse(&mut self, vec: &Vector3<f32>) {
-        self.ellipse.position += vec;
+    pub fn set_splat_view(&mut self, view: SplatView) {
+        self.splat_view = view;
         self.reset_blocks_size();
     }
 
-    pub fn set_brightness(&mut self, value: f32) {
-        self.brightness = value;
+    /// Turns the splat cloud around its center, angles are kept within one turn.
+    pub fn rotate_splats(&mut self, x: f32, y: f32, z: f32) {
+        let turn = |angle: f32| (angle + std::f32::consts::PI).rem_euclid(2.0 * std::f32::consts::PI) - std::f32::consts::PI;
+
+        self.splat_view.rotation = (self.splat_view.rotation + Vector3::new(x, y, z)).map(turn);
         self.reset_blocks_size();
     }
 
+    pub fn move_splats(&mut self, vec: &Vector3<f32>) {
+        self.splat_view.offset += vec;
+        self.reset_blocks_size();
+    }
+
+    /// Replaces the tensor field, returns the number of samples read. The glyph options are
+    /// fitted to the new field, keeping the coloring.
+    pub fn load_tensor_field(&mut self, path: &Path) -> Result<usize, TensorFieldError> {
+        let field = TensorField::load(path)?;
+        let samples = field.samples.len();
+
+        self.glyph_options = GlyphOptions {
+            coloring: self.glyph_options.coloring,
+            colormap: self.glyph_options.colormap,
+            ..field.default_options()
+        };
+        self.tensor_field = Some(field);
+        self.update_glyphs();
+
+        Ok(samples)
+    }
+
+    pub fn clear_tensor_field(&mut self) {
+        self.tensor_field = None;
+        self.update_glyphs();
+    }
+
+    pub fn tensor_field(&self) -> Option<&TensorField> {
+        self.tensor_field.as_ref()
+    }
+
+    pub fn glyph_options(&self) -> GlyphOptions {
+        self.glyph_options
+    }
+
+    pub fn set_glyph_options(&mut self, options: GlyphOptions) {
+        self.glyph_options = options;
+        self.update_glyphs();
+    }
+
+    pub fn glyphs(&self) -> &[Glyph] {
+        &self.glyphs
+    }
+
+    fn update_glyphs(&mut self) {
+        self.glyphs = self.tensor_field.as_ref()
+            .map_or(Vec::new(), |field| field.glyphs(&self.glyph_options));
+        self.reset_blocks_size();
+    }
+
+    /// Adds the ellipsoid fitted to the point cloud and selects it, returns `false` when the fit fails.
+    pub fn fit_points(&mut self, method: FitMethod) -> bool {
+        let material = self.ellipses.len() % self.materials.len();
+        let cloud = PointCloud { points: self.points.clone() };
+
+        let Some(ellipse) = cloud.fit(method, material) else {
+            return false;
+        };
+
+        self.ellipses.push(ellipse);
+        self.selected = self.ellipses.len() - 1;
+        self.shapes.push(CsgNode::Object(self.selected));
+        self.reset_blocks_size();
+
+        true
+    }
+
+    pub fn set_points_style(&mut self, show: bool, size: f32, color: Color) {
+        self.show_points = show;
+        self.point_size = size;
+        self.point_color = color;
+        self.reset_blocks_size();
+    }
+
+    /// Removes the selected object, the last one is never removed.
+    pub fn remove_selected(&mut self) {
+        if self.ellipses.len() <= 1 {
+            return;
+        }
+
+        self.ellipses.remove(self.selected);
+        self.shapes = self.shapes.iter()
+            .flat_map(|shape| {
+                let (tree, orphans) = shape.without_object(self.selected);
+                tree.into_iter().chain(orphans.into_iter().map(CsgNode::Object))
+            })
+            .collect();
+        self.selected = usize::min(self.selected, self.ellipses.len() - 1);
+        self.reset_blocks_size();
+    }
+
+    pub fn shapes(&self) -> &[CsgNode] {
+        &self.shapes
+    }
+
+    /// Replaces shapes `first` and `second` with their combination.
+    pub fn combine_shapes(&mut self, first: usize, second: usize, operation: CsgOperation) {
+        if first == second || first >= self.shapes.len() || second >= self.shapes.len() {
+            return;
+        }
+
+        let left = self.shapes[first].clone();
+        let right = self.shapes[second].clone();
+
+        self.shapes[first] = CsgNode::operation(operation, left, right);
+        self.shapes.remove(second);
+        self.reset_blocks_size();
+    }
+
+    /// Splits a shape into its two operands.
+    pub fn ungroup_shape(&mut self, index: usize) {
+        if let Some(CsgNode::Operation { left, right, .. }) = self.shapes.get(index).cloned() {
+            self.shapes[index] = *left;
+            self.shapes.insert(index + 1, *right);
+            self.reset_blocks_size();
+        }
+    }
+
+    /// Changes operation of the node at `path` inside shape `index`.
+    pub fn set_shape_operation(&mut self, index: usize, path: &[usize], new_operation: CsgOperation) {
+        if let Some(CsgNode::Operation { operation, .. }) = self.shapes.get_mut(index).and_then(|shape| shape.node_mut(path)) {
+            *operation = new_operation;
+            self.reset_blocks_size();
+        }
+    }
+
+    /// Swaps operands of the node at `path` inside shape `index`.
+    pub fn swap_shape_operands(&mut self, index: usize, path: &[usize]) {
+        if let Some(CsgNode::Operation { left, right, .. }) = self.shapes.get_mut(index).and_then(|shape| shape.node_mut(path)) {
+            std::mem::swap(left, right);
+            self.reset_blocks_size();
+        }
+    }
+
+    pub fn save_scene(&self, path: &Path) -> Result<(), SceneFileError> {
+        scene_file::save(path, &self.ellipses, &self.primitives, &self.shapes, &self.lights, &self.materials)
+    }
+
+    pub fn load_scene(&mut self, path: &Path) -> Result<(), SceneFileError> {
+        let description = scene_file::load(path)?;
+
+        if description.ellipses.is_empty() {
+            return Err(SceneFileError::Parse { line: 0, message: String::from("scene has no ellipsoids") });
+        }
+
+        self.ellipses = description.ellipses;
+        self.shapes = description.shapes;
+        self.primitives = description.primitives;
+        self.lights = description.lights;
+        self.materials = if description.materials.is_empty() { default_materials() } else { description.materials };
+        self.selected = 0;
+        self.reset_blocks_size();
+
+        Ok(())
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.ellipses.len() {
+            self.selected = index;
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_ellipse(&self) -> &Ellipse {
+        &self.ellipses[self.selected]
+    }
+
+    fn selected_ellipse_mut(&mut self) -> &mut Ellipse {
+        self.reset_blocks_size();
+        &mut self.ellipses[self.selected]
+    }
+
+    pub fn rotate_ellipse(&mut self, x: f32, y:f32, z: f32) {
+        let ellipse = self.selected_ellipse_mut();
+        ellipse.rotation.x += x;
+        ellipse.rotation.y += y;
+        ellipse.rotation.z += z;
+    }
+
+    pub fn set_ellipsoid_a(&mut self, a: f32) {
+        self.selected_ellipse_mut().set_a(a);
+    }
+
+    pub fn set_ellipsoid_b(&mut self, b: f32) {
+        self.selected_ellipse_mut().set_b(b);
+    }
+
+    pub fn set_ellipsoid_c(&mut self, c: f32) {
+        self.selected_ellipse_mut().set_c(c);
+    }
+
+    pub fn set_ellipsoid_scale(&mut self, scale: f32) {
+        self.selected_ellipse_mut().set_scale(scale);
+    }
+
+    pub fn move_ellipse(&mut self, vec: &Vector3<f32>) {
+        self.selected_ellipse_mut().position += vec;
+    }
+
+    /// Replaces the shape of the selected object by the confidence ellipsoid of a Gaussian,
+    /// keeping its material and clipping planes. Returns `false` when `covariance` is not positive definite.
+    pub fn set_ellipsoid_covariance(&mut self, mean: &Point3<f32>, covariance: &Matrix3<f32>, confidence: f32) -> bool {
+        let selected = self.selected_ellipse_mut();
+
+        let Some(mut ellipse) = Ellipse::from_covariance(mean, covariance, confidence, selected.material) else {
+            return false;
+        };
+
+        ellipse.density = selected.density;
+        ellipse.absorption = selected.absorption;
+        ellipse.clip_planes = std::mem::take(&mut selected.clip_planes);
+        *selected = ellipse;
+
+        true
+    }
+
+    pub fn set_ellipsoid_density(&mut self, density: f32) {
+        self.selected_ellipse_mut().density = density;
+    }
+
+    pub fn set_ellipsoid_absorption(&mut self, absorption: Color) {
+        self.selected_ellipse_mut().absorption = absorption;
+    }
+
+    pub fn primitives(&self) -> &[Primitive] {
+        &self.primitives
+    }
+
+
+    pub fn set_primitives(&mut self, primitives: Vec<Primitive>) {
+        self.primitives = primitives;
+        self.reset_blocks_size();
+    }
+
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+
+    pub fn set_lights(&mut self, lights: Vec<Light>) {
+        self.lights = lights;
+        self.reset_blocks_size();
+    }
+
+
+    pub fn set_shadow_mode(&mut self, mode: ShadowMode) {
+        self.shadow_mode = mode;
+        self.reset_blocks_size();
+    }
+
+
+    pub fn set_max_depth(&mut self, value: u32) {
+        self.max_depth = value;
+        self.reset_blocks_size();
+    }
+
+
+    pub fn set_ellipsoid_material(&mut self, index: usize) {
+        if index < self.materials.len() {
+            self.selected_ellipse_mut().material = index;
+        }
+    }
+
+
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+
+    /// Replaces the material library, objects keep their material indices.
+    pub fn set_materials(&mut self, materials: Vec<Material>) {
+        if !materials.is_empty() {
+            self.materials = materials;
+            self.reset_blocks_size();
+        }
+    }
+
+
+    /// Removes a material, objects using it get the first material. The last one is never removed.
+    pub fn remove_material(&mut self, index: usize) {
+        if self.materials.len() <= 1 || index >= self.materials.len() {
+            return;
+        }
+
+        self.materials.remove(index);
+
+        let used = self.ellipses.iter_mut().map(|ellipse| &mut ellipse.material)
+            .chain(self.primitives.iter_mut().map(|primitive| &mut primitive.material));
+
+        for material in used {
+            if *material == index {
+                *material = 0;
+            } else if *material > index {
+                *material -= 1;
+            }
+        }
+
+        self.reset_blocks_size();
+    }
+
+
+    /// Loads a PNG image as the texture of material `index`.
+    pub fn load_material_texture(&mut self, index: usize, path: &Path) -> Result<(), png::DecodingError> {
+        let texture = Texture::load(path)?;
+
+        if let Some(material) = self.materials.get_mut(index) {
+            material.texture = Some(texture);
+            self.reset_blocks_size();
+        }
+
+        Ok(())
+    }
+
+
+    pub fn set_ambient(&mut self, value: f32) {
+        self.ambient = value;
+        self.reset_blocks_size();
+    }
+
+
+    /// Near and far clipping distances measured from the camera.
+    pub fn set_clip_distances(&mut self, near: f32, far: f32) {
+        self.camera.near = near;
+        self.camera.far = far;
+        self.reset_blocks_size();
+    }
+
+    /// Replaces clipping planes of the selected object.
+    pub fn set_clip_planes(&mut self, planes: Vec<ClipPlane>) {
+        self.selected_ellipse_mut().clip_planes = planes;
+    }
+
+    pub fn set_cut_mode(&mut self, mode: CutMode) {
+        self.cut_mode = mode;
+        self.reset_blocks_size();
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+        self.reset_blocks_size();
+    }
+
+    pub fn set_environment(&mut self, environment: Option<Environment>) {
+        self.environment = environment;
+        self.reset_blocks_size();
+    }
+
+    /// Loads an equirectangular PNG or Radiance HDR image as the environment.
+    pub fn load_environment(&mut self, path: &Path) -> Result<(), EnvironmentError> {
+        self.set_environment(Some(Environment::load(path)?));
+        Ok(())
+    }
+
+    pub fn set_environment_rotation(&mut self, angle: f32) {
+        self.environment_rotation = angle;
+        self.reset_blocks_size();
+    }
+
+    pub fn set_environment_intensity(&mut self, value: f32) {
+        self.environment_intensity = value;
+        self.reset_blocks_size();
+    }
+
+    /// Exposure in stops applied before tone mapping.
+    pub fn set_exposure(&mut self, value: f32) {
+        self.exposure = value;
+        self.reset_blocks_size();
+    }
+
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_mapping = tone_mapping;
+        self.reset_blocks_size();
+    }
+
+    pub fn set_shading_mode(&mut self, mode: ShadingMode) {
+        self.shading_mode = mode;
+        self.observed_curvature_range = None;
+        self.reset_blocks_size();
+    }
+
+    pub fn set_colormap(&mut self, colormap: Colormap) {
+        self.colormap = colormap;
+        self.reset_blocks_size();
+    }
+
+    pub fn set_curvature_range(&mut self, min: f32, max: f32) {
+        self.curvature_range = (min, max);
+        self.reset_blocks_size();
+    }
+
+    /// Smallest and largest curvature drawn in the last rendering pass.
+    pub fn observed_curvature_range(&self) -> Option<(f32, f32)> {
+        self.observed_curvature_range
+    }
+
+    pub fn set_highlight_overlaps(&mut self, value: bool) {
+        self.highlight_overlaps = value;
+        self.reset_blocks_size();
+    }
+
+    /// Relations of all pairs of objects, empty unless overlaps are highlighted.
+    pub fn contacts(&self) -> &[(usize, usize, Contact)] {
+        if self.highlight_overlaps { &self.contacts } else { &[] }
+    }
+
+    pub fn set_intersection_style(&mut self, show: bool, width: f32, color: Color) {
+        self.show_intersections = show;
+        self.curve_width = width;
+        self.curve_color = color;
+        self.reset_blocks_size();
+    }
+
+    /// Saves the intersection curves of all pairs of objects as CSV.
+    pub fn export_intersection_curves(&self, path: &Path) -> io::Result<usize> {
+        let curves = self.all_intersection_curves();
+        save_curves(path, &curves)?;
+
+        Ok(curves.len())
+    }
+
     pub fn set_max_block_size(&mut self, value: u32) {
         self.max_block_size = value;
         self.reset_blocks_size();
@@ -154,9 +1380,180 @@ impl Scene {
 }
 
 
+fn default_materials() -> Vec<Material> {
+    OBJECT_COLORS.iter()
+        .map(|(name, (r, g, b))| Material::new(name, Color::from_rgb(*r, *g, *b)))
+        .collect()
+}
+
+
+/// Pseudo-random angle derived from the position.
+fn pattern_rotation(pos: &Point3<f32>) -> f32 {
+    let hash = (pos.coords.dot(&Vector3::new(12.9898, 78.233, 37.719)).sin() * 43758.547).fract();
+
+    hash * 2.0 * std::f32::consts::PI
+}
+
+
+/// First point of a span in front of a ray origin with the surface seen there and whether
+/// the ray enters the object, the end of the span when the origin lies inside.
+fn front_point<S>(start: f32, end: f32, entry: S, exit: S) -> Option<(f32, S, bool)> {
+    if start > RAY_BIAS {
+        Some((start, entry, true))
+    } else if end > RAY_BIAS {
+        Some((end, exit, false))
+    } else {
+        None
+    }
+}
+
+
+/// Closest of the hits `hit_item` finds on the items of `bvh` along +z from `origin`
+/// between `near_z` and `far_z`.
+fn nearest_item_hit(
+    bvh: &Bvh,
+    origin: &Point3<f32>,
+    near_z: f32,
+    far_z: f32,
+    hit_item: impl Fn(usize) -> Option<(ObjectId, f32, Surface)>
+) -> Option<(ObjectId, f32, Surface)> {
+    let mut nearest: Option<(ObjectId, f32, Surface)> = None;
+
+    bvh.traverse(origin, &Vector3::z(), near_z, far_z, |item| {
+        let hit = hit_item(item)?;
+
+        if nearest.is_none_or(|(_, z, _)| hit.1 < z) {
+            nearest = Some(hit);
+        }
+
+        Some(hit.1)
+    });
+
+    nearest
+}
+
+
+/// Sum of the optical depths `item_depth` gives for the items of `bvh` along +z from `origin`
+/// between `near_z` and `far_z`.
+fn xray_optical_depth(bvh: &Bvh, origin: &Point3<f32>, near_z: f32, far_z: f32, item_depth: impl Fn(usize) -> Color) -> Color {
+    let mut optical_depth = Color::from_rgb_f(0.0, 0.0, 0.0);
+
+    // Every item counts, so none is reported as hit to keep the ones behind it.
+    bvh.traverse(origin, &Vector3::z(), near_z, far_z, |item| {
+        optical_depth = optical_depth + item_depth(item);
+        None
+    });
+
+    optical_depth
+}
+
+
+/// Optical depth of the parts of `object` along +z from `origin` between `near_z` and `far_z`.
+fn spans_optical_depth(object: &dyn Hittable, density: f32, absorption: Color, origin: &Point3<f32>, near_z: f32, far_z: f32) -> Color {
+    let length: f32 = object.ray_spans(origin, &Vector3::z()).iter()
+        .map(|span| (span.end.min(far_z) - span.start.max(near_z)).max(0.0))
+        .sum();
+
+    absorption * (density * length)
+}
+
+
+/// `backlight` seen through `optical_depth` following the Beer-Lambert law, a transparent
+/// background becomes black with the absorbed fraction as alpha.
+fn xray_backlight(backlight: Color, optical_depth: Color) -> Color {
+    let transmittance = optical_depth.map(|depth| (-depth).exp());
+
+    if backlight.alpha() == 0.0 {
+        return Color::from_rgb_f(0.0, 0.0, 0.0).with_alpha(1.0 - transmittance.luminance());
+    }
+
+    backlight * transmittance
+}
+
+
+fn surface_normal(object: &dyn Hittable, pos: &Point3<f32>, surface: Surface) -> UnitVector3<f32> {
+    match surface {
+        Surface::Outer => object.normal(pos),
+        Surface::Inner => -object.normal(pos),
+        Surface::Cap(normal) => normal,
+    }
+}
+
+
+/// Tessellates every ellipsoid, sphere, disc and bounded plane and saves them together as one
+/// mesh file. Objects are written whole, ignoring CSG shapes and clip planes, and the other
+/// primitives are left out.
+pub fn export_mesh(path: &Path, ellipses: &[Ellipse], primitives: &[Primitive], tessellation: Tessellation) -> io::Result<()> {
+    let mut mesh = Mesh::default();
+
+    for ellipse in ellipses {
+        mesh.append(ellipse.tessellate(tessellation));
+    }
+
+    for primitive in primitives {
+        if let Some(primitive_mesh) = primitive.tessellate(tessellation) {
+            mesh.append(primitive_mesh);
+        }
+    }
+
+    mesh.save(path)
+}
+
+
 #[cfg(test)]
 mod various_tests {
+    use super::*;
+
+
+    #[test]
+    fn pick_finds_nearest_of_overlapping_objects() {
+        // Three balls on the line of sight, listed out of depth order, the middle one largest.
+        let primitives = [
+            Primitive::new(PrimitiveKind::Sphere { center: Point3::new(0.0, 0.0, 6.0), radius: 1.0 }, 0),
+            Primitive::new(PrimitiveKind::Sphere { center: Point3::new(0.0, 0.0, 2.0), radius: 1.0 }, 0),
+            Primitive::new(PrimitiveKind::Sphere { center: Point3::new(0.0, 0.0, 4.0), radius: 2.5 }, 0),
+        ];
+        let bvh = Bvh::new(primitives.iter().map(|primitive| primitive.bounds()).collect());
+        let origin = Point3::origin();
+
+        let nearest = |near_z: f32| {
+            let hit_item = |item: usize| primitives[item].ray_span(&origin, &Vector3::z())
+                .filter(|span| span.start >= near_z)
+                .map(|span| (ObjectId::Primitive(item), span.start, span.entry));
+
+            nearest_item_hit(&bvh, &origin, near_z, 100.0, hit_item).map(|(object, z, _)| (object, z))
+        };
+
+        assert_eq!(Some((ObjectId::Primitive(1), 1.0)), nearest(0.0));
+        // Past the front of the nearest ball the large one overlapping it is in front.
+        assert_eq!(Some((ObjectId::Primitive(2), 1.5)), nearest(1.2));
+    }
+
+
+    #[test]
+    fn xray_shows_primitives() {
+        let primitive = Primitive::new(PrimitiveKind::Sphere { center: Point3::new(0.0, 0.0, 5.0), radius: 1.0 }, 0);
+        let bvh = Bvh::new(vec![primitive.bounds()]);
+        let background = Color::from_rgb_f(1.0, 1.0, 1.0);
+
+        let color = |x: f32| {
+            let origin = Point3::new(x, 0.0, 0.0);
+            let optical_depth = xray_optical_depth(&bvh, &origin, 0.0, 10.0, |_| {
+                spans_optical_depth(&primitive, primitive.density, primitive.absorption, &origin, 0.0, 10.0)
+            });
+
+            xray_backlight(background, optical_depth)
+        };
+
+        assert_ne!(background, color(0.0));
+        // Density 0.5 over the diameter of 2 gives an optical depth of 1.
+        assert!((color(0.0).luminance() - (-1.0_f32).exp()).abs() < 1e-3);
+        assert_eq!(background, color(2.0));
+    }
+
+
     #[test]
+    #[allow(clippy::bool_assert_comparison, clippy::identity_op)]
     fn modula_test() {
         assert_eq!(false, 0 % 3 == 1);
         assert_eq!(true,  1 % 3 == 1);
@@ -1,7 +1,9 @@
 extern crate nalgebra as na;
 
-use ellipsoid_ray_casting::Scene;
-use na::{Point2, Vector3};
+use std::path::Path;
+
+use ellipsoid_ray_casting::{scene_file, Color, Scene, ShadingMode, Tessellation};
+use na::{Matrix3, Point2, Point3, Vector3};
 use winit::{
     event::{ElementState, Event, MouseButton, WindowEvent}, event_loop::{ControlFlow, EventLoop}, window::WindowBuilder
 };
@@ -11,10 +13,33 @@ use winit::dpi::LogicalSize;
 mod ui;
 
 
+use ui::{CsgCommand, EnvironmentMode};
+
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.iter().any(|arg| arg == "--export-mesh") {
+        if let Err(message) = export_mesh_from_args(&args) {
+            eprintln!("{}", message);
+            eprintln!("Usage: ellipsoid_ray_casting <scene> --export-mesh <out.obj|stl|ply> [--latitudes N] [--longitudes M] [--tolerance T]");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let (scene_path, background) = match window_args(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{}", message);
+            eprintln!("Usage: ellipsoid_ray_casting [scene] [--background <#rrggbb|color name>]");
+            std::process::exit(1);
+        }
+    };
+
     let event_loop = EventLoop::new();
     let window = {
-        let size = LogicalSize::new(600 as f64, 600 as f64);
+        let size = LogicalSize::new(600_f64, 600_f64);
         WindowBuilder::new()
             .with_title("Ellipsoid ray casting")
             .with_inner_size(size)
@@ -24,11 +49,34 @@ fn main() {
     };
 
     let mut scene = Scene::new(&window);
-    scene.update();
-    
+
     let window_size = window.inner_size();
     let mut gui = ui::Gui::new(&event_loop, window_size.width, window_size.height, window.scale_factor() as f32, scene.canvas.pixels());
 
+    if let Some(color) = background {
+        gui.state.set_background_color(color);
+    }
+
+    // Optional scene file given as an argument.
+    if let Some(path) = scene_path {
+        match scene.load_scene(Path::new(&path)) {
+            Ok(()) => {
+                gui.state.load_object(scene.selected_ellipse());
+                gui.state.lights = scene.lights().to_vec();
+                gui.state.old_lights = gui.state.lights.clone();
+                gui.state.primitives = scene.primitives().to_vec();
+                gui.state.old_primitives = gui.state.primitives.clone();
+            }
+            Err(err) => eprintln!("Cannot load scene {}: {}", path, err),
+        }
+        gui.state.scene_path = path;
+    }
+
+    gui.state.materials = scene.materials().to_vec();
+    gui.state.old_materials = gui.state.materials.clone();
+
+    scene.update();
+
     let mut mouse_left_pressed = false;
     let mut mouse_middle_presed = false;
     let mut cur_mouse_pos = Point2::origin();
@@ -79,12 +127,25 @@ fn main() {
         
                         let mouse_move_vec = cur_mouse_pos - prev_mouse_pos;
         
+                        // In splat mode the drags turn and move the splat cloud instead of the selected ellipsoid.
+                        let splats = gui.state.shading_mode == ShadingMode::Splats;
+
                         if mouse_left_pressed && !gui.uses_mouse() {
-                            scene.rotate_ellipse(-mouse_move_vec.y as f32 * 0.02, -mouse_move_vec.x as f32 * 0.02, 0.0);
+                            if splats {
+                                scene.rotate_splats(-mouse_move_vec.y as f32 * 0.02, -mouse_move_vec.x as f32 * 0.02, 0.0);
+                            } else {
+                                scene.rotate_ellipse(-mouse_move_vec.y as f32 * 0.02, -mouse_move_vec.x as f32 * 0.02, 0.0);
+                            }
                         }
 
                         if mouse_middle_presed && !gui.uses_mouse() {
-                            scene.move_ellipse(&Vector3::new(mouse_move_vec.x as f32 * 0.001, -mouse_move_vec.y as f32 * 0.001, 0.0))
+                            let offset = Vector3::new(mouse_move_vec.x as f32 * 0.001, -mouse_move_vec.y as f32 * 0.001, 0.0);
+
+                            if splats {
+                                scene.move_splats(&offset);
+                            } else {
+                                scene.move_ellipse(&offset);
+                            }
                         }
                     }
 
@@ -101,6 +162,8 @@ fn main() {
             Event::RedrawRequested(_) => {
                 handle_user_input(&mut scene, &mut gui);
                 scene.update();
+                handle_exports(&mut scene, &mut gui);
+                gui.state.hover = scene.pick(cur_mouse_pos.x as f32, cur_mouse_pos.y as f32);
                 gui.prepare(&window);
 
                 scene.canvas.pixels().render_with(|encoder, render_target, context| {
@@ -118,7 +181,229 @@ fn main() {
 }
 
 
+/// Scene file and background color given when opening the window.
+fn window_args(args: &[String]) -> Result<(Option<String>, Option<Color>), String> {
+    let mut scene_path = None;
+    let mut background = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--background" => {
+                let value = iter.next().ok_or(format!("missing value after {}", arg))?;
+                background = Some(value.parse::<Color>().map_err(|err| format!("invalid --background: {}", err))?);
+            }
+            _ if scene_path.is_none() && !arg.starts_with("--") => scene_path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    Ok((scene_path, background))
+}
+
+
+/// Writes the mesh of a scene file without opening a window.
+fn export_mesh_from_args(args: &[String]) -> Result<(), String> {
+    let mut scene_path = None;
+    let mut mesh_path = None;
+    let mut latitudes = 32;
+    let mut longitudes = 64;
+    let mut tolerance = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or(format!("missing value after {}", arg));
+
+        match arg.as_str() {
+            "--export-mesh" => mesh_path = Some(value()?.clone()),
+            "--latitudes" => latitudes = value()?.parse().map_err(|_| String::from("invalid --latitudes"))?,
+            "--longitudes" => longitudes = value()?.parse().map_err(|_| String::from("invalid --longitudes"))?,
+            "--tolerance" => tolerance = Some(value()?.parse().map_err(|_| String::from("invalid --tolerance"))?),
+            _ if scene_path.is_none() && !arg.starts_with("--") => scene_path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    let scene_path = scene_path.ok_or("missing scene file")?;
+    let mesh_path = mesh_path.ok_or("missing mesh file")?;
+
+    let tessellation = match tolerance {
+        Some(tolerance) => Tessellation::Adaptive { tolerance },
+        None => Tessellation::Uniform { latitudes, longitudes },
+    };
+
+    let description = scene_file::load(Path::new(&scene_path))
+        .map_err(|err| format!("Cannot load scene {}: {}", scene_path, err))?;
+
+    ellipsoid_ray_casting::export_mesh(Path::new(&mesh_path), &description.ellipses, &description.primitives, tessellation)
+        .map_err(|err| format!("Cannot export mesh {}: {}", mesh_path, err))
+}
+
+
 fn handle_user_input(scene: &mut Scene, gui: &mut ui::Gui) {
+    let mut selection_changed = false;
+
+    if gui.state.add_object {
+        scene.add_ellipse();
+        gui.state.add_object = false;
+        selection_changed = true;
+    }
+
+    if gui.state.remove_object {
+        scene.remove_selected();
+        gui.state.remove_object = false;
+        selection_changed = true;
+    }
+
+    if gui.state.old_selected != gui.state.selected {
+        scene.select(gui.state.selected);
+        selection_changed = true;
+    }
+
+    if gui.state.load_scene {
+        gui.state.scene_status = match scene.load_scene(Path::new(&gui.state.scene_path)) {
+            Ok(()) => format!("Loaded {}", gui.state.scene_path),
+            Err(err) => format!("Loading failed: {}", err),
+        };
+        gui.state.load_scene = false;
+        gui.state.lights = scene.lights().to_vec();
+        gui.state.old_lights = gui.state.lights.clone();
+        gui.state.primitives = scene.primitives().to_vec();
+        gui.state.old_primitives = gui.state.primitives.clone();
+        gui.state.materials = scene.materials().to_vec();
+        gui.state.old_materials = gui.state.materials.clone();
+        selection_changed = true;
+    }
+
+    if gui.state.save_scene {
+        gui.state.scene_status = match scene.save_scene(Path::new(&gui.state.scene_path)) {
+            Ok(()) => format!("Saved {}", gui.state.scene_path),
+            Err(err) => format!("Saving failed: {}", err),
+        };
+        gui.state.save_scene = false;
+    }
+
+    if gui.state.load_points {
+        gui.state.points_status = match scene.load_points(Path::new(&gui.state.points_path)) {
+            Ok(count) => format!("Loaded {} points", count),
+            Err(err) => format!("Loading failed: {}", err),
+        };
+        gui.state.load_points = false;
+    }
+
+    if gui.state.fit_points {
+        if scene.fit_points(gui.state.fit_method) {
+            gui.state.points_status = format!("Added ellipsoid {}", scene.selected() + 1);
+            selection_changed = true;
+        } else {
+            gui.state.points_status = String::from("No ellipsoid fits the points");
+        }
+        gui.state.fit_points = false;
+    }
+
+    let points_style = (gui.state.show_points, gui.state.point_size, gui.state.point_color);
+    if gui.state.old_points_style != Some(points_style) {
+        let [r, g, b] = gui.state.point_color;
+        scene.set_points_style(gui.state.show_points, gui.state.point_size, Color::from_rgb(r, g, b));
+        gui.state.old_points_style = Some(points_style);
+    }
+
+    if gui.state.load_splats {
+        match scene.load_splats(Path::new(&gui.state.splats_path)) {
+            Ok(count) => {
+                gui.state.splats_status = format!("Loaded {} splats", count);
+                gui.state.shading_mode = ShadingMode::Splats;
+            }
+            Err(err) => gui.state.splats_status = format!("Loading failed: {}", err),
+        }
+        gui.state.load_splats = false;
+    }
+
+    if gui.state.old_splat_view != gui.state.splat_view {
+        scene.set_splat_view(gui.state.splat_view);
+    }
+
+    if gui.state.reset_splat_view {
+        scene.reset_splat_view();
+        gui.state.reset_splat_view = false;
+    }
+
+    // Mouse drags move the splat cloud directly, the controls show where it ended up.
+    gui.state.splat_view = scene.splat_view();
+    gui.state.old_splat_view = gui.state.splat_view;
+
+    if gui.state.load_tensors {
+        gui.state.tensors_status = match scene.load_tensor_field(Path::new(&gui.state.tensors_path)) {
+            Ok(count) => format!("Loaded {} samples", count),
+            Err(err) => format!("Loading failed: {}", err),
+        };
+        gui.state.trace_limits = scene.tensor_field().map(|field| field.trace_range());
+        gui.state.glyph_options = scene.glyph_options();
+        gui.state.old_glyph_options = gui.state.glyph_options;
+        gui.state.glyph_count = scene.glyphs().len();
+        gui.state.load_tensors = false;
+    }
+
+    if gui.state.clear_tensors {
+        scene.clear_tensor_field();
+        gui.state.trace_limits = None;
+        gui.state.tensors_status.clear();
+        gui.state.glyph_count = 0;
+        gui.state.clear_tensors = false;
+    }
+
+    if gui.state.old_glyph_options != gui.state.glyph_options {
+        scene.set_glyph_options(gui.state.glyph_options);
+        gui.state.old_glyph_options = gui.state.glyph_options;
+        gui.state.glyph_count = scene.glyphs().len();
+    }
+
+    for command in std::mem::take(&mut gui.state.csg_commands) {
+        match command {
+            CsgCommand::Combine { first, second, operation } => scene.combine_shapes(first, second, operation),
+            CsgCommand::Ungroup(shape) => scene.ungroup_shape(shape),
+            CsgCommand::SetOperation { shape, path, operation } => scene.set_shape_operation(shape, &path, operation),
+            CsgCommand::Swap { shape, path } => scene.swap_shape_operands(shape, &path),
+        }
+    }
+
+    gui.state.shapes = scene.shapes().to_vec();
+
+    if gui.state.old_highlight_overlaps != gui.state.highlight_overlaps {
+        scene.set_highlight_overlaps(gui.state.highlight_overlaps);
+        gui.state.old_highlight_overlaps = gui.state.highlight_overlaps;
+    }
+
+    gui.state.contacts = scene.contacts().to_vec();
+
+    let curves_style = (gui.state.show_curves, gui.state.curve_width, gui.state.curve_color);
+    if gui.state.old_curves_style != Some(curves_style) {
+        let [r, g, b] = gui.state.curve_color;
+        scene.set_intersection_style(gui.state.show_curves, gui.state.curve_width, Color::from_rgb(r, g, b));
+        gui.state.old_curves_style = Some(curves_style);
+    }
+
+    if selection_changed {
+        gui.state.load_object(scene.selected_ellipse());
+        gui.state.selected = scene.selected();
+        gui.state.old_selected = scene.selected();
+    }
+
+    gui.state.object_count = scene.ellipses.len();
+
+    if gui.state.apply_covariance {
+        let mean = Point3::from(gui.state.mean);
+        let covariance = Matrix3::from(gui.state.covariance).transpose();
+
+        if scene.set_ellipsoid_covariance(&mean, &covariance, gui.state.confidence) {
+            gui.state.load_object(scene.selected_ellipse());
+            gui.state.covariance_status.clear();
+        } else {
+            gui.state.covariance_status = String::from("Covariance must be symmetric positive definite");
+        }
+        gui.state.apply_covariance = false;
+    }
+
     if gui.state.old_a != gui.state.a {
         scene.set_ellipsoid_a(1.0 / (gui.state.a * gui.state.a));
         gui.state.old_a = gui.state.a;
@@ -134,9 +419,100 @@ fn handle_user_input(scene: &mut Scene, gui: &mut ui::Gui) {
         gui.state.old_c = gui.state.c;
     }
 
-    if gui.state.old_m != gui.state.m {
-        scene.set_brightness(gui.state.m);
-        gui.state.old_m = gui.state.m;
+    if gui.state.old_density != gui.state.density {
+        scene.set_ellipsoid_density(gui.state.density);
+        gui.state.old_density = gui.state.density;
+    }
+
+    if gui.state.old_absorption != gui.state.absorption {
+        let [r, g, b] = gui.state.absorption;
+        scene.set_ellipsoid_absorption(Color::from_rgb(r, g, b));
+        gui.state.old_absorption = gui.state.absorption;
+    }
+
+    if gui.state.old_materials != gui.state.materials {
+        scene.set_materials(gui.state.materials.clone());
+        gui.state.old_materials = gui.state.materials.clone();
+    }
+
+    if let Some(index) = gui.state.remove_material.take() {
+        scene.remove_material(index);
+        gui.state.materials = scene.materials().to_vec();
+        gui.state.old_materials = gui.state.materials.clone();
+        gui.state.load_object(scene.selected_ellipse());
+    }
+
+    if let Some(index) = gui.state.load_texture.take() {
+        gui.state.material_status = match scene.load_material_texture(index, Path::new(&gui.state.texture_path)) {
+            Ok(()) => format!("Loaded {}", gui.state.texture_path),
+            Err(err) => format!("Loading failed: {}", err),
+        };
+        gui.state.materials = scene.materials().to_vec();
+        gui.state.old_materials = gui.state.materials.clone();
+    }
+
+    if gui.state.old_material != gui.state.material {
+        scene.set_ellipsoid_material(gui.state.material);
+        gui.state.old_material = gui.state.material;
+    }
+
+    if gui.state.old_max_depth != gui.state.max_depth {
+        scene.set_max_depth(gui.state.max_depth);
+        gui.state.old_max_depth = gui.state.max_depth;
+    }
+
+    if gui.state.old_exposure != gui.state.exposure {
+        scene.set_exposure(gui.state.exposure);
+        gui.state.old_exposure = gui.state.exposure;
+    }
+
+    if gui.state.old_primitives != gui.state.primitives {
+        scene.set_primitives(gui.state.primitives.clone());
+        gui.state.old_primitives = gui.state.primitives.clone();
+    }
+
+    if gui.state.old_lights != gui.state.lights {
+        scene.set_lights(gui.state.lights.clone());
+        gui.state.old_lights = gui.state.lights.clone();
+    }
+
+    if gui.state.old_shadow_mode != gui.state.shadow_mode {
+        scene.set_shadow_mode(gui.state.shadow_mode);
+        gui.state.old_shadow_mode = gui.state.shadow_mode;
+    }
+
+    if gui.state.old_ambient != gui.state.ambient {
+        scene.set_ambient(gui.state.ambient);
+        gui.state.old_ambient = gui.state.ambient;
+    }
+
+    if gui.state.old_tone_mapping != gui.state.tone_mapping {
+        scene.set_tone_mapping(gui.state.tone_mapping);
+        gui.state.old_tone_mapping = gui.state.tone_mapping;
+    }
+
+    if gui.state.old_shading_mode != gui.state.shading_mode {
+        scene.set_shading_mode(gui.state.shading_mode);
+        gui.state.old_shading_mode = gui.state.shading_mode;
+    }
+
+    if gui.state.old_colormap != gui.state.colormap {
+        scene.set_colormap(gui.state.colormap);
+        gui.state.old_colormap = gui.state.colormap;
+    }
+
+    if gui.state.fit_curvature_range {
+        if let Some((min, max)) = scene.observed_curvature_range() {
+            gui.state.curvature_min = min;
+            gui.state.curvature_max = max;
+        }
+        gui.state.fit_curvature_range = false;
+    }
+
+    if gui.state.old_curvature_min != gui.state.curvature_min || gui.state.old_curvature_max != gui.state.curvature_max {
+        scene.set_curvature_range(gui.state.curvature_min, gui.state.curvature_max);
+        gui.state.old_curvature_min = gui.state.curvature_min;
+        gui.state.old_curvature_max = gui.state.curvature_max;
     }
 
     if gui.state.max_block_size != gui.state.old_max_block_size {
@@ -144,8 +520,92 @@ fn handle_user_input(scene: &mut Scene, gui: &mut ui::Gui) {
         gui.state.old_max_block_size = gui.state.max_block_size;
     }
 
+    if gui.state.old_near != gui.state.near || gui.state.old_far != gui.state.far {
+        scene.set_clip_distances(gui.state.near, gui.state.far);
+        gui.state.old_near = gui.state.near;
+        gui.state.old_far = gui.state.far;
+    }
+
+    if gui.state.old_cut_mode != gui.state.cut_mode {
+        scene.set_cut_mode(gui.state.cut_mode);
+        gui.state.old_cut_mode = gui.state.cut_mode;
+    }
+
+    let clip_planes = gui.state.clip_planes();
+    if gui.state.old_clip_planes.as_ref() != Some(&clip_planes) {
+        scene.set_clip_planes(clip_planes.clone());
+        gui.state.old_clip_planes = Some(clip_planes);
+    }
+
+    if gui.state.old_environment != Some(gui.state.environment()) || gui.state.load_environment {
+        match gui.state.environment_mode {
+            EnvironmentMode::Off => scene.set_environment(None),
+            EnvironmentMode::Sky => scene.set_environment(Some(gui.state.sky())),
+            EnvironmentMode::Image => {
+                gui.state.environment_status = match scene.load_environment(Path::new(&gui.state.environment_path)) {
+                    Ok(()) => format!("Loaded {}", gui.state.environment_path),
+                    Err(err) => format!("Loading failed: {}", err),
+                };
+            }
+        }
+        gui.state.old_environment = Some(gui.state.environment());
+        gui.state.load_environment = false;
+    }
+
+    if gui.state.old_environment_rotation != gui.state.environment_rotation {
+        scene.set_environment_rotation(gui.state.environment_rotation.to_radians());
+        gui.state.old_environment_rotation = gui.state.environment_rotation;
+    }
+
+    if gui.state.old_environment_intensity != gui.state.environment_intensity {
+        scene.set_environment_intensity(gui.state.environment_intensity);
+        gui.state.old_environment_intensity = gui.state.environment_intensity;
+    }
+
+    let background = gui.state.background();
+    if gui.state.old_background != Some(background) {
+        scene.set_background(background);
+        gui.state.old_background = Some(background);
+    }
+
     if gui.state.scale != gui.state.old_scale {
         scene.set_ellipsoid_scale(gui.state.scale);
         gui.state.old_scale = gui.state.scale;
     }
 }
+
+
+/// Writes the files asked for in the GUI, after `Scene::update` has applied this frame's edits.
+fn handle_exports(scene: &mut Scene, gui: &mut ui::Gui) {
+    if gui.state.export_curves {
+        gui.state.curves_status = match scene.export_intersection_curves(Path::new(&gui.state.curves_path)) {
+            Ok(count) => format!("Saved {} curves to {}", count, gui.state.curves_path),
+            Err(err) => format!("Export failed: {}", err),
+        };
+        gui.state.export_curves = false;
+    }
+
+    if gui.state.save_png {
+        gui.state.export_status = match scene.save_png(Path::new(&gui.state.export_path)) {
+            Ok(()) => format!("Saved {}", gui.state.export_path),
+            Err(err) => format!("Export failed: {}", err),
+        };
+        gui.state.save_png = false;
+    }
+
+    if gui.state.export_mesh {
+        gui.state.mesh_status = match scene.export_mesh(Path::new(&gui.state.mesh_path), gui.state.tessellation()) {
+            Ok(()) => format!("Saved {}", gui.state.mesh_path),
+            Err(err) => format!("Export failed: {}", err),
+        };
+        gui.state.export_mesh = false;
+    }
+
+    if gui.state.export_svg {
+        gui.state.svg_status = match scene.export_svg(Path::new(&gui.state.svg_path), &gui.state.svg_options) {
+            Ok(()) => format!("Saved {}", gui.state.svg_path),
+            Err(err) => format!("Export failed: {}", err),
+        };
+        gui.state.export_svg = false;
+    }
+}
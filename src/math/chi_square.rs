@@ -0,0 +1,83 @@
+//! Chi-square distribution with three degrees of freedom, the distribution of the
+//! squared Mahalanobis distance of a 3D Gaussian.
+
+use std::f64::consts::PI;
+
+
+/// Probability that a sample is smaller than `x`.
+pub fn cdf(x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+
+    let half = 0.5 * x;
+
+    1.0 - erfc(half.sqrt()) - (2.0 * x / PI).sqrt() * (-half).exp()
+}
+
+
+/// Value below which a sample falls with the given probability.
+pub fn quantile(probability: f64) -> f64 {
+    if probability <= 0.0 {
+        return 0.0;
+    }
+
+    let probability = probability.min(1.0 - 1e-12);
+
+    let mut low = 0.0;
+    let mut high = 1.0;
+
+    while cdf(high) < probability {
+        high *= 2.0;
+    }
+
+    for _ in 0..100 {
+        let middle = 0.5 * (low + high);
+
+        if cdf(middle) < probability {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    0.5 * (low + high)
+}
+
+
+/// Complementary error function with relative error below 1.2e-7 (Numerical Recipes `erfcc`).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+
+    let polynomial = -z * z - 1.26551223 + t * (1.00002368 + t * (0.37409196 + t * (0.09678418
+        + t * (-0.18628806 + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587
+        + t * (-0.82215223 + t * 0.17087277))))))));
+
+    let result = t * polynomial.exp();
+
+    if x >= 0.0 { result } else { 2.0 - result }
+}
+
+
+#[cfg(test)]
+mod chi_square_tests {
+    use super::*;
+
+
+    #[test]
+    fn known_quantiles() {
+        assert!((quantile(0.95) - 7.8147).abs() < 1e-3);
+        assert!((quantile(0.99) - 11.3449).abs() < 1e-3);
+        assert!((quantile(0.5) - 2.3660).abs() < 1e-3);
+        assert_eq!(0.0, quantile(0.0));
+    }
+
+
+    #[test]
+    fn cdf_inverts_quantile() {
+        for probability in [0.1, 0.3935, 0.6827, 0.9973] {
+            assert!((cdf(quantile(probability)) - probability).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Coordinates of directions: the texture coordinates of a sphere around them and axes
+//! perpendicular to them.
+
+extern crate nalgebra as na;
+
+use std::f32::consts::PI;
+
+use na::{RealField, Vector3};
+
+
+/// Texture coordinates of a sphere seen in `direction`, `u` around the y axis and half
+/// towards +z, `v` from +y down to -y.
+pub fn spherical_uv(direction: &Vector3<f32>) -> (f32, f32) {
+    let direction = direction.normalize();
+
+    (0.5 + direction.x.atan2(direction.z) / (2.0 * PI), direction.y.clamp(-1.0, 1.0).acos() / PI)
+}
+
+
+/// Inverse of `spherical_uv`, a unit vector.
+pub fn spherical_direction(u: f32, v: f32) -> Vector3<f32> {
+    let (phi, theta) = ((u - 0.5) * 2.0 * PI, v * PI);
+
+    Vector3::new(theta.sin() * phi.sin(), theta.cos(), theta.sin() * phi.cos())
+}
+
+
+/// Two unit vectors perpendicular to `normal` and to each other, right handed with it.
+pub fn perpendicular_axes<T: RealField + Copy>(normal: &Vector3<T>) -> (Vector3<T>, Vector3<T>) {
+    let normal = normal.normalize();
+    let helper = if normal.x.abs() < na::convert(0.9) { Vector3::x() } else { Vector3::y() };
+    let first = normal.cross(&helper).normalize();
+
+    (first, normal.cross(&first))
+}
+
+
+#[cfg(test)]
+mod directions_tests {
+    use super::*;
+
+
+    #[test]
+    fn spherical_coordinates_round_trip() {
+        for direction in [Vector3::new(0.3, 0.5, -0.8), Vector3::new(-1.0, 0.2, 0.1), Vector3::z()] {
+            let (u, v) = spherical_uv(&direction);
+            assert!((spherical_direction(u, v) - direction.normalize()).norm() < 1e-5);
+        }
+
+        assert_eq!((0.5, 0.5), spherical_uv(&Vector3::z()));
+    }
+
+
+    #[test]
+    fn perpendicular_axes_are_orthonormal() {
+        for normal in [Vector3::new(0.3_f32, 0.5, -0.8), Vector3::x(), Vector3::new(0.0, 0.0, -2.0)] {
+            let (first, second) = perpendicular_axes(&normal);
+
+            assert!((first.norm() - 1.0).abs() < 1e-6 && (second.norm() - 1.0).abs() < 1e-6);
+            assert!(first.dot(&normal).abs() < 1e-6 && second.dot(&normal).abs() < 1e-6 && first.dot(&second).abs() < 1e-6);
+            assert!(first.cross(&second).dot(&normal) > 0.0);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Ellipsoids fitted to point sets. Results are given as a center `c` and a shape
+//! matrix `A` describing the points `p` with `(p - c)^T A (p - c) = 1`.
+
+extern crate nalgebra as na;
+
+use na::{DMatrix, DVector, Matrix3, Matrix4, Vector3, Vector4};
+
+
+pub struct FittedEllipsoid {
+    pub center: Vector3<f64>,
+    pub shape: Matrix3<f64>,
+}
+
+
+/// Algebraic least-squares fit of a general quadric, `None` when the best quadric
+/// is not an ellipsoid or there are fewer than nine points.
+pub fn least_squares(points: &[Vector3<f64>]) -> Option<FittedEllipsoid> {
+    if points.len() < 9 {
+        return None;
+    }
+
+    let (mean, scale, points) = normalize(points);
+
+    // Quadric x^T M x + 2 g^T x = 1 with six coefficients of M and three of g.
+    let design = DMatrix::from_fn(points.len(), 9, |row, column| {
+        let p = &points[row];
+        match column {
+            0 => p.x * p.x,
+            1 => p.y * p.y,
+            2 => p.z * p.z,
+            3 => 2.0 * p.x * p.y,
+            4 => 2.0 * p.x * p.z,
+            5 => 2.0 * p.y * p.z,
+            6 => 2.0 * p.x,
+            7 => 2.0 * p.y,
+            _ => 2.0 * p.z,
+        }
+    });
+
+    let coefficients = design.svd(true, true).solve(&DVector::from_element(points.len(), 1.0), 1e-12).ok()?;
+    let k = |i: usize| coefficients[i];
+
+    let m = Matrix3::new(
+        k(0), k(3), k(4),
+        k(3), k(1), k(5),
+        k(4), k(5), k(2)
+    );
+    let g = Vector3::new(k(6), k(7), k(8));
+
+    let center = -m.try_inverse()? * g;
+    let right_side = 1.0 + center.dot(&(m * center));
+
+    if right_side <= 0.0 {
+        return None;
+    }
+
+    let shape = m / right_side;
+
+    // Only positive definite quadrics are ellipsoids.
+    shape.cholesky()?;
+
+    Some(denormalize(FittedEllipsoid { center, shape }, &mean, scale))
+}
+
+
+/// Minimum volume ellipsoid enclosing all points, computed with Khachiyan's algorithm.
+/// Iteration stops once scaling the ellipsoid by `1 + tolerance` would enclose every
+/// point, the result is scaled so that it encloses them.
+pub fn minimum_volume_enclosing(points: &[Vector3<f64>], tolerance: f64) -> Option<FittedEllipsoid> {
+    const MAX_ITERATIONS: usize = 10000;
+
+    if points.len() < 4 {
+        return None;
+    }
+
+    let (mean, scale, points) = normalize(points);
+    let lifted: Vec<Vector4<f64>> = points.iter().map(|p| p.push(1.0)).collect();
+
+    let n = points.len();
+    let d = 3.0;
+    let mut weights = vec![1.0 / n as f64; n];
+
+    for _ in 0..MAX_ITERATIONS {
+        let x = lifted.iter().zip(&weights)
+            .fold(Matrix4::zeros(), |sum, (q, w)| sum + q * q.transpose() * *w);
+        let x_inv = x.try_inverse()?;
+
+        let distances: Vec<f64> = lifted.iter().map(|q| q.dot(&(x_inv * q))).collect();
+
+        let (farthest, max_distance) = distances.iter().copied()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        if max_distance <= (d + 1.0) * (1.0 + tolerance) {
+            break;
+        }
+
+        // Away step of Todd and Yildirim: moves weight off the supporting point lying deepest
+        // inside when that improves more than moving weight to the farthest point.
+        let (nearest, min_distance) = distances.iter().copied()
+            .enumerate()
+            .filter(|(i, _)| weights[*i] > 0.0)
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        let (index, distance) = if d + 1.0 - min_distance > max_distance - d - 1.0 && min_distance > 1.0 + 1e-12 {
+            (nearest, min_distance)
+        } else {
+            (farthest, max_distance)
+        };
+
+        let mut step = (distance - d - 1.0) / ((d + 1.0) * (distance - 1.0));
+        if weights[index] < 1.0 {
+            step = step.max(-weights[index] / (1.0 - weights[index]));
+        }
+
+        for weight in weights.iter_mut() {
+            *weight *= 1.0 - step;
+        }
+        weights[index] = (weights[index] + step).max(0.0);
+    }
+
+    let center = points.iter().zip(&weights).fold(Vector3::zeros(), |sum, (p, w)| sum + p * *w);
+    let spread = points.iter().zip(&weights)
+        .fold(Matrix3::zeros(), |sum, (p, w)| sum + p * p.transpose() * *w) - center * center.transpose();
+
+    let mut shape = spread.try_inverse()? / d;
+
+    let largest = points.iter()
+        .map(|p| (p - center).dot(&(shape * (p - center))))
+        .fold(0.0, f64::max);
+
+    if largest > 1.0 {
+        shape /= largest;
+    }
+
+    Some(denormalize(FittedEllipsoid { center, shape }, &mean, scale))
+}
+
+
+/// Moves the centroid to the origin and scales to unit RMS distance, for better conditioning.
+fn normalize(points: &[Vector3<f64>]) -> (Vector3<f64>, f64, Vec<Vector3<f64>>) {
+    let mean = points.iter().sum::<Vector3<f64>>() / points.len() as f64;
+    let spread = (points.iter().map(|p| (p - mean).norm_squared()).sum::<f64>() / points.len() as f64).sqrt();
+    let scale = if spread > 0.0 { spread } else { 1.0 };
+
+    (mean, scale, points.iter().map(|p| (p - mean) / scale).collect())
+}
+
+
+fn denormalize(fit: FittedEllipsoid, mean: &Vector3<f64>, scale: f64) -> FittedEllipsoid {
+    FittedEllipsoid {
+        center: mean + fit.center * scale,
+        shape: fit.shape / (scale * scale),
+    }
+}
+
+
+#[cfg(test)]
+mod ellipsoid_fit_tests {
+    use super::*;
+    use na::Rotation3;
+
+
+    fn surface_points(axes: Vector3<f64>, rotation: &Matrix3<f64>, center: Vector3<f64>) -> Vec<Vector3<f64>> {
+        let mut points = Vec::new();
+
+        for i in 1..10 {
+            for j in 0..12 {
+                let theta = std::f64::consts::PI * i as f64 / 10.0;
+                let phi = std::f64::consts::PI * j as f64 / 6.0;
+                let local = Vector3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+                points.push(center + rotation * local.component_mul(&axes));
+            }
+        }
+
+        points
+    }
+
+
+    fn expected_shape(axes: Vector3<f64>, rotation: &Matrix3<f64>) -> Matrix3<f64> {
+        let coefficients = axes.map(|axis| 1.0 / (axis * axis));
+        rotation * Matrix3::from_diagonal(&coefficients) * rotation.transpose()
+    }
+
+
+    #[test]
+    fn least_squares_recovers_ellipsoid() {
+        let axes = Vector3::new(3.0, 1.0, 0.5);
+        let rotation = Rotation3::from_euler_angles(0.4, 1.1, -0.3).into_inner();
+        let center = Vector3::new(5.0, -2.0, 1.0);
+
+        let fit = least_squares(&surface_points(axes, &rotation, center)).unwrap();
+
+        assert!((fit.center - center).norm() < 1e-6);
+        assert!((fit.shape - expected_shape(axes, &rotation)).abs().max() < 1e-6);
+    }
+
+
+    #[test]
+    fn enclosing_ellipsoid_contains_all_points() {
+        let axes = Vector3::new(2.0, 1.0, 1.5);
+        let rotation = Rotation3::from_euler_angles(-0.7, 0.2, 0.9).into_inner();
+        let center = Vector3::new(-1.0, 0.5, 3.0);
+
+        let mut points = surface_points(axes, &rotation, center);
+        points.extend(points.clone().iter().map(|p| center + (p - center) * 0.5));
+
+        let fit = minimum_volume_enclosing(&points, 1e-3).unwrap();
+
+        for p in &points {
+            assert!((p - fit.center).dot(&(fit.shape * (p - fit.center))) <= 1.0 + 1e-9);
+        }
+
+        // Volume is proportional to 1 / sqrt(det(shape)).
+        let volume_ratio = (expected_shape(axes, &rotation).determinant() / fit.shape.determinant()).sqrt();
+        assert!(volume_ratio < 1.02);
+    }
+
+
+    #[test]
+    fn degenerate_inputs() {
+        let flat: Vec<Vector3<f64>> = (0..20).map(|i| Vector3::new(i as f64, (i * i) as f64, 0.0)).collect();
+
+        assert!(least_squares(&flat[..5]).is_none());
+        assert!(minimum_volume_enclosing(&flat, 1e-6).is_none());
+    }
+}
@@ -0,0 +1,282 @@
+//! Geometry of the axis aligned ellipsoid `(x/e0)^2 + (y/e1)^2 + (z/e2)^2 = 1` centered at the origin.
+
+use std::f64::consts::PI;
+
+
+const MAX_BISECTIONS: usize = 1100;
+
+
+/// Point on the surface closest to `point`, for points inside as well as outside.
+///
+/// Follows Eberly, "Distance from a Point to an Ellipse, an Ellipsoid, or a Hyperellipsoid":
+/// the problem is reduced to the first octant with axes sorted in decreasing order and the
+/// root of a monotonic function is found by bisection, which also handles points on the
+/// axes and planes of symmetry.
+pub fn closest_point(axes: [f64; 3], point: [f64; 3]) -> [f64; 3] {
+    let mut order = [0, 1, 2];
+    order.sort_by(|i, j| axes[*j].total_cmp(&axes[*i]));
+
+    let e = order.map(|i| axes[i]);
+    let y = order.map(|i| point[i].abs());
+
+    let x = closest_point_sorted(e, y);
+
+    let mut result = [0.0; 3];
+    for (k, i) in order.into_iter().enumerate() {
+        result[i] = x[k].copysign(point[i]);
+    }
+
+    result
+}
+
+
+/// Area of the surface. The integrand over `u = cos(theta)` and the longitude is smooth,
+/// so Gauss-Legendre quadrature in both directions converges quickly.
+pub fn surface_area(axes: [f64; 3]) -> f64 {
+    const NODES: usize = 48;
+
+    let [a, b, c] = axes;
+    let nodes = gauss_legendre(NODES);
+
+    let mut area = 0.0;
+
+    for (u, u_weight) in &nodes {
+        for (t, phi_weight) in &nodes {
+            // Longitude over [0, pi / 2], the other quadrants are symmetric.
+            let phi = PI / 4.0 * (t + 1.0);
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            let sin2_theta = 1.0 - u * u;
+
+            let integrand = (b * b * c * c * sin2_theta * cos_phi * cos_phi
+                + a * a * c * c * sin2_theta * sin_phi * sin_phi
+                + a * a * b * b * u * u).sqrt();
+
+            area += u_weight * phi_weight * PI / 4.0 * integrand;
+        }
+    }
+
+    4.0 * area
+}
+
+
+fn closest_point_sorted(e: [f64; 3], y: [f64; 3]) -> [f64; 3] {
+    if y[2] > 0.0 {
+        if y[1] > 0.0 {
+            if y[0] > 0.0 {
+                let z = [y[0] / e[0], y[1] / e[1], y[2] / e[2]];
+                let g = z[0] * z[0] + z[1] * z[1] + z[2] * z[2] - 1.0;
+
+                if g == 0.0 {
+                    return y;
+                }
+
+                let r = [(e[0] / e[2]).powi(2), (e[1] / e[2]).powi(2)];
+                let s = root_3d(r, z, g);
+
+                [r[0] * y[0] / (s + r[0]), r[1] * y[1] / (s + r[1]), y[2] / (s + 1.0)]
+            } else {
+                let [x1, x2] = closest_point_2d([e[1], e[2]], [y[1], y[2]]);
+                [0.0, x1, x2]
+            }
+        } else if y[0] > 0.0 {
+            let [x0, x2] = closest_point_2d([e[0], e[2]], [y[0], y[2]]);
+            [x0, 0.0, x2]
+        } else {
+            [0.0, 0.0, e[2]]
+        }
+    } else {
+        // The point lies in the plane of the two largest axes. Inside of the focal ellipse
+        // the closest point leaves the plane.
+        let denominators = [e[0] * e[0] - e[2] * e[2], e[1] * e[1] - e[2] * e[2]];
+        let numerators = [e[0] * y[0], e[1] * y[1]];
+
+        if numerators[0] < denominators[0] && numerators[1] < denominators[1] {
+            let ratio = [numerators[0] / denominators[0], numerators[1] / denominators[1]];
+            let discriminant = 1.0 - ratio[0] * ratio[0] - ratio[1] * ratio[1];
+
+            if discriminant > 0.0 {
+                return [e[0] * ratio[0], e[1] * ratio[1], e[2] * discriminant.sqrt()];
+            }
+        }
+
+        let [x0, x1] = closest_point_2d([e[0], e[1]], [y[0], y[1]]);
+        [x0, x1, 0.0]
+    }
+}
+
+
+fn closest_point_2d(e: [f64; 2], y: [f64; 2]) -> [f64; 2] {
+    if y[1] > 0.0 {
+        if y[0] > 0.0 {
+            let z = [y[0] / e[0], y[1] / e[1]];
+            let g = z[0] * z[0] + z[1] * z[1] - 1.0;
+
+            if g == 0.0 {
+                return y;
+            }
+
+            let r = (e[0] / e[1]).powi(2);
+            let s = root_2d(r, z, g);
+
+            [r * y[0] / (s + r), y[1] / (s + 1.0)]
+        } else {
+            [0.0, e[1]]
+        }
+    } else {
+        let numerator = e[0] * y[0];
+        let denominator = e[0] * e[0] - e[1] * e[1];
+
+        if numerator < denominator {
+            let ratio = numerator / denominator;
+            [e[0] * ratio, e[1] * (1.0 - ratio * ratio).sqrt()]
+        } else {
+            [e[0], 0.0]
+        }
+    }
+}
+
+
+fn root_2d(r: f64, z: [f64; 2], g: f64) -> f64 {
+    let n = r * z[0];
+
+    bisect(z[1] - 1.0, if g < 0.0 { 0.0 } else { (n * n + z[1] * z[1]).sqrt() - 1.0 }, |s| {
+        (n / (s + r)).powi(2) + (z[1] / (s + 1.0)).powi(2) - 1.0
+    })
+}
+
+
+fn root_3d(r: [f64; 2], z: [f64; 3], g: f64) -> f64 {
+    let n = [r[0] * z[0], r[1] * z[1]];
+
+    bisect(z[2] - 1.0, if g < 0.0 { 0.0 } else { (n[0] * n[0] + n[1] * n[1] + z[2] * z[2]).sqrt() - 1.0 }, |s| {
+        (n[0] / (s + r[0])).powi(2) + (n[1] / (s + r[1])).powi(2) + (z[2] / (s + 1.0)).powi(2) - 1.0
+    })
+}
+
+
+/// Root of the decreasing function `f` between `low` and `high`, bisected until the
+/// interval cannot shrink any more.
+fn bisect(mut low: f64, mut high: f64, f: impl Fn(f64) -> f64) -> f64 {
+    let mut middle = 0.5 * (low + high);
+
+    for _ in 0..MAX_BISECTIONS {
+        middle = 0.5 * (low + high);
+
+        if middle == low || middle == high {
+            break;
+        }
+
+        let value = f(middle);
+
+        if value > 0.0 {
+            low = middle;
+        } else if value < 0.0 {
+            high = middle;
+        } else {
+            break;
+        }
+    }
+
+    middle
+}
+
+
+/// Nodes and weights of the `n` point Gauss-Legendre rule on [-1, 1].
+fn gauss_legendre(n: usize) -> Vec<(f64, f64)> {
+    (0..n)
+        .map(|i| {
+            // Newton iteration from the Chebyshev approximation of the root.
+            let mut x = (PI * (i as f64 + 0.75) / (n as f64 + 0.5)).cos();
+            let mut derivative = 0.0;
+
+            for _ in 0..100 {
+                let (mut p0, mut p1) = (1.0, x);
+                for k in 2..=n {
+                    let p2 = ((2 * k - 1) as f64 * x * p1 - (k - 1) as f64 * p0) / k as f64;
+                    p0 = p1;
+                    p1 = p2;
+                }
+
+                derivative = n as f64 * (x * p1 - p0) / (x * x - 1.0);
+                let step = p1 / derivative;
+                x -= step;
+
+                if step.abs() < 1e-15 {
+                    break;
+                }
+            }
+
+            (x, 2.0 / ((1.0 - x * x) * derivative * derivative))
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod ellipsoid_geometry_tests {
+    use super::*;
+
+
+    fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+        ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+    }
+
+
+    #[test]
+    fn closest_point_on_sphere() {
+        let x = closest_point([2.0, 2.0, 2.0], [3.0, -4.0, 0.0]);
+        assert!(distance(x, [1.2, -1.6, 0.0]) < 1e-12);
+
+        let x = closest_point([2.0, 2.0, 2.0], [0.0, 0.5, 0.0]);
+        assert!(distance(x, [0.0, 2.0, 0.0]) < 1e-12);
+    }
+
+
+    #[test]
+    fn closest_point_on_axes() {
+        // Outside along the longest axis the vertex is closest.
+        assert!(distance(closest_point([1.0, 3.0, 2.0], [0.0, -5.0, 0.0]), [0.0, -3.0, 0.0]) < 1e-12);
+
+        // From the center the closest point is the end of the shortest axis.
+        let x = closest_point([1.0, 3.0, 2.0], [0.0, 0.0, 0.0]);
+        assert!(distance(x, [1.0, 0.0, 0.0]) < 1e-12 || distance(x, [-1.0, 0.0, 0.0]) < 1e-12);
+    }
+
+
+    #[test]
+    fn closest_point_is_orthogonal_projection() {
+        let axes = [3.0, 1.0, 2.0];
+
+        for point in [[2.0, 1.5, -1.0], [0.3, -0.2, 0.1], [-4.0, 0.0, 3.0], [1.0, 0.0, 0.0]] {
+            let x = closest_point(axes, point);
+            let on_surface: f64 = (0..3).map(|i| (x[i] / axes[i]).powi(2)).sum();
+            assert!((on_surface - 1.0).abs() < 1e-9);
+
+            // The offset is parallel to the surface normal.
+            let normal = [x[0] / (axes[0] * axes[0]), x[1] / (axes[1] * axes[1]), x[2] / (axes[2] * axes[2])];
+            let offset = [point[0] - x[0], point[1] - x[1], point[2] - x[2]];
+            let cross = [
+                normal[1] * offset[2] - normal[2] * offset[1],
+                normal[2] * offset[0] - normal[0] * offset[2],
+                normal[0] * offset[1] - normal[1] * offset[0],
+            ];
+            assert!(distance(cross, [0.0; 3]) < 1e-9);
+        }
+    }
+
+
+    #[test]
+    fn surface_areas() {
+        assert!((surface_area([2.0, 2.0, 2.0]) - 16.0 * PI).abs() < 1e-9);
+
+        // Prolate spheroid with equatorial radius 1 and polar radius 2.
+        let eccentricity = (1.0_f64 - 0.25).sqrt();
+        let prolate = 2.0 * PI * (1.0 + 2.0 / eccentricity * eccentricity.asin());
+        assert!((surface_area([1.0, 2.0, 1.0]) - prolate).abs() < 1e-9);
+
+        // Oblate spheroid with equatorial radius 2 and polar radius 1.
+        let eccentricity = (1.0_f64 - 0.25).sqrt();
+        let oblate = 2.0 * PI * 4.0 + PI / eccentricity * ((1.0 + eccentricity) / (1.0 - eccentricity)).ln();
+        assert!((surface_area([2.0, 2.0, 1.0]) - oblate).abs() < 1e-9);
+    }
+}
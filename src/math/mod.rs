@@ -1 +1,7 @@
-pub mod quadratic_equation;
\ No newline at end of file
+pub mod chi_square;
+pub mod directions;
+pub mod ellipsoid_fit;
+pub mod ellipsoid_geometry;
+pub mod optics;
+pub mod quadratic_equation;
+pub mod quartic_equation;
\ No newline at end of file
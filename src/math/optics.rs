@@ -0,0 +1,92 @@
+//! Reflection and refraction of rays at surfaces. Directions point along the ray and
+//! normals face the side the ray comes from.
+
+extern crate nalgebra as na;
+
+use na::Vector3;
+
+
+pub fn reflect(direction: &Vector3<f32>, normal: &Vector3<f32>) -> Vector3<f32> {
+    direction - normal * (2.0 * direction.dot(normal))
+}
+
+
+/// Direction of the ray refracted by Snell's law, `ratio` is the index of refraction on
+/// the incoming side over the one on the far side. `None` on total internal reflection.
+pub fn refract(direction: &Vector3<f32>, normal: &Vector3<f32>, ratio: f32) -> Option<Vector3<f32>> {
+    let direction = direction.normalize();
+    let cos_i = -direction.dot(normal);
+    let sin2_t = ratio * ratio * (1.0 - cos_i * cos_i);
+
+    if sin2_t > 1.0 {
+        return None;
+    }
+
+    Some(direction * ratio + normal * (ratio * cos_i - (1.0 - sin2_t).sqrt()))
+}
+
+
+/// Fraction of unpolarized light reflected when passing from index `n1` to `n2` at an
+/// angle of incidence with cosine `cos_i`, one on total internal reflection.
+pub fn fresnel(cos_i: f32, n1: f32, n2: f32) -> f32 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (n1 / n2).powi(2) * (1.0 - cos_i * cos_i);
+
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let parallel = (n2 * cos_i - n1 * cos_t) / (n2 * cos_i + n1 * cos_t);
+    let perpendicular = (n1 * cos_i - n2 * cos_t) / (n1 * cos_i + n2 * cos_t);
+
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+
+/// Schlick's approximation of the reflectance of a surface reflecting `normal_reflectance`
+/// at normal incidence.
+pub fn schlick(normal_reflectance: f32, cos_i: f32) -> f32 {
+    normal_reflectance + (1.0 - normal_reflectance) * (1.0 - cos_i.clamp(0.0, 1.0)).powi(5)
+}
+
+
+#[cfg(test)]
+mod optics_tests {
+    use super::*;
+
+
+    #[test]
+    fn reflection_mirrors_normal_component() {
+        let reflected = reflect(&Vector3::new(1.0, -1.0, 0.0), &Vector3::y());
+
+        assert_eq!(Vector3::new(1.0, 1.0, 0.0), reflected);
+    }
+
+
+    #[test]
+    fn refraction_follows_snell() {
+        let direction = Vector3::new(0.5_f32.sqrt(), -(0.5_f32.sqrt()), 0.0);
+        let refracted = refract(&direction, &Vector3::y(), 1.0 / 1.5).unwrap();
+
+        let sin_t = refracted.x / refracted.norm();
+        assert!((sin_t - 0.5_f32.sqrt() / 1.5).abs() < 1e-6);
+        assert!((refracted.norm() - 1.0).abs() < 1e-6);
+
+        // Leaving glass at 45 degrees is beyond the critical angle.
+        assert!(refract(&direction, &Vector3::y(), 1.5).is_none());
+    }
+
+
+    #[test]
+    fn fresnel_reflectance() {
+        // Glass at normal incidence reflects ((n - 1) / (n + 1))^2.
+        assert!((fresnel(1.0, 1.0, 1.5) - 0.04).abs() < 1e-6);
+        assert!((fresnel(1.0, 1.5, 1.0) - 0.04).abs() < 1e-6);
+        assert_eq!(1.0, fresnel(0.5, 1.5, 1.0));
+        assert!((fresnel(0.0, 1.0, 1.5) - 1.0).abs() < 1e-6);
+
+        assert_eq!(0.04, schlick(0.04, 1.0));
+        assert_eq!(1.0, schlick(0.04, 0.0));
+    }
+}
@@ -12,14 +12,28 @@ pub fn solve(a: f32, b: f32, c: f32) -> Solutions {
     }
 
     if delta == 0.0 {
-        return Solutions::One(-b/2.0*a);
+        return Solutions::One(-b/(2.0*a));
     }
 
     let delta_sqrt = delta.sqrt();
     let two_a = 2.0*a;
 
-    return Solutions::Two(
+    Solutions::Two(
         (-b - delta_sqrt)/two_a,
         (-b + delta_sqrt)/two_a
-    );
+    )
+}
+
+
+#[cfg(test)]
+mod quadratic_equation_tests {
+    use super::*;
+
+
+    #[test]
+    fn double_root_divides_by_two_a() {
+        assert!(matches!(solve(2.0, -4.0, 2.0), Solutions::One(x) if x == 1.0));
+        assert!(matches!(solve(0.5, 3.0, 4.5), Solutions::One(x) if x == -3.0));
+        assert!(matches!(solve(2.0, 0.0, -8.0), Solutions::Two(x1, x2) if x1 == -2.0 && x2 == 2.0));
+    }
 }
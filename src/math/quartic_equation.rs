@@ -0,0 +1,138 @@
+//! Real roots of polynomials up to the fourth degree, isolated between the roots of the
+//! derivative and refined with Newton steps kept inside the bracket.
+
+const MAX_ITERATIONS: usize = 100;
+
+
+/// Real roots of `a x^4 + b x^3 + c x^2 + d x + e` in increasing order. Roots of even
+/// multiplicity, where the polynomial only touches zero, are found only when exact.
+pub fn solve(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    roots(&[e, d, c, b, a])
+}
+
+
+/// Real roots of the polynomial with `coefficients` from the constant term up.
+fn roots(coefficients: &[f64]) -> Vec<f64> {
+    let degree = match coefficients.iter().rposition(|&coefficient| coefficient != 0.0) {
+        Some(degree) => degree,
+        None => return Vec::new(),
+    };
+    let coefficients = &coefficients[..=degree];
+
+    match degree {
+        0 => return Vec::new(),
+        1 => return vec![-coefficients[0] / coefficients[1]],
+        _ => (),
+    }
+
+    // The polynomial is monotonic between neighbouring roots of its derivative, so each of
+    // those intervals holds at most one root. Cauchy's bound closes the outer ones.
+    let derivative: Vec<f64> = coefficients.iter().enumerate().skip(1).map(|(i, coefficient)| i as f64 * coefficient).collect();
+    let bound = 1.0 + coefficients[..degree].iter().map(|coefficient| (coefficient / coefficients[degree]).abs()).fold(0.0, f64::max);
+
+    let mut points = vec![-bound];
+    points.extend(roots(&derivative).into_iter().filter(|x| x.abs() < bound));
+    points.push(bound);
+
+    let mut result: Vec<f64> = Vec::new();
+
+    for pair in points.windows(2) {
+        let (low, high) = (pair[0], pair[1]);
+        let (value_low, value_high) = (evaluate(coefficients, low), evaluate(coefficients, high));
+
+        let root = if value_low == 0.0 {
+            low
+        } else if value_high != 0.0 && (value_low < 0.0) != (value_high < 0.0) {
+            refine(coefficients, &derivative, low, high, value_low)
+        } else {
+            continue;
+        };
+
+        if result.last() != Some(&root) {
+            result.push(root);
+        }
+    }
+
+    if evaluate(coefficients, bound) == 0.0 {
+        result.push(bound);
+    }
+
+    result
+}
+
+
+/// Root inside `[low, high]`, where the polynomial changes sign and has `value_low` at `low`.
+fn refine(coefficients: &[f64], derivative: &[f64], mut low: f64, mut high: f64, value_low: f64) -> f64 {
+    let mut x = 0.5 * (low + high);
+
+    for _ in 0..MAX_ITERATIONS {
+        let value = evaluate(coefficients, x);
+
+        if value == 0.0 {
+            return x;
+        }
+
+        if (value < 0.0) == (value_low < 0.0) {
+            low = x;
+        } else {
+            high = x;
+        }
+
+        // Newton step, falling back to bisection when it leaves the bracket.
+        let next = x - value / evaluate(derivative, x);
+        let next = if low < next && next < high { next } else { 0.5 * (low + high) };
+
+        if (next - x).abs() <= 1e-12 * (1.0 + x.abs()) {
+            return next;
+        }
+
+        x = next;
+    }
+
+    x
+}
+
+
+fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |result, coefficient| result * x + coefficient)
+}
+
+
+#[cfg(test)]
+mod quartic_equation_tests {
+    use super::*;
+
+
+    fn assert_roots(expected: &[f64], found: &[f64]) {
+        assert_eq!(expected.len(), found.len(), "{:?}", found);
+
+        for (expected, found) in expected.iter().zip(found) {
+            assert!((expected - found).abs() < 1e-9, "{} != {}", expected, found);
+        }
+    }
+
+
+    #[test]
+    fn four_real_roots() {
+        // (x + 3)(x + 1)(x - 0.5)(x - 2)
+        assert_roots(&[-3.0, -1.0, 0.5, 2.0], &solve(1.0, 1.5, -6.0, -3.5, 3.0));
+    }
+
+
+    #[test]
+    fn fewer_real_roots() {
+        // (x^2 + 1)(x - 1)(x - 4)
+        assert_roots(&[1.0, 4.0], &solve(1.0, -5.0, 5.0, -5.0, 4.0));
+        assert_roots(&[], &solve(1.0, 0.0, 2.0, 0.0, 1.0));
+        // Touching zero at a double root.
+        assert_roots(&[-1.0, 1.0], &solve(1.0, 0.0, -2.0, 0.0, 1.0));
+    }
+
+
+    #[test]
+    fn lower_degrees() {
+        assert_roots(&[-2.0, 2.0], &solve(0.0, 0.0, 1.0, 0.0, -4.0));
+        assert_roots(&[-1.0, 0.0, 1.0], &solve(0.0, 1.0, 0.0, -1.0, 0.0));
+        assert_roots(&[1.5], &solve(0.0, 0.0, 0.0, 2.0, -3.0));
+    }
+}
@@ -0,0 +1,75 @@
+extern crate nalgebra as na;
+
+use na::{Point3, Vector3};
+
+
+/// Axis aligned bounding box in world coordinates.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+
+impl Aabb {
+    pub fn from_center(center: &Point3<f32>, half_size: &Vector3<f32>) -> Aabb {
+        Aabb { min: center - half_size, max: center + half_size }
+    }
+
+
+    pub fn center(&self) -> Point3<f32> {
+        na::center(&self.min, &self.max)
+    }
+
+
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+
+    pub fn surface_area(&self) -> f32 {
+        let size = self.size();
+
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+
+    pub fn contains(&self, point: &Point3<f32>) -> bool {
+        (0..3).all(|i| self.min[i] <= point[i] && point[i] <= self.max[i])
+    }
+
+
+    /// Smallest box holding both boxes.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb { min: self.min.inf(&other.min), max: self.max.sup(&other.max) }
+    }
+
+
+    pub fn expanded(&self, margin: f32) -> Aabb {
+        Aabb { min: self.min - Vector3::repeat(margin), max: self.max + Vector3::repeat(margin) }
+    }
+
+
+    /// Values of `t` where the ray `origin + t * direction` enters and leaves the box.
+    pub fn ray_range(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Option<(f32, f32)> {
+        let mut start = f32::NEG_INFINITY;
+        let mut end = f32::INFINITY;
+
+        for i in 0..3 {
+            if direction[i] == 0.0 {
+                if origin[i] < self.min[i] || origin[i] > self.max[i] {
+                    return None;
+                }
+                continue;
+            }
+
+            let first = (self.min[i] - origin[i]) / direction[i];
+            let second = (self.max[i] - origin[i]) / direction[i];
+
+            start = start.max(first.min(second));
+            end = end.min(first.max(second));
+        }
+
+        (start <= end).then_some((start, end))
+    }
+}
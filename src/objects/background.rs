@@ -0,0 +1,61 @@
+use super::Color;
+
+
+/// What is drawn where no object was hit.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Background {
+    Solid(Color),
+    /// Vertical gradient from the top to the bottom edge of the image.
+    Gradient{top: Color, bottom: Color},
+    /// Fully transparent, only visible in exported images.
+    Transparent,
+}
+
+
+impl Background {
+    /// Background color at relative height `v`, 0 is the top edge and 1 the bottom one.
+    pub fn color(&self, v: f32) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { top, bottom } => top.mix(*bottom, v.clamp(0.0, 1.0)),
+            Background::Transparent => Color::transparent(),
+        }
+    }
+}
+
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid(Color::from_rgb(120, 120, 120))
+    }
+}
+
+
+#[cfg(test)]
+mod background_tests {
+    use super::*;
+
+
+    #[test]
+    fn gradient_ends() {
+        let top = Color::from_rgb(255, 0, 0);
+        let bottom = Color::from_rgb(0, 0, 255);
+        let background = Background::Gradient { top, bottom };
+
+        assert_eq!((255, 0, 0), bytes(background.color(0.0)));
+        assert_eq!((0, 0, 255), bytes(background.color(1.0)));
+        assert_eq!((0, 0, 255), bytes(background.color(2.0)));
+    }
+
+
+    #[test]
+    fn transparent_has_no_coverage() {
+        assert_eq!(0, Background::Transparent.color(0.5).alpha_byte());
+        assert_eq!(255, Background::default().color(0.5).alpha_byte());
+    }
+
+
+    fn bytes(color: Color) -> (u8, u8, u8) {
+        (color.red(), color.green(), color.blue())
+    }
+}
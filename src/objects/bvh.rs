@@ -0,0 +1,302 @@
+//! Bounding volume hierarchy over the objects of the scene, so a ray is tested only against
+//! the objects whose boxes it passes through.
+
+extern crate nalgebra as na;
+
+use na::{Point3, Vector3};
+
+use super::Aabb;
+
+
+/// Most items kept in one leaf.
+const LEAF_SIZE: usize = 4;
+
+/// Refitting stops and the tree is built again once its boxes have grown by this factor
+/// in total surface area since the last build.
+const REBUILD_GROWTH: f32 = 2.0;
+
+const MAX_DEPTH: usize = 64;
+
+
+#[derive(Clone, Copy, Debug)]
+enum BvhNode {
+    /// Holds `count` items from `first` in `Bvh::items`.
+    Leaf { bounds: Aabb, first: usize, count: usize },
+    Inner { bounds: Aabb, left: usize, right: usize },
+}
+
+
+/// Hierarchy over items given by index, each with a box holding it or `None` when unbounded.
+#[derive(Clone, Debug, Default)]
+pub struct Bvh {
+    /// Root first, every node before its children.
+    nodes: Vec<BvhNode>,
+    /// Bounded items in the order the leaves refer to them.
+    items: Vec<usize>,
+    /// Items without a box, met by every ray.
+    unbounded: Vec<usize>,
+    boxes: Vec<Option<Aabb>>,
+    /// Total surface area of the boxes of the nodes right after the last build.
+    built_area: f32,
+}
+
+
+impl BvhNode {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bounds, .. } | BvhNode::Inner { bounds, .. } => bounds,
+        }
+    }
+}
+
+
+impl Bvh {
+    pub fn new(boxes: Vec<Option<Aabb>>) -> Bvh {
+        let mut bvh = Bvh { boxes, ..Bvh::default() };
+        bvh.build();
+        bvh
+    }
+
+
+    /// Takes the new boxes of the items. The tree is refitted when the same items only moved
+    /// and built again when items were added, removed, or the refitted boxes became too loose.
+    pub fn update(&mut self, boxes: Vec<Option<Aabb>>) {
+        if boxes == self.boxes {
+            return;
+        }
+
+        let same_items = boxes.len() == self.boxes.len()
+            && boxes.iter().zip(&self.boxes).all(|(new, old)| new.is_some() == old.is_some());
+
+        self.boxes = boxes;
+
+        if same_items {
+            self.refit();
+
+            if self.area() <= REBUILD_GROWTH * self.built_area {
+                return;
+            }
+        }
+
+        self.build();
+    }
+
+
+    /// Calls `visit` for every item whose box the ray `origin + t * direction` meets with `t`
+    /// in `[t_min, t_max]`, nearer boxes first. `visit` returns the `t` where the ray hits the
+    /// item, if it does, and boxes behind the nearest hit so far are skipped.
+    pub fn traverse(
+        &self,
+        origin: &Point3<f32>,
+        direction: &Vector3<f32>,
+        t_min: f32,
+        mut t_max: f32,
+        mut visit: impl FnMut(usize) -> Option<f32>
+    ) {
+        for &item in &self.unbounded {
+            if let Some(t) = visit(item) {
+                t_max = t_max.min(t);
+            }
+        }
+
+        let entry = |node: usize, t_max: f32| self.nodes[node].bounds().ray_range(origin, direction)
+            .filter(|(start, end)| *start <= t_max && *end >= t_min)
+            .map(|(start, _)| start);
+
+        let Some(root_entry) = self.nodes.first().and_then(|_| entry(0, t_max)) else {
+            return;
+        };
+
+        let mut stack = [(0, 0.0); MAX_DEPTH];
+        stack[0] = (0, root_entry);
+        let mut depth = 1;
+
+        while depth > 0 {
+            depth -= 1;
+            let (node, start) = stack[depth];
+
+            if start > t_max {
+                continue;
+            }
+
+            match self.nodes[node] {
+                BvhNode::Leaf { first, count, .. } => {
+                    for &item in &self.items[first..first + count] {
+                        let crossed = self.item_box(item).ray_range(origin, direction)
+                            .is_some_and(|(start, end)| start <= t_max && end >= t_min);
+
+                        if let Some(t) = crossed.then(|| visit(item)).flatten() {
+                            t_max = t_max.min(t);
+                        }
+                    }
+                }
+                BvhNode::Inner { left, right, .. } => {
+                    let mut children = [(left, entry(left, t_max)), (right, entry(right, t_max))];
+
+                    // The nearer child goes on top of the stack.
+                    if children[0].1 < children[1].1 {
+                        children.swap(0, 1);
+                    }
+
+                    for (child, start) in children {
+                        if let Some(start) = start {
+                            stack[depth] = (child, start);
+                            depth += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+
+    fn build(&mut self) {
+        self.nodes.clear();
+        self.items = (0..self.boxes.len()).filter(|&item| self.boxes[item].is_some()).collect();
+        self.unbounded = (0..self.boxes.len()).filter(|&item| self.boxes[item].is_none()).collect();
+
+        if !self.items.is_empty() {
+            self.build_node(0, self.items.len(), 0);
+        }
+
+        self.built_area = self.area();
+    }
+
+
+    /// Adds the node over `items[first..end]` and its children, returns its index.
+    fn build_node(&mut self, first: usize, end: usize, depth: usize) -> usize {
+        let index = self.nodes.len();
+        let bounds = self.items_bounds(first, end);
+
+        // Placeholder until the children are known.
+        self.nodes.push(BvhNode::Leaf { bounds, first, count: end - first });
+
+        if end - first <= LEAF_SIZE || depth + 2 >= MAX_DEPTH {
+            return index;
+        }
+
+        // Median split along the axis the centers of the boxes spread the most.
+        let centers: Vec<Point3<f32>> = self.items[first..end].iter().map(|&item| self.item_box(item).center()).collect();
+        let low = centers.iter().fold(centers[0], |low, center| low.inf(center));
+        let high = centers.iter().fold(centers[0], |high, center| high.sup(center));
+        let axis = (high - low).imax();
+
+        let middle = (end - first) / 2;
+        let boxes = &self.boxes;
+        self.items[first..end].select_nth_unstable_by(middle, |a, b| {
+            let center = |item: &usize| boxes[*item].unwrap().center()[axis];
+            center(a).total_cmp(&center(b))
+        });
+
+        let left = self.build_node(first, first + middle, depth + 1);
+        let right = self.build_node(first + middle, end, depth + 1);
+        self.nodes[index] = BvhNode::Inner { bounds, left, right };
+
+        index
+    }
+
+
+    /// Recomputes the boxes of the nodes bottom up, keeping the tree.
+    fn refit(&mut self) {
+        // Children come after their parents.
+        for index in (0..self.nodes.len()).rev() {
+            self.nodes[index] = match self.nodes[index] {
+                BvhNode::Leaf { first, count, .. } => BvhNode::Leaf { bounds: self.items_bounds(first, first + count), first, count },
+                BvhNode::Inner { left, right, .. } => BvhNode::Inner {
+                    bounds: self.nodes[left].bounds().union(self.nodes[right].bounds()),
+                    left,
+                    right,
+                },
+            };
+        }
+    }
+
+
+    fn items_bounds(&self, first: usize, end: usize) -> Aabb {
+        self.items[first + 1..end].iter()
+            .fold(self.item_box(self.items[first]), |bounds, &item| bounds.union(&self.item_box(item)))
+    }
+
+
+    fn item_box(&self, item: usize) -> Aabb {
+        self.boxes[item].expect("only bounded items are in the tree")
+    }
+
+
+    fn area(&self) -> f32 {
+        self.nodes.iter().map(|node| node.bounds().surface_area()).sum()
+    }
+}
+
+
+#[cfg(test)]
+mod bvh_tests {
+    use super::*;
+
+
+    /// Unit boxes on a grid in the plane `z = 0`, and one unbounded item.
+    fn grid_boxes() -> Vec<Option<Aabb>> {
+        let mut boxes: Vec<Option<Aabb>> = (0..100)
+            .map(|i| Some(Aabb::from_center(&Point3::new((i % 10) as f32 * 3.0, (i / 10) as f32 * 3.0, 0.0), &Vector3::repeat(1.0))))
+            .collect();
+        boxes.push(None);
+        boxes
+    }
+
+
+    fn visited(bvh: &Bvh, x: f32, y: f32) -> Vec<usize> {
+        let mut items = Vec::new();
+        bvh.traverse(&Point3::new(x, y, -10.0), &Vector3::z(), 0.0, f32::INFINITY, |item| {
+            items.push(item);
+            None
+        });
+        items.sort();
+        items
+    }
+
+
+    #[test]
+    fn rays_meet_only_boxes_they_cross() {
+        let bvh = Bvh::new(grid_boxes());
+
+        assert_eq!(vec![23, 100], visited(&bvh, 9.5, 6.5));
+        assert_eq!(vec![100], visited(&bvh, 10.5, 6.5));
+        assert_eq!(vec![100], visited(&bvh, -5.0, 0.0));
+    }
+
+
+    #[test]
+    fn nearer_hits_skip_boxes_behind() {
+        // A row of boxes along the ray, more than fit in one leaf.
+        let boxes = (0..8).map(|i| Some(Aabb::from_center(&Point3::new(0.0, 0.0, 2.0 * (i + 1) as f32), &Vector3::repeat(0.5)))).collect();
+        let bvh = Bvh::new(boxes);
+
+        let mut items = Vec::new();
+        bvh.traverse(&Point3::origin(), &Vector3::z(), 0.0, f32::INFINITY, |item| {
+            items.push(item);
+            Some(2.0 * (item + 1) as f32 - 0.5)
+        });
+
+        assert!(items.contains(&0));
+        assert!(items.iter().all(|item| *item < 4));
+    }
+
+
+    #[test]
+    fn moved_items_are_refitted() {
+        let mut boxes = grid_boxes();
+        let mut bvh = Bvh::new(boxes.clone());
+        let nodes = bvh.nodes.len();
+
+        boxes[23] = Some(Aabb::from_center(&Point3::new(-5.0, 0.0, 0.0), &Vector3::repeat(1.0)));
+        bvh.update(boxes.clone());
+
+        assert_eq!(nodes, bvh.nodes.len());
+        assert_eq!(vec![100], visited(&bvh, 9.5, 6.5));
+        assert_eq!(vec![23, 100], visited(&bvh, -5.0, 0.0));
+
+        boxes.pop();
+        bvh.update(boxes);
+        assert_eq!(vec![23], visited(&bvh, -5.0, 0.0));
+    }
+}
@@ -8,7 +8,12 @@ pub const CAMERA_CENTER: Point3<f32> = point![0.0, 0.0, -20.0];
 
 pub struct Camera {
     pub viewport_width: f32,
-    pub viewport_height: f32
+    pub viewport_height: f32,
+
+    /// Distance from `CAMERA_CENTER` to the near clipping plane.
+    pub near: f32,
+    /// Distance from `CAMERA_CENTER` to the far clipping plane.
+    pub far: f32,
 }
 
 
@@ -28,7 +33,17 @@ pub struct PointsIter {
 
 impl Camera {
     pub fn new(viewport_width: f32, viewport_height: f32) -> Camera {
-        Camera{viewport_width, viewport_height}
+        Camera{viewport_width, viewport_height, near: 0.0, far: f32::INFINITY}
+    }
+
+
+    pub fn near_z(&self) -> f32 {
+        CAMERA_CENTER.z + self.near
+    }
+
+
+    pub fn far_z(&self) -> f32 {
+        CAMERA_CENTER.z + self.far
     }
 
 
@@ -39,6 +54,26 @@ impl Camera {
     }
 
 
+    /// Point on the viewport seen through image coordinates `(x, y)`, measured in pixels
+    /// from the upper left corner. Pixel centers lie at half-integer coordinates.
+    pub fn viewport_point(&self, x: f32, y: f32, img_width: u32, img_height: u32) -> Point3<f32> {
+        self.upper_left_corner()
+            + Vector3::x() * (self.viewport_width * x / img_width as f32)
+            - Vector3::y() * (self.viewport_height * y / img_height as f32)
+    }
+
+
+    /// Image coordinates in pixels where `point` is seen, inverse of `viewport_point`.
+    pub fn pixel_position(&self, point: &Point3<f32>, img_width: u32, img_height: u32) -> (f32, f32) {
+        let offset = point - self.upper_left_corner();
+
+        (
+            offset.x / self.viewport_width * img_width as f32,
+            -offset.y / self.viewport_height * img_height as f32
+        )
+    }
+
+
     pub fn get_points_iterator(&self, img_width: u32, img_height: u32) -> PointsIter {
         PointsIter::new(self, img_width, img_height)
     }
@@ -102,6 +137,24 @@ mod camera_tests {
 
         assert_eq!(point![-2.0, 1.5, CAMERA_CENTER.z], camera.upper_left_corner());
     }
+
+    #[test]
+    fn viewport_point_of_pixel_centers() {
+        let camera = Camera::new(3.0, 3.0);
+
+        assert_eq!(point![-1.0, 1.0, CAMERA_CENTER.z], camera.viewport_point(0.5, 0.5, 3, 3));
+        assert_eq!(point![0.0, 0.0, CAMERA_CENTER.z], camera.viewport_point(1.5, 1.5, 3, 3));
+        assert_eq!(point![1.0, -1.0, CAMERA_CENTER.z], camera.viewport_point(2.5, 2.5, 3, 3));
+    }
+
+
+    #[test]
+    fn pixel_position_inverts_viewport_point() {
+        let camera = Camera::new(4.0, 3.0);
+
+        assert_eq!((2.0, 1.5), camera.pixel_position(&point![-1.0, 0.0, 5.0], 8, 3));
+        assert_eq!((0.0, 0.0), camera.pixel_position(&camera.viewport_point(0.0, 0.0, 8, 3), 8, 3));
+    }
 }
 
 
@@ -1,5 +1,9 @@
 extern crate nalgebra as na;
 
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
 use pixels::{Pixels, SurfaceTexture};
 use winit::window::Window;
 use na::Point2;
@@ -18,11 +22,13 @@ pub struct Canvas {
 }
 
 
+#[allow(dead_code)]
 pub struct Pixel<'a> {
     bytes: &'a [u8; PIXEL_LEN],
 }
 
 
+#[allow(dead_code)]
 pub struct PixelsIter<'a> {
     data: &'a [u8],
     cur_pos: usize,
@@ -67,6 +73,7 @@ impl Canvas {
         &self.pixels
     }
 
+    /// Stores linear `color` clamped and encoded as sRGB, alpha is stored as coverage.
     pub fn set_pixel(&mut self, color: Color, row: u32, column: u32) {
         assert!(row < self.height, "Row is outside of range");
         assert!(column < self.width, "Column is outside of range");
@@ -77,6 +84,40 @@ impl Canvas {
         frame[pixel_index] = color.red();
         frame[pixel_index + 1] = color.green();
         frame[pixel_index + 2] = color.blue();
+        frame[pixel_index + 3] = color.alpha_byte();
+    }
+
+    pub fn get_pixel(&self, row: u32, column: u32) -> Color {
+        let pixel_index: usize = (row * self.width + column) as usize * PIXEL_LEN;
+        let frame = self.pixels.frame();
+
+        Color::from_rgba(frame[pixel_index], frame[pixel_index + 1], frame[pixel_index + 2], frame[pixel_index + 3])
+    }
+
+    /// Composites `color` over the stored pixel, its alpha is used as coverage.
+    pub fn blend_pixel(&mut self, color: Color, row: u32, column: u32) {
+        let below = self.get_pixel(row, column);
+        self.set_pixel(color.over(below), row, column);
+    }
+
+    /// Writes the current frame as an 8-bit sRGB PNG with alpha channel.
+    pub fn save_png(&self, path: &Path) -> Result<(), png::EncodingError> {
+        let file = File::create(path)?;
+
+        self.write_png(BufWriter::new(file))
+    }
+
+    /// Encodes the current frame as in `save_png`.
+    pub fn write_png(&self, writer: impl Write) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.pixels.frame())?;
+
+        writer.finish()
     }
 
     pub fn get_width(&self) -> u32 {
@@ -91,6 +132,71 @@ impl Canvas {
         self.pixels.render().expect("Error while rendering image");
     }
 
+    /// Fills the pixels whose centers lie within `radius` of `center`, parts outside the image are skipped.
+    pub fn draw_disc(&mut self, center: Point2<f32>, radius: f32, color: Color) {
+        let min_x = f32::max(center.x - radius, 0.0) as u32;
+        let min_y = f32::max(center.y - radius, 0.0) as u32;
+        let max_x = f32::min(center.x + radius, self.width as f32 - 1.0);
+        let max_y = f32::min(center.y + radius, self.height as f32 - 1.0);
+
+        if max_x < 0.0 || max_y < 0.0 {
+            return;
+        }
+
+        for x in min_x..=max_x as u32 {
+            for y in min_y..=max_y as u32 {
+                let dx = x as f32 + 0.5 - center.x;
+                let dy = y as f32 + 0.5 - center.y;
+
+                if dx * dx + dy * dy <= radius * radius {
+                    self.set_pixel(color, y, x);
+                }
+            }
+        }
+    }
+
+    /// Pixels covered by an anti-aliased line of `width` pixels as `(column, row, t, coverage)`,
+    /// where `t` is the position along the line, 0 at `start` and 1 at `end`. Coverage falls
+    /// off linearly over one pixel at the edges.
+    pub fn line_coverage(&self, start: Point2<f32>, end: Point2<f32>, width: f32) -> Vec<(u32, u32, f32, f32)> {
+        let reach = 0.5 * width + 1.0;
+
+        let min_x = (start.x.min(end.x) - reach).max(0.0);
+        let min_y = (start.y.min(end.y) - reach).max(0.0);
+        let max_x = (start.x.max(end.x) + reach).min(self.width as f32 - 1.0);
+        let max_y = (start.y.max(end.y) + reach).min(self.height as f32 - 1.0);
+
+        let mut result = Vec::new();
+
+        if max_x < min_x || max_y < min_y {
+            return result;
+        }
+
+        let direction = end - start;
+        let length_squared = direction.norm_squared();
+
+        for x in min_x as u32..=max_x as u32 {
+            for y in min_y as u32..=max_y as u32 {
+                let center = Point2::new(x as f32 + 0.5, y as f32 + 0.5);
+
+                let t = if length_squared > 0.0 {
+                    ((center - start).dot(&direction) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+
+                let distance = (center - (start + direction * t)).norm();
+                let coverage = (0.5 * width + 0.5 - distance).clamp(0.0, 1.0);
+
+                if coverage > 0.0 {
+                    result.push((x, y, t, coverage));
+                }
+            }
+        }
+
+        result
+    }
+
     pub fn draw_rectangle(&mut self, up_left: Point2<u32>, down_right: Point2<u32>, color: Color) {
         let max_x = u32::min(down_right.x, self.width - 1);
         let max_y = u32::min(down_right.y, self.height - 1);
@@ -110,7 +216,8 @@ impl<'a> Pixel<'a> {
 
 
 impl<'a> PixelsIter<'a> {
-    fn new(canvas: &mut Canvas) -> PixelsIter {
+    #[allow(dead_code)]
+    fn new(canvas: &mut Canvas) -> PixelsIter<'_> {
         PixelsIter {
             data: canvas.pixels.frame_mut(),
             cur_pos: 0,
@@ -0,0 +1,45 @@
+extern crate nalgebra as na;
+
+use na::{Point3, UnitVector3, Vector3};
+
+
+/// Plane in world coordinates cutting away everything on the side its normal points to.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ClipPlane {
+    pub normal: UnitVector3<f32>,
+    pub offset: f32,
+}
+
+
+impl ClipPlane {
+    pub fn new(normal: &Vector3<f32>, offset: f32) -> ClipPlane {
+        ClipPlane {
+            normal: UnitVector3::new_normalize(*normal),
+            offset
+        }
+    }
+
+
+    /// Plane with normal given by spherical angles in radians. Azimuth 0 and
+    /// elevation 0 give a normal pointing towards the camera.
+    pub fn from_angles(azimuth: f32, elevation: f32, offset: f32) -> ClipPlane {
+        let normal = Vector3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos()
+        );
+
+        ClipPlane::new(&normal, offset)
+    }
+
+
+    /// Positive on the removed side of the plane.
+    pub fn signed_distance(&self, point: &Point3<f32>) -> f32 {
+        self.normal.dot(&point.coords) - self.offset
+    }
+
+
+    pub fn keeps(&self, point: &Point3<f32>) -> bool {
+        self.signed_distance(point) <= 0.0
+    }
+}
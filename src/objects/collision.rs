@@ -0,0 +1,164 @@
+extern crate nalgebra as na;
+
+use na::{Matrix3, Point3};
+
+use super::Ellipse;
+
+
+const MAX_PROJECTIONS: usize = 500;
+
+
+/// Relation of two solid ellipsoids.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Contact {
+    pub overlapping: bool,
+    /// Distance between the surfaces, zero for overlapping objects.
+    pub distance: f32,
+    /// Closest points of the two objects, the same point when they overlap.
+    pub closest: (Point3<f32>, Point3<f32>),
+    /// Approximate point of contact: midway between the closest points of separated objects,
+    /// inside both objects when they overlap.
+    pub point: Point3<f32>,
+}
+
+
+impl Ellipse {
+    /// Whether the solid ellipsoids share a point. Clipping planes are not taken into account.
+    pub fn overlaps(&self, other: &Ellipse) -> bool {
+        contact_function(self, other).0 <= 1.0
+    }
+
+
+    /// Separation distance, closest points and contact point of the two solid ellipsoids.
+    pub fn contact(&self, other: &Ellipse) -> Contact {
+        let (value, point) = contact_function(self, other);
+
+        if value <= 1.0 {
+            return Contact { overlapping: true, distance: 0.0, closest: (point, point), point };
+        }
+
+        // Alternating projections onto the two convex solids converge to the closest pair.
+        let mut first = self.closest_point(&other.center());
+        let mut second = other.closest_point(&first);
+
+        for _ in 0..MAX_PROJECTIONS {
+            let next_first = self.closest_point(&second);
+            let next_second = other.closest_point(&next_first);
+
+            let change = (next_first - first).norm() + (next_second - second).norm();
+            first = next_first;
+            second = next_second;
+
+            if change <= 1e-6 * (1.0 + (second - first).norm()) {
+                break;
+            }
+        }
+
+        Contact {
+            overlapping: false,
+            distance: (second - first).norm(),
+            closest: (first, second),
+            point: na::center(&first, &second),
+        }
+    }
+}
+
+
+/// Maximum of the Perram-Wertheim contact function over `lambda` in [0, 1] with the point
+/// where the two ellipsoids scaled by its square root would touch. The ellipsoids overlap
+/// exactly when the maximum is at most one.
+///
+/// The function is concave, so golden section search finds the maximum.
+fn contact_function(first: &Ellipse, second: &Ellipse) -> (f32, Point3<f32>) {
+    const ITERATIONS: usize = 60;
+    let ratio = (5.0_f64.sqrt() - 1.0) / 2.0;
+
+    let first_inv = first.shape_matrix().cast::<f64>().try_inverse().unwrap_or(Matrix3::zeros());
+    let second_inv = second.shape_matrix().cast::<f64>().try_inverse().unwrap_or(Matrix3::zeros());
+    let offset = (second.center() - first.center()).cast::<f64>();
+
+    let evaluate = |lambda: f64| {
+        let combined = first_inv * (1.0 - lambda) + second_inv * lambda;
+        let solved = combined.try_inverse().unwrap_or(Matrix3::zeros()) * offset;
+
+        (lambda * (1.0 - lambda) * offset.dot(&solved), solved)
+    };
+
+    let (mut low, mut high) = (0.0, 1.0);
+    let mut left = high - ratio * (high - low);
+    let mut right = low + ratio * (high - low);
+    let mut left_value = evaluate(left).0;
+    let mut right_value = evaluate(right).0;
+
+    for _ in 0..ITERATIONS {
+        if left_value < right_value {
+            low = left;
+            left = right;
+            left_value = right_value;
+            right = low + ratio * (high - low);
+            right_value = evaluate(right).0;
+        } else {
+            high = right;
+            right = left;
+            right_value = left_value;
+            left = high - ratio * (high - low);
+            left_value = evaluate(left).0;
+        }
+    }
+
+    let lambda = 0.5 * (low + high);
+    let (value, solved) = evaluate(lambda);
+    let point = first.center().cast::<f64>() + first_inv * solved * (1.0 - lambda);
+
+    (value as f32, point.cast())
+}
+
+
+#[cfg(test)]
+mod collision_tests {
+    use super::*;
+
+
+    fn sphere(radius: f32, x: f32) -> Ellipse {
+        Ellipse::new(1.0 / (radius * radius), 1.0 / (radius * radius), 1.0 / (radius * radius), &Point3::new(x, 0.0, 0.0), 0)
+    }
+
+
+    #[test]
+    fn separated_spheres() {
+        let contact = sphere(1.0, 0.0).contact(&sphere(2.0, 5.0));
+
+        assert!(!contact.overlapping);
+        assert!((contact.distance - 2.0).abs() < 1e-4);
+        assert!((contact.closest.0 - Point3::new(1.0, 0.0, 0.0)).norm() < 1e-4);
+        assert!((contact.point - Point3::new(2.0, 0.0, 0.0)).norm() < 1e-4);
+    }
+
+
+    #[test]
+    fn overlapping_spheres() {
+        let first = sphere(1.0, 0.0);
+        let second = sphere(2.0, 2.5);
+        let contact = first.contact(&second);
+
+        assert!(contact.overlapping);
+        assert_eq!(0.0, contact.distance);
+        assert!(first.contains(&contact.point) && second.contains(&contact.point));
+    }
+
+
+    #[test]
+    fn rotated_ellipsoids() {
+        // Long thin ellipsoid along x reaches the sphere only when it is not rotated away.
+        let mut needle = Ellipse::new(1.0 / 16.0, 4.0, 4.0, &Point3::origin(), 0);
+        let ball = sphere(1.0, 4.5);
+
+        assert!(needle.overlaps(&ball));
+
+        needle.rotation = na::vector![0.0, 0.0, std::f32::consts::FRAC_PI_2];
+        needle.recalculate();
+
+        assert!(!needle.overlaps(&ball));
+        assert!((needle.contact(&ball).distance - 3.0).abs() < 1e-3);
+    }
+}
@@ -1,66 +1,380 @@
-use std::ops;
+use std::{error, fmt, ops, str::FromStr};
 
 const MAX_VALUE: u8 = u8::MAX;
 const MAX_VALUE_F: f32 = MAX_VALUE as f32;
 
+
+/// Color in linear light. Components are not clamped, so values above 1.0
+/// are valid and represent HDR intensities until they are tone mapped.
+///
+/// Alpha is straight (not premultiplied) coverage in [0, 1].
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub struct Color {
     red: f32,
     green: f32,
-    blue: f32
+    blue: f32,
+    alpha: f32
 }
 
 
+#[derive(PartialEq, Clone, Debug)]
+pub enum ParseColorError {
+    InvalidHex(String),
+    UnknownName(String),
+}
+
+
+// Subset of CSS named colors, sRGB encoded.
+const CSS_COLORS: [(&str, [u8; 3]); 42] = [
+    ("black", [0, 0, 0]),
+    ("silver", [192, 192, 192]),
+    ("gray", [128, 128, 128]),
+    ("grey", [128, 128, 128]),
+    ("white", [255, 255, 255]),
+    ("maroon", [128, 0, 0]),
+    ("red", [255, 0, 0]),
+    ("purple", [128, 0, 128]),
+    ("fuchsia", [255, 0, 255]),
+    ("magenta", [255, 0, 255]),
+    ("green", [0, 128, 0]),
+    ("lime", [0, 255, 0]),
+    ("olive", [128, 128, 0]),
+    ("yellow", [255, 255, 0]),
+    ("navy", [0, 0, 128]),
+    ("blue", [0, 0, 255]),
+    ("teal", [0, 128, 128]),
+    ("aqua", [0, 255, 255]),
+    ("cyan", [0, 255, 255]),
+    ("orange", [255, 165, 0]),
+    ("brown", [165, 42, 42]),
+    ("pink", [255, 192, 203]),
+    ("gold", [255, 215, 0]),
+    ("indigo", [75, 0, 130]),
+    ("violet", [238, 130, 238]),
+    ("coral", [255, 127, 80]),
+    ("salmon", [250, 128, 114]),
+    ("tomato", [255, 99, 71]),
+    ("crimson", [220, 20, 60]),
+    ("khaki", [240, 230, 140]),
+    ("beige", [245, 245, 220]),
+    ("ivory", [255, 255, 240]),
+    ("lavender", [230, 230, 250]),
+    ("turquoise", [64, 224, 208]),
+    ("skyblue", [135, 206, 235]),
+    ("steelblue", [70, 130, 180]),
+    ("royalblue", [65, 105, 225]),
+    ("darkgray", [169, 169, 169]),
+    ("darkgrey", [169, 169, 169]),
+    ("lightgray", [211, 211, 211]),
+    ("lightgrey", [211, 211, 211]),
+    ("darkgreen", [0, 100, 0]),
+];
+
+
 impl Color {
+    /// Creates color from 8-bit sRGB encoded components.
     pub fn from_rgb(r: u8, g: u8, b: u8) -> Color {
+        Color::from_srgb_f(
+            (r as f32) / MAX_VALUE_F,
+            (g as f32) / MAX_VALUE_F,
+            (b as f32) / MAX_VALUE_F
+        )
+    }
+
+    /// Creates color from 8-bit sRGB encoded components and linear alpha.
+    pub fn from_rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color::from_rgb(r, g, b).with_alpha((a as f32) / MAX_VALUE_F)
+    }
+
+    pub fn transparent() -> Color {
+        Color::from_rgb_f(0.0, 0.0, 0.0).with_alpha(0.0)
+    }
+
+    /// Creates color from linear components.
+    pub fn from_rgb_f(r: f64, g: f64, b: f64) -> Color {
         Color {
-            red: (r as f32) / MAX_VALUE_F,
-            green: (g as f32) / MAX_VALUE_F,
-            blue: (b as f32) / MAX_VALUE_F
+            red: r as f32,
+            green: g as f32,
+            blue: b as f32,
+            alpha: 1.0
         }
     }
 
-    pub fn from_rgb_f(r: f64, g: f64, b: f64) -> Color {
+    /// Creates color from sRGB encoded components in [0, 1] range.
+    pub fn from_srgb_f(r: f32, g: f32, b: f32) -> Color {
+        Color {
+            red: srgb_to_linear(r),
+            green: srgb_to_linear(g),
+            blue: srgb_to_linear(b),
+            alpha: 1.0
+        }
+    }
+
+    /// Creates color from hue in degrees, saturation and value in [0, 1].
+    /// The conversion happens on sRGB encoded components.
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Color {
+        let chroma = v * s;
+        let [r, g, b] = hue_to_rgb(h, chroma);
+        let m = v - chroma;
+
+        Color::from_srgb_f(r + m, g + m, b + m)
+    }
+
+    /// Creates color from hue in degrees, saturation and lightness in [0, 1].
+    /// The conversion happens on sRGB encoded components.
+    pub fn from_hsl(h: f32, s: f32, l: f32) -> Color {
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        let [r, g, b] = hue_to_rgb(h, chroma);
+        let m = l - chroma / 2.0;
+
+        Color::from_srgb_f(r + m, g + m, b + m)
+    }
+
+    /// Hue in degrees, saturation and value of the sRGB encoded color.
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue_max_min();
+        let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+
+        (hue, saturation, max)
+    }
+
+    /// Hue in degrees, saturation and lightness of the sRGB encoded color.
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue_max_min();
+        let lightness = (max + min) / 2.0;
+        let saturation = if max == min { 0.0 } else { (max - min) / (1.0 - (2.0 * lightness - 1.0).abs()) };
+
+        (hue, saturation, lightness)
+    }
+
+    pub fn alpha(self) -> f32 {
+        self.alpha
+    }
+
+    pub fn with_alpha(self, alpha: f32) -> Color {
+        Color { alpha: alpha.clamp(0.0, 1.0), ..self }
+    }
+
+    /// Alpha as byte, it is stored without gamma encoding.
+    pub fn alpha_byte(self) -> u8 {
+        (self.alpha.clamp(0.0, 1.0) * MAX_VALUE_F + 0.5) as u8
+    }
+
+    /// Composites `self` over `below` (Porter-Duff "over" with straight alpha).
+    pub fn over(self, below: Color) -> Color {
+        let alpha = self.alpha + below.alpha * (1.0 - self.alpha);
+
+        if alpha <= 0.0 {
+            return Color::transparent();
+        }
+
+        let below_weight = below.alpha * (1.0 - self.alpha);
+        let blend = |top: f32, bottom: f32| (top * self.alpha + bottom * below_weight) / alpha;
+
         Color {
-            red: Color::trim_component(r as f32),
-            green: Color::trim_component(g as f32),
-            blue: Color::trim_component(b as f32)
+            red: blend(self.red, below.red),
+            green: blend(self.green, below.green),
+            blue: blend(self.blue, below.blue),
+            alpha
         }
     }
 
+    /// Linear interpolation in linear light, including alpha.
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+
+        Color {
+            red: mix(self.red, other.red),
+            green: mix(self.green, other.green),
+            blue: mix(self.blue, other.blue),
+            alpha: mix(self.alpha, other.alpha)
+        }
+    }
+
+    /// Interpolation of sRGB encoded components, like CSS `color-mix`.
+    /// Gives perceptually more even gradients than [`Color::lerp`].
+    pub fn mix(self, other: Color, t: f32) -> Color {
+        let [r1, g1, b1] = self.to_srgb_f();
+        let [r2, g2, b2] = other.to_srgb_f();
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+
+        Color::from_srgb_f(mix(r1, r2), mix(g1, g2), mix(b1, b2))
+            .with_alpha(mix(self.alpha, other.alpha))
+    }
+
+    /// `#rrggbb` or `#rrggbbaa` representation.
+    pub fn to_hex(self) -> String {
+        if self.alpha_byte() == MAX_VALUE {
+            format!("#{:02x}{:02x}{:02x}", self.red(), self.green(), self.blue())
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", self.red(), self.green(), self.blue(), self.alpha_byte())
+        }
+    }
+
+    /// sRGB encoded red component.
     pub fn red(self) -> u8 {
-        (self.red * MAX_VALUE_F) as u8
+        encode_component(self.red)
     }
 
+    /// sRGB encoded green component.
     pub fn green(self) -> u8 {
-        (self.green * MAX_VALUE_F) as u8
+        encode_component(self.green)
     }
 
+    /// sRGB encoded blue component.
     pub fn blue(self) -> u8 {
-        (self.blue * MAX_VALUE_F) as u8
+        encode_component(self.blue)
+    }
+
+    /// Relative luminance of the linear color (Rec. 709 weights).
+    pub fn luminance(self) -> f32 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
     }
 
-    fn trim_component(val: f32) -> f32 {
-        if val > 1.0 {
-            return 1.0;
+    /// Applies `f` to every linear component, alpha is kept.
+    pub fn map(self, f: impl Fn(f32) -> f32) -> Color {
+        Color {
+            red: f(self.red),
+            green: f(self.green),
+            blue: f(self.blue),
+            alpha: self.alpha
         }
-        
-        if val < 0.0 {
-            return  0.0;
+    }
+
+    fn to_srgb_f(self) -> [f32; 3] {
+        [self.red, self.green, self.blue].map(|c| linear_to_srgb(c.clamp(0.0, 1.0)))
+    }
+
+    fn hue_max_min(self) -> (f32, f32, f32) {
+        let [r, g, b] = self.to_srgb_f();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+
+        (hue, max, min)
+    }
+
+    fn from_hex(hex: &str) -> Result<Color, ParseColorError> {
+        let invalid = || ParseColorError::InvalidHex(hex.to_string());
+
+        let digits = &hex[1..];
+
+        // `from_str_radix` alone would also take signs like "+1".
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
         }
 
-        return  val;
+        let byte = |i: usize| u8::from_str_radix(&digits[2*i..2*i + 2], 16).map_err(|_| invalid());
+        let short = |i: usize| u8::from_str_radix(&digits[i..=i], 16).map(|v| v * 17).map_err(|_| invalid());
+
+        match digits.len() {
+            3 => Ok(Color::from_rgb(short(0)?, short(1)?, short(2)?)),
+            4 => Ok(Color::from_rgba(short(0)?, short(1)?, short(2)?, short(3)?)),
+            6 => Ok(Color::from_rgb(byte(0)?, byte(1)?, byte(2)?)),
+            8 => Ok(Color::from_rgba(byte(0)?, byte(1)?, byte(2)?, byte(3)?)),
+            _ => Err(invalid())
+        }
     }
 }
 
+
+/// Parses `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa` and CSS color names.
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.starts_with('#') {
+            return Color::from_hex(s);
+        }
+
+        let name = s.to_ascii_lowercase();
+
+        if name == "transparent" {
+            return Ok(Color::transparent());
+        }
+
+        CSS_COLORS.iter()
+            .find(|(css_name, _)| *css_name == name)
+            .map(|(_, [r, g, b])| Color::from_rgb(*r, *g, *b))
+            .ok_or(ParseColorError::UnknownName(s.to_string()))
+    }
+}
+
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseColorError::InvalidHex(value) => write!(f, "invalid hex color \"{}\"", value),
+            ParseColorError::UnknownName(value) => write!(f, "unknown color name \"{}\"", value),
+        }
+    }
+}
+
+
+impl error::Error for ParseColorError {}
+
+
+fn hue_to_rgb(h: f32, chroma: f32) -> [f32; 3] {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+
+    match h as u32 {
+        0 => [chroma, x, 0.0],
+        1 => [x, chroma, 0.0],
+        2 => [0.0, chroma, x],
+        3 => [0.0, x, chroma],
+        4 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    }
+}
+
+
+fn srgb_to_linear(val: f32) -> f32 {
+    if val <= 0.04045 {
+        val / 12.92
+    } else {
+        ((val + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+
+fn linear_to_srgb(val: f32) -> f32 {
+    if val <= 0.0031308 {
+        val * 12.92
+    } else {
+        1.055 * val.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+
+fn encode_component(val: f32) -> u8 {
+    let val = if val.is_nan() { 0.0 } else { val.clamp(0.0, 1.0) };
+
+    (linear_to_srgb(val) * MAX_VALUE_F + 0.5) as u8
+}
+
+
 impl ops::Mul<f32> for Color {
     type Output = Color;
 
     fn mul(self, rhs: f32) -> Self::Output {
         Color {
-            red: Color::trim_component(self.red * rhs),
-            green: Color::trim_component(self.green * rhs),
-            blue: Color::trim_component(self.blue * rhs),
+            red: self.red * rhs,
+            green: self.green * rhs,
+            blue: self.blue * rhs,
+            alpha: self.alpha
         }
     }
 }
@@ -89,14 +403,154 @@ impl ops::Mul<Color> for f64 {
     }
 }
 
+/// Component-wise product, e.g. light filtered by a colored medium. Keeps alpha of `self`.
+impl ops::Mul<Color> for Color {
+    type Output = Color;
+
+    fn mul(self, rhs: Color) -> Self::Output {
+        Color {
+            red: self.red * rhs.red,
+            green: self.green * rhs.green,
+            blue: self.blue * rhs.blue,
+            alpha: self.alpha
+        }
+    }
+}
+
+/// Adds light contributions, the result keeps the higher alpha.
 impl ops::Add for Color {
     type Output = Color;
 
     fn add(self, rhs: Self) -> Self::Output {
         Color {
-            red: Color::trim_component(self.red + rhs.red),
-            green: Color::trim_component(self.green + rhs.green),
-            blue: Color::trim_component(self.blue + rhs.blue)
+            red: self.red + rhs.red,
+            green: self.green + rhs.green,
+            blue: self.blue + rhs.blue,
+            alpha: f32::max(self.alpha, rhs.alpha)
         }
     }
 }
+
+
+#[cfg(test)]
+mod color_tests {
+    use super::*;
+
+
+    #[test]
+    fn srgb_bytes_round_trip() {
+        for value in [0, 1, 10, 55, 128, 200, 254, 255] {
+            let color = Color::from_rgb(value, value, value);
+            assert_eq!(value, color.red());
+            assert_eq!(value, color.green());
+            assert_eq!(value, color.blue());
+        }
+    }
+
+
+    #[test]
+    fn arithmetic_is_not_clamped() {
+        let color = Color::from_rgb_f(0.75, 0.5, 0.125) * 2.0_f32 + Color::from_rgb_f(0.5, 0.0, 0.0);
+
+        assert_eq!(Color::from_rgb_f(2.0, 1.0, 0.25), color);
+    }
+
+
+    #[test]
+    fn encoding_clamps_out_of_range_values() {
+        let color = Color::from_rgb_f(3.0, -1.0, 0.5);
+
+        assert_eq!(255, color.red());
+        assert_eq!(0, color.green());
+        assert_eq!(188, color.blue());
+    }
+
+
+    fn assert_bytes(expected: (u8, u8, u8), color: Color) {
+        assert_eq!(expected, (color.red(), color.green(), color.blue()));
+    }
+
+
+    #[test]
+    fn parse_hex() {
+        assert_bytes((255, 128, 0), "#ff8000".parse().unwrap());
+        assert_bytes((255, 128, 0), "#FF8000".parse().unwrap());
+        assert_bytes((255, 136, 0), "#f80".parse().unwrap());
+
+        let with_alpha: Color = "#10203080".parse().unwrap();
+        assert_bytes((16, 32, 48), with_alpha);
+        assert_eq!(128, with_alpha.alpha_byte());
+    }
+
+
+    #[test]
+    fn parse_invalid_hex() {
+        assert_eq!(Err(ParseColorError::InvalidHex("#12345".to_string())), "#12345".parse::<Color>());
+        assert_eq!(Err(ParseColorError::InvalidHex("#gg0000".to_string())), "#gg0000".parse::<Color>());
+        assert_eq!(Err(ParseColorError::InvalidHex("#+1+2+3".to_string())), "#+1+2+3".parse::<Color>());
+    }
+
+
+    #[test]
+    fn parse_css_names() {
+        assert_bytes((255, 165, 0), "orange".parse().unwrap());
+        assert_bytes((70, 130, 180), " SteelBlue ".parse().unwrap());
+        assert_eq!(0.0, "transparent".parse::<Color>().unwrap().alpha());
+        assert_eq!(Err(ParseColorError::UnknownName("blurple".to_string())), "blurple".parse::<Color>());
+    }
+
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!("#ff8000", "#ff8000".parse::<Color>().unwrap().to_hex());
+        assert_eq!("#10203080", "#10203080".parse::<Color>().unwrap().to_hex());
+    }
+
+
+    #[test]
+    fn hsv_conversions() {
+        assert_bytes((255, 0, 0), Color::from_hsv(0.0, 1.0, 1.0));
+        assert_bytes((0, 255, 0), Color::from_hsv(120.0, 1.0, 1.0));
+        assert_bytes((0, 0, 255), Color::from_hsv(240.0, 1.0, 1.0));
+        assert_bytes((255, 0, 0), Color::from_hsv(360.0, 1.0, 1.0));
+
+        let (h, s, v) = Color::from_rgb(255, 255, 0).to_hsv();
+        assert!((h - 60.0).abs() < 1e-3);
+        assert!((s - 1.0).abs() < 1e-3);
+        assert!((v - 1.0).abs() < 1e-3);
+    }
+
+
+    #[test]
+    fn hsl_conversions() {
+        assert_bytes((128, 128, 128), Color::from_hsl(0.0, 0.0, 128.0 / 255.0));
+        assert_bytes((0, 255, 255), Color::from_hsl(180.0, 1.0, 0.5));
+
+        let (h, s, l) = Color::from_rgb(0, 0, 128).to_hsl();
+        assert!((h - 240.0).abs() < 1e-3);
+        assert!((s - 1.0).abs() < 1e-3);
+        assert!((l - 128.0 / 255.0 / 2.0).abs() < 1e-3);
+    }
+
+
+    #[test]
+    fn over_compositing() {
+        let top = Color::from_rgb_f(1.0, 0.0, 0.0).with_alpha(0.5);
+        let bottom = Color::from_rgb_f(0.0, 0.0, 1.0);
+
+        assert_eq!(Color::from_rgb_f(0.5, 0.0, 0.5), top.over(bottom));
+        assert_eq!(top, top.over(Color::transparent()));
+        assert_eq!(bottom, Color::transparent().over(bottom));
+    }
+
+
+    #[test]
+    fn lerp_and_mix() {
+        let black = Color::from_rgb(0, 0, 0);
+        let white = Color::from_rgb(255, 255, 255);
+
+        assert_eq!(Color::from_rgb_f(0.5, 0.5, 0.5), black.lerp(white, 0.5));
+        assert!((127..=128).contains(&black.mix(white, 0.5).red()));
+        assert_bytes((255, 255, 255), black.mix(white, 1.0));
+    }
+}
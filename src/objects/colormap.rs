@@ -0,0 +1,96 @@
+use super::Color;
+
+
+// Tables hold sRGB encoded values, so the interpolation is perceptually even.
+const VIRIDIS: [[f32; 3]; 9] = [
+    [0.267004, 0.004874, 0.329415],
+    [0.282623, 0.140926, 0.457517],
+    [0.253935, 0.265254, 0.529983],
+    [0.206756, 0.371758, 0.553117],
+    [0.163625, 0.471133, 0.558148],
+    [0.127568, 0.566949, 0.550556],
+    [0.134692, 0.658636, 0.517649],
+    [0.477504, 0.821444, 0.318195],
+    [0.993248, 0.906157, 0.143936],
+];
+
+const COOLWARM: [[f32; 3]; 5] = [
+    [0.230, 0.299, 0.754],
+    [0.552, 0.690, 0.996],
+    [0.865, 0.865, 0.865],
+    [0.958, 0.604, 0.482],
+    [0.706, 0.016, 0.150],
+];
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Colormap {
+    Viridis,
+    Coolwarm,
+}
+
+
+impl Colormap {
+    pub const ALL: [Colormap; 2] = [Colormap::Viridis, Colormap::Coolwarm];
+
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Colormap::Viridis => "viridis",
+            Colormap::Coolwarm => "coolwarm",
+        }
+    }
+
+
+    /// Color for `t` in [0, 1]. Values outside of the range are clamped.
+    pub fn sample(self, t: f32) -> Color {
+        let table: &[[f32; 3]] = match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Coolwarm => &COOLWARM,
+        };
+
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let pos = t * (table.len() - 1) as f32;
+        let index = usize::min(pos as usize, table.len() - 2);
+        let frac = pos - index as f32;
+
+        let low = table[index];
+        let high = table[index + 1];
+        let mix = |i: usize| low[i] + (high[i] - low[i]) * frac;
+
+        Color::from_srgb_f(mix(0), mix(1), mix(2))
+    }
+
+
+    /// Maps `value` from the `[min, max]` range onto the colormap.
+    pub fn map(self, value: f32, min: f32, max: f32) -> Color {
+        if max <= min {
+            return self.sample(0.5);
+        }
+
+        self.sample((value - min) / (max - min))
+    }
+}
+
+
+#[cfg(test)]
+mod colormap_tests {
+    use super::*;
+
+
+    #[test]
+    fn ends_of_the_range() {
+        let first = Colormap::Viridis.sample(0.0);
+        assert_eq!((68, 1, 84), (first.red(), first.green(), first.blue()));
+
+        let last = Colormap::Coolwarm.sample(1.0);
+        assert_eq!((180, 4, 38), (last.red(), last.green(), last.blue()));
+    }
+
+
+    #[test]
+    fn values_outside_range_are_clamped() {
+        assert_eq!(Colormap::Viridis.sample(0.0), Colormap::Viridis.sample(-3.0));
+        assert_eq!(Colormap::Viridis.sample(1.0), Colormap::Viridis.map(10.0, 0.0, 2.0));
+    }
+}
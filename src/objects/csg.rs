@@ -0,0 +1,367 @@
+extern crate nalgebra as na;
+
+use na::{Point3, Vector3};
+
+use super::{Aabb, Ellipse};
+use super::ellipse::Surface;
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+
+/// Tree of boolean operations over objects of the scene, leaves refer to objects by index.
+#[derive(PartialEq, Clone, Debug)]
+pub enum CsgNode {
+    Object(usize),
+    Operation {
+        operation: CsgOperation,
+        left: Box<CsgNode>,
+        right: Box<CsgNode>,
+    },
+}
+
+
+/// Part of a view ray inside a CSG solid. Boundaries remember the object and surface seen there.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct CsgSpan {
+    pub start: f32,
+    pub end: f32,
+    pub entry: (usize, Surface),
+    pub exit: (usize, Surface),
+}
+
+
+impl CsgOperation {
+    pub const ALL: [CsgOperation; 3] = [CsgOperation::Union, CsgOperation::Intersection, CsgOperation::Difference];
+
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CsgOperation::Union => "union",
+            CsgOperation::Intersection => "intersection",
+            CsgOperation::Difference => "difference",
+        }
+    }
+
+
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+
+
+    /// Combines two sorted lists of disjoint spans by sweeping over their boundaries.
+    pub fn combine(self, left: &[CsgSpan], right: &[CsgSpan]) -> Vec<CsgSpan> {
+        struct Event {
+            z: f32,
+            is_left: bool,
+            is_entry: bool,
+            surface: (usize, Surface),
+        }
+
+        let mut events: Vec<Event> = Vec::with_capacity(2 * (left.len() + right.len()));
+
+        for (spans, is_left) in [(left, true), (right, false)] {
+            for span in spans {
+                events.push(Event { z: span.start, is_left, is_entry: true, surface: span.entry });
+                events.push(Event { z: span.end, is_left, is_entry: false, surface: span.exit });
+            }
+        }
+
+        events.sort_by(|a, b| a.z.total_cmp(&b.z));
+
+        let mut result = Vec::new();
+        let mut in_left = false;
+        let mut in_right = false;
+        let mut current_start: Option<(f32, (usize, Surface))> = None;
+
+        for event in events {
+            let was_inside = self.contains(in_left, in_right);
+
+            if event.is_left {
+                in_left = event.is_entry;
+            } else {
+                in_right = event.is_entry;
+            }
+
+            let is_inside = self.contains(in_left, in_right);
+
+            if !was_inside && is_inside {
+                current_start = Some((event.z, event.surface));
+            } else if was_inside && !is_inside {
+                if let Some((start, entry)) = current_start.take() {
+                    result.push(CsgSpan { start, end: event.z, entry, exit: event.surface });
+                }
+            }
+        }
+
+        result
+    }
+}
+
+
+impl CsgNode {
+    pub fn operation(operation: CsgOperation, left: CsgNode, right: CsgNode) -> CsgNode {
+        CsgNode::Operation { operation, left: Box::new(left), right: Box::new(right) }
+    }
+
+
+    /// Spans of the view ray through `(x, y)` lying inside the solid, sorted by depth.
+    pub fn spans(&self, objects: &[Ellipse], x: f32, y: f32, near_z: f32, far_z: f32) -> Vec<CsgSpan> {
+        match self {
+            CsgNode::Object(index) => objects[*index].visible_interval(x, y, near_z, far_z)
+                .map(|span| CsgSpan {
+                    start: span.start,
+                    end: span.end,
+                    entry: (*index, span.entry),
+                    exit: (*index, span.exit),
+                })
+                .into_iter()
+                .collect(),
+
+            CsgNode::Operation { operation, left, right } => {
+                let left = left.spans(objects, x, y, near_z, far_z);
+
+                if left.is_empty() && *operation != CsgOperation::Union {
+                    return left;
+                }
+
+                operation.combine(&left, &right.spans(objects, x, y, near_z, far_z))
+            }
+        }
+    }
+
+
+    /// Spans of the ray `origin + t * direction` lying inside the solid, measured in `t`
+    /// and sorted.
+    pub fn ray_spans(&self, objects: &[Ellipse], origin: &Point3<f32>, direction: &Vector3<f32>) -> Vec<CsgSpan> {
+        match self {
+            CsgNode::Object(index) => objects[*index].ray_span(origin, direction)
+                .map(|span| CsgSpan {
+                    start: span.start,
+                    end: span.end,
+                    entry: (*index, span.entry),
+                    exit: (*index, span.exit),
+                })
+                .into_iter()
+                .collect(),
+
+            CsgNode::Operation { operation, left, right } => {
+                let left = left.ray_spans(objects, origin, direction);
+
+                if left.is_empty() && *operation != CsgOperation::Union {
+                    return left;
+                }
+
+                operation.combine(&left, &right.ray_spans(objects, origin, direction))
+            }
+        }
+    }
+
+
+    /// Box holding the solid. Intersections and differences lie inside their left operand.
+    pub fn bounds(&self, objects: &[Ellipse]) -> Aabb {
+        match self {
+            CsgNode::Object(index) => objects[*index].bounding_box(),
+            CsgNode::Operation { operation: CsgOperation::Union, left, right } => left.bounds(objects).union(&right.bounds(objects)),
+            CsgNode::Operation { left, .. } => left.bounds(objects),
+        }
+    }
+
+
+    /// Indices of all objects in the tree.
+    pub fn objects(&self) -> Vec<usize> {
+        match self {
+            CsgNode::Object(index) => vec![*index],
+            CsgNode::Operation { left, right, .. } => {
+                let mut result = left.objects();
+                result.extend(right.objects());
+                result
+            }
+        }
+    }
+
+
+    /// Node at `path`, every step picks the left (0) or right (1) operand.
+    pub fn node_mut(&mut self, path: &[usize]) -> Option<&mut CsgNode> {
+        let Some((step, rest)) = path.split_first() else {
+            return Some(self);
+        };
+
+        match self {
+            CsgNode::Object(_) => None,
+            CsgNode::Operation { left, right, .. } => match step {
+                0 => left.node_mut(rest),
+                1 => right.node_mut(rest),
+                _ => None,
+            },
+        }
+    }
+
+
+    /// Whether `point` on the surface of object `index` is on the surface of the solid, that
+    /// is whether moving it into or out of the object changes which side of the solid it is on.
+    pub fn surface_contains(&self, objects: &[Ellipse], index: usize, point: &Point3<f32>) -> bool {
+        self.contains_with(objects, point, index, true) != self.contains_with(objects, point, index, false)
+    }
+
+
+    /// Whether `point` is inside the solid, taking it to be inside object `index` when `inside`.
+    fn contains_with(&self, objects: &[Ellipse], point: &Point3<f32>, index: usize, inside: bool) -> bool {
+        match self {
+            CsgNode::Object(i) if *i == index => inside,
+            CsgNode::Object(i) => objects[*i].contains(point),
+            CsgNode::Operation { operation, left, right } => operation.contains(
+                left.contains_with(objects, point, index, inside),
+                right.contains_with(objects, point, index, inside)
+            ),
+        }
+    }
+
+
+    /// Tree with object `index` removed and higher indices shifted down to stay valid, `None`
+    /// when nothing of the solid remains. Operands left without a solid when an intersection
+    /// or difference collapses are returned as well, so they can be shown on their own.
+    pub fn without_object(&self, index: usize) -> (Option<CsgNode>, Vec<usize>) {
+        match self {
+            CsgNode::Object(i) if *i == index => (None, Vec::new()),
+            CsgNode::Object(i) if *i > index => (Some(CsgNode::Object(i - 1)), Vec::new()),
+            CsgNode::Object(i) => (Some(CsgNode::Object(*i)), Vec::new()),
+
+            CsgNode::Operation { operation, left, right } => {
+                let (left, mut orphans) = left.without_object(index);
+                let (right, right_orphans) = right.without_object(index);
+                orphans.extend(right_orphans);
+
+                let tree = match (left, right) {
+                    (Some(left), Some(right)) => Some(CsgNode::operation(*operation, left, right)),
+                    (Some(left), None) => match operation {
+                        CsgOperation::Intersection => {
+                            orphans.extend(left.objects());
+                            None
+                        }
+                        _ => Some(left),
+                    },
+                    (None, Some(right)) => match operation {
+                        CsgOperation::Union => Some(right),
+                        _ => {
+                            orphans.extend(right.objects());
+                            None
+                        }
+                    },
+                    (None, None) => None,
+                };
+
+                (tree, orphans)
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod csg_tests {
+    use super::*;
+
+
+    fn span(start: f32, end: f32, object: usize) -> CsgSpan {
+        CsgSpan { start, end, entry: (object, Surface::Outer), exit: (object, Surface::Inner) }
+    }
+
+
+    #[test]
+    fn union_merges_overlapping_spans() {
+        let result = CsgOperation::Union.combine(&[span(0.0, 2.0, 0)], &[span(1.0, 3.0, 1), span(4.0, 5.0, 1)]);
+
+        assert_eq!(vec![
+            CsgSpan { start: 0.0, end: 3.0, entry: (0, Surface::Outer), exit: (1, Surface::Inner) },
+            span(4.0, 5.0, 1),
+        ], result);
+    }
+
+
+    #[test]
+    fn intersection_keeps_common_part() {
+        let result = CsgOperation::Intersection.combine(&[span(0.0, 2.0, 0)], &[span(1.0, 3.0, 1)]);
+
+        assert_eq!(vec![
+            CsgSpan { start: 1.0, end: 2.0, entry: (1, Surface::Outer), exit: (0, Surface::Inner) },
+        ], result);
+    }
+
+
+    #[test]
+    fn difference_starts_at_exit_of_subtracted_object() {
+        let result = CsgOperation::Difference.combine(&[span(0.0, 4.0, 0)], &[span(-1.0, 1.0, 1), span(2.0, 3.0, 1)]);
+
+        assert_eq!(vec![
+            CsgSpan { start: 1.0, end: 2.0, entry: (1, Surface::Inner), exit: (1, Surface::Outer) },
+            CsgSpan { start: 3.0, end: 4.0, entry: (1, Surface::Inner), exit: (0, Surface::Inner) },
+        ], result);
+    }
+
+
+    #[test]
+    fn removing_object_simplifies_tree() {
+        let tree = CsgNode::operation(
+            CsgOperation::Difference,
+            CsgNode::operation(CsgOperation::Union, CsgNode::Object(0), CsgNode::Object(1)),
+            CsgNode::Object(2)
+        );
+
+        assert_eq!(
+            (Some(CsgNode::operation(CsgOperation::Difference, CsgNode::Object(0), CsgNode::Object(1))), Vec::new()),
+            tree.without_object(1)
+        );
+        assert_eq!(
+            (Some(CsgNode::operation(CsgOperation::Union, CsgNode::Object(0), CsgNode::Object(1))), Vec::new()),
+            tree.without_object(2)
+        );
+    }
+
+
+    #[test]
+    fn surface_of_difference_leaves_out_removed_parts() {
+        let objects = [
+            Ellipse::new(0.25, 0.25, 0.25, &Point3::origin(), 0),
+            Ellipse::new(1.0, 1.0, 1.0, &Point3::new(2.0, 0.0, 0.0), 0),
+        ];
+        let tree = CsgNode::operation(CsgOperation::Difference, CsgNode::Object(0), CsgNode::Object(1));
+
+        // The outer surface is kept outside the subtracted ball and removed inside it.
+        assert!(tree.surface_contains(&objects, 0, &Point3::new(-2.0, 0.0, 0.0)));
+        assert!(!tree.surface_contains(&objects, 0, &Point3::new(2.0, 0.0, 0.0)));
+        // The subtracted ball shows only where it carves into the minuend.
+        assert!(tree.surface_contains(&objects, 1, &Point3::new(1.0, 0.0, 0.0)));
+        assert!(!tree.surface_contains(&objects, 1, &Point3::new(3.0, 0.0, 0.0)));
+    }
+
+
+    #[test]
+    fn collapsed_operations_leave_operands_alone() {
+        let difference = CsgNode::operation(
+            CsgOperation::Difference,
+            CsgNode::Object(0),
+            CsgNode::operation(CsgOperation::Union, CsgNode::Object(1), CsgNode::Object(2))
+        );
+
+        // Without the minuend the subtracted objects are shown on their own.
+        assert_eq!((None, vec![0, 1]), difference.without_object(0));
+
+        let intersection = CsgNode::operation(
+            CsgOperation::Union,
+            CsgNode::operation(CsgOperation::Intersection, CsgNode::Object(0), CsgNode::Object(1)),
+            CsgNode::Object(2)
+        );
+
+        assert_eq!((Some(CsgNode::Object(1)), vec![0]), intersection.without_object(1));
+    }
+}
@@ -0,0 +1,288 @@
+extern crate nalgebra as na;
+
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use na::{Point3, Vector3};
+
+use super::Ellipse;
+
+
+const EDGE_BISECTIONS: usize = 40;
+
+
+/// Chain of points in world coordinates.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Polyline {
+    pub points: Vec<Point3<f32>>,
+    /// Whether the last point connects back to the first one.
+    pub closed: bool,
+}
+
+
+/// Planar ellipse `center + axes[0] cos(t) + axes[1] sin(t)` in world coordinates,
+/// the axes are conjugate semi-diameters.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct EllipseCurve {
+    pub center: Point3<f32>,
+    pub axes: [Vector3<f32>; 2],
+}
+
+
+/// Edge of the parameter grid, `(row, column, horizontal)`. Horizontal edges run along the
+/// longitude from `(row, column)`, vertical ones along the latitude.
+type EdgeKey = (usize, usize, bool);
+
+
+impl Polyline {
+    /// Consecutive pairs of points, including the closing one.
+    pub fn segments(&self) -> impl Iterator<Item = (Point3<f32>, Point3<f32>)> + '_ {
+        let closing = match (self.closed, self.points.first(), self.points.last()) {
+            (true, Some(first), Some(last)) if self.points.len() > 2 => Some((*last, *first)),
+            _ => None,
+        };
+
+        self.points.windows(2).map(|pair| (pair[0], pair[1])).chain(closing)
+    }
+}
+
+
+impl EllipseCurve {
+    pub fn point(&self, t: f32) -> Point3<f32> {
+        self.center + self.axes[0] * t.cos() + self.axes[1] * t.sin()
+    }
+}
+
+
+impl Ellipse {
+    /// Curves where the surfaces of the two objects cross.
+    ///
+    /// The sign of `other`'s implicit function is sampled on a `resolution` by `2 * resolution`
+    /// latitude and longitude grid over this surface. Crossings on grid edges are found by
+    /// bisection, so every point lies on both surfaces, and joined cell by cell as in
+    /// marching squares.
+    pub fn intersection_curves(&self, other: &Ellipse, resolution: usize) -> Vec<Polyline> {
+        let rows = resolution.max(4);
+        let columns = 2 * rows;
+
+        let theta = |row: f32| PI * row / rows as f32;
+        let phi = |column: f32| 2.0 * PI * column / columns as f32;
+
+        let value_at = |t: f32, p: f32| {
+            let value = other.implicit_value(&self.surface_point(t, p));
+            // Zero is counted as outside, so every crossing changes sign.
+            if value == 0.0 { f32::EPSILON } else { value }
+        };
+
+        let values: Vec<Vec<f32>> = (0..=rows)
+            .map(|row| (0..columns).map(|column| value_at(theta(row as f32), phi(column as f32))).collect())
+            .collect();
+
+        let value = |row: usize, column: usize| values[row][column % columns];
+
+        let mut points: HashMap<EdgeKey, Point3<f32>> = HashMap::new();
+        let mut neighbours: HashMap<EdgeKey, Vec<EdgeKey>> = HashMap::new();
+
+        let mut edge_point = |key: EdgeKey| {
+            *points.entry(key).or_insert_with(|| {
+                let (row, column, horizontal) = key;
+                let (start, end) = if horizontal { ((row, column), (row, column + 1)) } else { ((row, column), (row + 1, column)) };
+
+                let (mut low, mut high) = (0.0, 1.0);
+                let start_inside = value(start.0, start.1) < 0.0;
+
+                let position = |s: f32| (
+                    theta(start.0 as f32 + s * (end.0 - start.0) as f32),
+                    phi(start.1 as f32 + s * (end.1 - start.1) as f32)
+                );
+
+                for _ in 0..EDGE_BISECTIONS {
+                    let middle = 0.5 * (low + high);
+                    let (t, p) = position(middle);
+
+                    if (value_at(t, p) < 0.0) == start_inside {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+
+                let (t, p) = position(0.5 * (low + high));
+                self.surface_point(t, p)
+            })
+        };
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let next = (column + 1) % columns;
+
+                let corners = [value(row, column), value(row, column + 1), value(row + 1, column + 1), value(row + 1, column)];
+                let edges: [EdgeKey; 4] = [(row, column, true), (row, next, false), (row + 1, column, true), (row, column, false)];
+
+                // Edge `i` joins corners `i` and `i + 1`.
+                let crossing: Vec<usize> = (0..4).filter(|i| (corners[*i] < 0.0) != (corners[(i + 1) % 4] < 0.0)).collect();
+
+                let pairs: Vec<(usize, usize)> = match crossing[..] {
+                    [first, second] => vec![(first, second)],
+                    [_, _, _, _] => {
+                        let center = corners.iter().sum::<f32>() / 4.0;
+
+                        if (center < 0.0) == (corners[0] < 0.0) {
+                            vec![(0, 1), (2, 3)]
+                        } else {
+                            vec![(3, 0), (1, 2)]
+                        }
+                    }
+                    _ => Vec::new(),
+                };
+
+                for (first, second) in pairs {
+                    let (a, b) = (normalize_edge(edges[first], columns), normalize_edge(edges[second], columns));
+                    edge_point(a);
+                    edge_point(b);
+                    neighbours.entry(a).or_default().push(b);
+                    neighbours.entry(b).or_default().push(a);
+                }
+            }
+        }
+
+        chain(&neighbours)
+            .into_iter()
+            .map(|(keys, closed)| {
+                let mut curve: Vec<Point3<f32>> = Vec::with_capacity(keys.len());
+
+                for key in keys {
+                    let point = points[&key];
+                    // Edges meeting at the poles give repeated points.
+                    if curve.last().is_none_or(|last| (last - point).norm() > 1e-6) {
+                        curve.push(point);
+                    }
+                }
+
+                Polyline { points: curve, closed }
+            })
+            .filter(|polyline| polyline.points.len() > 1)
+            .collect()
+    }
+}
+
+
+/// Writes curves as CSV with the columns `x,y,z,curve`, readable as a point cloud.
+pub fn save_curves(path: &Path, curves: &[Polyline]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writeln!(writer, "x,y,z,curve")?;
+
+    for (index, curve) in curves.iter().enumerate() {
+        for point in &curve.points {
+            writeln!(writer, "{},{},{},{}", point.x, point.y, point.z, index)?;
+        }
+    }
+
+    writer.flush()
+}
+
+
+/// Same edge with the column wrapped around the longitude.
+fn normalize_edge((row, column, horizontal): EdgeKey, columns: usize) -> EdgeKey {
+    (row, column % columns, horizontal)
+}
+
+
+/// Joins the edges into chains following the neighbours, open chains start at edges with a single neighbour.
+fn chain(neighbours: &HashMap<EdgeKey, Vec<EdgeKey>>) -> Vec<(Vec<EdgeKey>, bool)> {
+    let mut visited: HashSet<EdgeKey> = HashSet::new();
+    let mut result = Vec::new();
+
+    let mut starts: Vec<&EdgeKey> = neighbours.keys().collect();
+    starts.sort_by_key(|key| (neighbours[key].len() != 1, **key));
+
+    for start in starts {
+        if visited.contains(start) {
+            continue;
+        }
+
+        let mut keys = vec![*start];
+        visited.insert(*start);
+
+        let mut current = *start;
+        let mut closed = false;
+
+        while let Some(next) = neighbours[&current].iter().find(|key| !visited.contains(*key)) {
+            visited.insert(*next);
+            keys.push(*next);
+            current = *next;
+        }
+
+        if keys.len() > 2 && neighbours[&current].contains(start) {
+            closed = true;
+        }
+
+        result.push((keys, closed));
+    }
+
+    result
+}
+
+
+#[cfg(test)]
+mod curve_tests {
+    use super::*;
+
+
+    fn ellipse(a: f32, b: f32, c: f32, x: f32) -> Ellipse {
+        Ellipse::new(1.0 / (a * a), 1.0 / (b * b), 1.0 / (c * c), &Point3::new(x, 0.0, 0.0), 0)
+    }
+
+
+    #[test]
+    fn crossing_spheres_meet_in_circle() {
+        let curves = ellipse(1.0, 1.0, 1.0, 0.0).intersection_curves(&ellipse(1.0, 1.0, 1.0, 1.0), 32);
+
+        assert_eq!(1, curves.len());
+        assert!(curves[0].closed);
+
+        for point in &curves[0].points {
+            assert!((point.x - 0.5).abs() < 1e-4);
+            assert!((point.y.hypot(point.z) - 0.75_f32.sqrt()).abs() < 1e-4);
+        }
+    }
+
+
+    #[test]
+    fn needle_through_sphere_gives_two_loops() {
+        let needle = ellipse(2.0, 0.5, 0.5, 0.0);
+        let sphere = ellipse(1.0, 1.0, 1.0, 0.0);
+
+        let curves = needle.intersection_curves(&sphere, 32);
+
+        assert_eq!(2, curves.len());
+        for curve in &curves {
+            assert!(curve.closed);
+            for point in &curve.points {
+                assert!(needle.implicit_value(point).abs() < 1e-4);
+                assert!(sphere.implicit_value(point).abs() < 1e-4);
+            }
+        }
+    }
+
+
+    #[test]
+    fn separated_objects_have_no_curve() {
+        assert!(ellipse(1.0, 1.0, 1.0, 0.0).intersection_curves(&ellipse(1.0, 1.0, 1.0, 3.0), 16).is_empty());
+    }
+
+
+    #[test]
+    fn segments_close_loop() {
+        let polyline = Polyline {
+            points: vec![Point3::origin(), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)],
+            closed: true,
+        };
+
+        assert_eq!(3, polyline.segments().count());
+    }
+}
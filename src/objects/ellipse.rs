@@ -1,41 +1,105 @@
 extern crate nalgebra as na;
 
-use crate::math::quadratic_equation;
-use na::{Matrix4, Point3, Vector3, Vector4, UnitVector3};
+use crate::math::{chi_square, directions, ellipsoid_geometry, quadratic_equation};
+use na::{Matrix3, Matrix4, Point3, Vector2, Vector3, Vector4, UnitVector3};
 
-use super::Color;
+use super::{Aabb, ClipPlane, Color};
+use super::curve::EllipseCurve;
+use super::primitive::Hittable;
 
 
 pub struct Ellipse {
     ellipse_m: Matrix4<f32>,
     result_m: Matrix4<f32>,
+    model_m: Matrix4<f32>,
+    model_inv: Matrix4<f32>,
 
     pub rotation: Vector3<f32>,
     pub position: Point3<f32>,
     pub scale: f32,
 
-    pub color: Color,
+    /// Index of the material in the scene's material library.
+    pub material: usize,
+
+    /// Density used by X-ray rendering, scales the absorption per unit of length.
+    pub density: f32,
+    /// Per channel absorption coefficients used by X-ray rendering.
+    pub absorption: Color,
+
+    /// Planes cutting away parts of the object, in world coordinates.
+    pub clip_planes: Vec<ClipPlane>,
+}
+
+
+/// Part of the object visible at the hit point.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Surface {
+    /// Outside of the ellipsoid.
+    Outer,
+    /// Inner side of the ellipsoid shell, visible through a cut.
+    Inner,
+    /// Flat face closing the solid where it was cut, with the face normal.
+    Cap(UnitVector3<f32>),
+}
+
+
+/// How the object looks where its front is clipped away.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum CutMode {
+    /// Object is filled, cuts are closed with caps.
+    Solid,
+    /// Object is an infinitely thin shell, cuts reveal its inner side.
+    Hollow,
+}
+
+
+/// Part of a view ray lying inside the solid object.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Span {
+    pub start: f32,
+    pub end: f32,
+    /// Surface seen at `start`.
+    pub entry: Surface,
+    /// Surface seen at `end` when everything in front of it is removed.
+    pub exit: Surface,
 }
 
 
 pub enum HitRecord {
-    Hit{z: f32},
+    Hit{z: f32, surface: Surface},
     Miss
 }
 
 
+/// Principal curvature invariants of the surface at a single point.
+///
+/// Signs follow the outward normal, so convex surfaces have positive mean curvature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Curvature {
+    pub gaussian: f32,
+    pub mean: f32,
+}
+
+
 impl Ellipse {
-    pub fn new(a: f32, b: f32, c: f32, pos: &Point3<f32>, col: Color) -> Ellipse {
+    pub fn new(a: f32, b: f32, c: f32, pos: &Point3<f32>, material: usize) -> Ellipse {
         let mut res = Ellipse {
             ellipse_m: Matrix4::from_diagonal(&Vector4::new(a, b, c, -1.0_f32)),
 
             rotation: Vector3::zeros(),
-            position: pos.clone(),
+            position: *pos,
             scale: 1.0,
 
             result_m: Matrix4::zeros(),
+            model_m: Matrix4::identity(),
+            model_inv: Matrix4::identity(),
+
+            material,
 
-            color: col,
+            density: 0.5,
+            absorption: Color::from_rgb_f(1.0, 1.0, 1.0),
+
+            clip_planes: Vec::new(),
         };
 
         res.recalculate();
@@ -44,27 +108,308 @@ impl Ellipse {
     }
 
 
+    /// Confidence ellipsoid of a 3D Gaussian: the region holding a sample with probability
+    /// `confidence`. Returns `None` unless `covariance` is positive definite.
+    pub fn from_covariance(mean: &Point3<f32>, covariance: &Matrix3<f32>, confidence: f32, material: usize) -> Option<Ellipse> {
+        let squared_distance = chi_square::quantile(confidence as f64) as f32;
+        let shape = (covariance * squared_distance).try_inverse()?;
+
+        Ellipse::from_shape_matrix(mean, &shape, material)
+    }
+
+
+    /// Ellipsoid of points `p` with `(p - center)^T shape (p - center) = 1`.
+    /// Returns `None` unless `shape` is positive definite.
+    pub fn from_shape_matrix(center: &Point3<f32>, shape: &Matrix3<f32>, material: usize) -> Option<Ellipse> {
+        let symmetric = (shape + shape.transpose()) * 0.5;
+        let eigen = symmetric.symmetric_eigen();
+
+        let largest = eigen.eigenvalues.max();
+        if !eigen.eigenvalues.iter().all(|value| *value > largest * 1e-9) {
+            return None;
+        }
+
+        let mut axes = eigen.eigenvectors;
+        if axes.determinant() < 0.0 {
+            axes.set_column(2, &-axes.column(2));
+        }
+
+        let coefficients = eigen.eigenvalues;
+        let mut res = Ellipse::new(coefficients.x, coefficients.y, coefficients.z, &Point3::origin(), material);
+
+        res.rotation = rotation_angles(&axes);
+        // The model matrix rotates after translating, so the center is rotated back first.
+        res.position = Point3::from(axes.transpose() * center.coords);
+        res.recalculate();
+
+        Some(res)
+    }
+
+
+    /// Covariance of the Gaussian whose `confidence` ellipsoid is this object, inverse of `from_covariance`.
+    pub fn covariance(&self, confidence: f32) -> Matrix3<f32> {
+        let squared_distance = chi_square::quantile(confidence as f64) as f32;
+        let axes = self.semi_axes() * self.scale;
+        let variances = axes.component_mul(&axes) / squared_distance;
+        let rotation = self.model_m.fixed_view::<3, 3>(0, 0) / self.scale;
+
+        rotation * Matrix3::from_diagonal(&variances) * rotation.transpose()
+    }
+
+
+    /// Center of the object in world coordinates.
+    pub fn center(&self) -> Point3<f32> {
+        self.model_m.transform_point(&Point3::origin())
+    }
+
+
     pub fn recalculate(&mut self) {
         let model_m = self.rotation_matrix() * self.scale_matrix() * self.translation_matrix();
         let model_inv = model_m.try_inverse().unwrap();
 
         self.result_m = model_inv.transpose() * self.ellipse_m * model_inv;
+        self.model_m = model_m;
+        self.model_inv = model_inv;
+    }
+
+
+    /// Transformation from object to world coordinates, valid after `recalculate`.
+    pub fn model_matrix(&self) -> &Matrix4<f32> {
+        &self.model_m
+    }
+
+
+    /// Lengths of the semi-axes before scaling.
+    pub fn semi_axes(&self) -> Vector3<f32> {
+        Vector3::new(
+            1.0 / self.ellipse_m[(0, 0)].sqrt(),
+            1.0 / self.ellipse_m[(1, 1)].sqrt(),
+            1.0 / self.ellipse_m[(2, 2)].sqrt()
+        )
+    }
+
+
+    /// World point for polar angle `theta` measured from the object's `+y` pole and longitude `phi`.
+    pub fn surface_point(&self, theta: f32, phi: f32) -> Point3<f32> {
+        let axes = self.semi_axes();
+        let local = Point3::new(
+            axes.x * theta.sin() * phi.cos(),
+            axes.y * theta.cos(),
+            axes.z * theta.sin() * phi.sin()
+        );
+
+        self.model_m.transform_point(&local)
+    }
+
+
+    /// Implicit function of the surface in normalized object coordinates: negative inside,
+    /// zero on the surface and positive outside.
+    pub fn implicit_value(&self, point: &Point3<f32>) -> f32 {
+        let local = self.model_inv.transform_point(point);
+
+        local.coords.component_div(&self.semi_axes()).norm_squared() - 1.0
+    }
+
+
+    /// Whether `point` lies inside or on the surface. Clipping planes are not taken into account.
+    pub fn contains(&self, point: &Point3<f32>) -> bool {
+        self.implicit_value(point) <= 0.0
+    }
+
+
+    /// Point of the surface closest to `point`.
+    pub fn closest_point(&self, point: &Point3<f32>) -> Point3<f32> {
+        let local = self.model_inv.transform_point(point);
+        let axes = self.semi_axes();
+
+        let closest = ellipsoid_geometry::closest_point(
+            [axes.x as f64, axes.y as f64, axes.z as f64],
+            [local.x as f64, local.y as f64, local.z as f64]
+        );
+
+        self.model_m.transform_point(&Point3::new(closest[0] as f32, closest[1] as f32, closest[2] as f32))
+    }
+
+
+    /// Euclidean distance to the surface, negative inside.
+    pub fn signed_distance(&self, point: &Point3<f32>) -> f32 {
+        let distance = (point - self.closest_point(point)).norm();
+
+        if self.contains(point) { -distance } else { distance }
+    }
+
+
+    /// Smallest axis aligned box holding the transformed ellipsoid.
+    pub fn bounding_box(&self) -> Aabb {
+        // Half extent along world axis `i` is the length of row `i` of the linear part
+        // of the model matrix with columns scaled by the semi-axes.
+        let linear = self.model_m.fixed_view::<3, 3>(0, 0) * Matrix3::from_diagonal(&self.semi_axes());
+        let half_size = Vector3::from_fn(|i, _| linear.row(i).norm());
+
+        Aabb::from_center(&self.center(), &half_size)
+    }
+
+
+    /// Matrix `S` describing the surface as the points `p` with `(p - center)^T S (p - center) = 1`.
+    pub fn shape_matrix(&self) -> Matrix3<f32> {
+        let inverse = self.model_inv.fixed_view::<3, 3>(0, 0);
+        let axes = self.semi_axes();
+
+        inverse.transpose() * Matrix3::from_diagonal(&axes.map(|axis| 1.0 / (axis * axis))) * inverse
+    }
+
+
+    /// Curve on the surface where view rays touch it, seen as the outline of the object.
+    ///
+    /// The view ray through `(x, y)` meets the surface where `a z^2 + 2 w z + c = 0` with
+    /// coefficients taken from `result_m`. It touches the surface where the discriminant
+    /// `w^2 - a c` vanishes, which is a conic in `(x, y)`, and the depth of the touching
+    /// point `-w / a` is affine in `(x, y)`.
+    pub fn outline(&self) -> Option<EllipseCurve> {
+        let m = self.result_m.cast::<f64>();
+
+        let a = m[(2, 2)];
+        let w = Vector3::new(m[(2, 0)], m[(2, 1)], m[(2, 3)]);
+        let c = Matrix3::new(
+            m[(0, 0)], m[(0, 1)], m[(0, 3)],
+            m[(1, 0)], m[(1, 1)], m[(1, 3)],
+            m[(3, 0)], m[(3, 1)], m[(3, 3)]
+        );
+
+        // Positive inside the outline, where the view rays cross the surface twice.
+        let conic = w * w.transpose() - c * a;
+
+        let quadratic = conic.fixed_view::<2, 2>(0, 0).into_owned();
+        let linear = Vector2::new(conic[(0, 2)], conic[(1, 2)]);
+
+        let center = -(quadratic.try_inverse()? * linear);
+        let constant = conic[(2, 2)] + linear.dot(&center);
+
+        let eigen = (quadratic / -constant).symmetric_eigen();
+        if eigen.eigenvalues.iter().any(|value| *value <= 0.0) {
+            return None;
+        }
+
+        let depth = |x: f64, y: f64, homogeneous: f64| -(w.x * x + w.y * y + w.z * homogeneous) / a;
+        let axis = |i: usize| {
+            let v = eigen.eigenvectors.column(i) / eigen.eigenvalues[i].sqrt();
+            Vector3::new(v.x, v.y, depth(v.x, v.y, 0.0)).cast()
+        };
+
+        Some(EllipseCurve {
+            center: Point3::new(center.x, center.y, depth(center.x, center.y, 1.0)).cast(),
+            axes: [axis(0), axis(1)],
+        })
+    }
+
+
+    /// Curve where `plane` cuts the surface, `None` when the plane misses the object.
+    pub fn cross_section(&self, plane: &ClipPlane) -> Option<EllipseCurve> {
+        let model = self.model_m.cast::<f64>();
+        let linear = model.fixed_view::<3, 3>(0, 0) * Matrix3::from_diagonal(&self.semi_axes().cast());
+        let translation = model.fixed_view::<3, 1>(0, 3).into_owned();
+        let plane_normal = plane.normal.into_inner().cast::<f64>();
+
+        // In coordinates where the object is the unit sphere the plane cuts a circle.
+        let normal = linear.transpose() * plane_normal;
+        let length = normal.norm();
+        let distance = (plane.offset as f64 - plane_normal.dot(&translation)) / length;
+
+        if length == 0.0 || distance.abs() >= 1.0 {
+            return None;
+        }
+
+        let normal = normal / length;
+        let radius = (1.0 - distance * distance).sqrt();
+        let (first, second) = directions::perpendicular_axes(&normal);
+
+        Some(EllipseCurve {
+            center: Point3::from(linear * normal * distance + translation).cast(),
+            axes: [(linear * first * radius).cast(), (linear * second * radius).cast()],
+        })
+    }
+
+
+    pub fn volume(&self) -> f32 {
+        let axes = self.semi_axes() * self.scale;
+
+        4.0 / 3.0 * std::f32::consts::PI * axes.x * axes.y * axes.z
+    }
+
+
+    /// Surface area, integrated numerically since it has no elementary closed form.
+    pub fn surface_area(&self) -> f32 {
+        let axes = self.semi_axes() * self.scale;
+
+        ellipsoid_geometry::surface_area([axes.x as f64, axes.y as f64, axes.z as f64]) as f32
+    }
+
+
+    /// Surface coordinates of a point on the ellipsoid: `u` is the longitude around the
+    /// object's `y` axis and `v` the latitude from its `+y` pole, both in [0, 1].
+    pub fn surface_uv(&self, pos: &Point3<f32>) -> (f32, f32) {
+        let local = self.model_inv.transform_point(pos);
+
+        let x = local.x * self.ellipse_m[(0, 0)].sqrt();
+        let y = local.y * self.ellipse_m[(1, 1)].sqrt();
+        let z = local.z * self.ellipse_m[(2, 2)].sqrt();
+        let len = (x*x + y*y + z*z).sqrt();
+
+        if len == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let u = 0.5 + f32::atan2(z, x) / (2.0 * std::f32::consts::PI);
+        let v = (y / len).clamp(-1.0, 1.0).acos() / std::f32::consts::PI;
+
+        (u, v)
     }
 
 
     pub fn normal(&self, pos: &Point3<f32>) -> UnitVector3<f32> {
+        UnitVector3::new_normalize(self.gradient(pos))
+    }
+
+
+    /// Gradient of the implicit function `F(p) = p^T M p` at `pos`.
+    pub fn gradient(&self, pos: &Point3<f32>) -> Vector3<f32> {
         let m = &self.result_m;
 
-        UnitVector3::new_normalize(
-            Vector3::new(
-                2.0*m[(0, 0)]*pos.x + (m[(1, 0)] + m[(0, 1)])*pos.y + (m[(2, 0)] + m[(0, 2)])*pos.z + m[(3, 0)] + m[(0, 3)],
-                2.0*m[(1, 1)]*pos.y + (m[(0, 1)] + m[(1, 0)])*pos.x + (m[(2, 1)] + m[(1, 2)])*pos.z + m[(3, 1)] + m[(1, 3)],
-                2.0*m[(2, 2)]*pos.z + (m[(0, 2)] + m[(2, 0)])*pos.x + (m[(1, 2)] + m[(2, 1)])*pos.y + m[(3, 2)] + m[(2, 3)]
-            )
+        Vector3::new(
+            2.0*m[(0, 0)]*pos.x + (m[(1, 0)] + m[(0, 1)])*pos.y + (m[(2, 0)] + m[(0, 2)])*pos.z + m[(3, 0)] + m[(0, 3)],
+            2.0*m[(1, 1)]*pos.y + (m[(0, 1)] + m[(1, 0)])*pos.x + (m[(2, 1)] + m[(1, 2)])*pos.z + m[(3, 1)] + m[(1, 3)],
+            2.0*m[(2, 2)]*pos.z + (m[(0, 2)] + m[(2, 0)])*pos.x + (m[(1, 2)] + m[(2, 1)])*pos.y + m[(3, 2)] + m[(2, 3)]
         )
     }
 
 
+    /// Hessian of the implicit function. For a quadric it does not depend on the position.
+    pub fn hessian(&self) -> Matrix3<f32> {
+        let m = self.result_m.fixed_view::<3, 3>(0, 0);
+
+        m + m.transpose()
+    }
+
+
+    /// Gaussian and mean curvature at a point lying on the surface
+    /// (Goldman, "Curvature formulas for implicit curves and surfaces").
+    pub fn curvature(&self, pos: &Point3<f32>) -> Curvature {
+        let grad = self.gradient(pos);
+        let hess = self.hessian();
+
+        let grad_len_sq = grad.norm_squared();
+        let grad_len = grad_len_sq.sqrt();
+
+        let adjoint = adjugate(&hess);
+
+        let gaussian = (grad.transpose() * adjoint * grad)[(0, 0)] / (grad_len_sq * grad_len_sq);
+        let mean = (grad_len_sq * hess.trace() - (grad.transpose() * hess * grad)[(0, 0)])
+            / (2.0 * grad_len_sq * grad_len);
+
+        Curvature { gaussian, mean }
+    }
+
+
     fn translation_matrix(&self) -> Matrix4<f32> {
         Matrix4::new(
             1.0, 0.0, 0.0, self.position.x,
@@ -108,7 +453,8 @@ impl Ellipse {
     }
 
 
-    pub fn hit(&self, x: f32, y:f32) -> HitRecord {
+    /// Entry and exit depth of the view ray through `(x, y)`, ordered by `z`.
+    pub fn interval(&self, x: f32, y: f32) -> Option<(f32, f32)> {
         let m = &self.result_m;
 
         let a: f32 = m[(2, 2)];
@@ -126,14 +472,169 @@ impl Ellipse {
         
         match quadratic_equation::solve(a, b, c) {
             quadratic_equation::Solutions::Two(v1, v2) =>
-                HitRecord::Hit { z: f32::min(v1, v2) },
+                Some((f32::min(v1, v2), f32::max(v1, v2))),
             
-            quadratic_equation::Solutions::One(v) => HitRecord::Hit { z: v },
+            quadratic_equation::Solutions::One(v) => Some((v, v)),
+
+            quadratic_equation::Solutions::None => None
+        }
+    }
+
+
+    pub fn hit(&self, x: f32, y:f32) -> HitRecord {
+        match self.interval(x, y) {
+            Some((near, _)) => HitRecord::Hit { z: near, surface: Surface::Outer },
+            None => HitRecord::Miss
+        }
+    }
+
+
+    /// Hit of the view ray through `(x, y)` restricted to depths between `near_z` and `far_z`
+    /// and to the kept side of all clipping planes.
+    pub fn hit_clipped(&self, x: f32, y: f32, near_z: f32, far_z: f32, mode: CutMode) -> HitRecord {
+        let Some((entry, exit)) = self.interval(x, y) else {
+            return HitRecord::Miss;
+        };
+
+        match mode {
+            CutMode::Solid => match self.clip_interval(x, y, entry, exit, near_z, far_z) {
+                Some(span) => HitRecord::Hit { z: span.start, surface: span.entry },
+                None => HitRecord::Miss
+            },
+            CutMode::Hollow => {
+                let visible = |z: f32| {
+                    let point = Point3::new(x, y, z);
+                    z >= near_z && z <= far_z && self.clip_planes.iter().all(|plane| plane.keeps(&point))
+                };
+
+                if visible(entry) {
+                    HitRecord::Hit { z: entry, surface: Surface::Outer }
+                } else if visible(exit) {
+                    HitRecord::Hit { z: exit, surface: Surface::Inner }
+                } else {
+                    HitRecord::Miss
+                }
+            }
+        }
+    }
+
+
+    /// Part of the view ray through `(x, y)` lying inside the clipped solid.
+    pub fn visible_interval(&self, x: f32, y: f32, near_z: f32, far_z: f32) -> Option<Span> {
+        let (entry, exit) = self.interval(x, y)?;
+
+        self.clip_interval(x, y, entry, exit, near_z, far_z)
+    }
+
+
+    /// Part of the ray `origin + t * direction` inside the solid cut by its clipping planes,
+    /// with `start` and `end` given as values of `t`.
+    pub fn ray_span(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Option<Span> {
+        let m = &self.result_m;
+        let o = origin.to_homogeneous();
+        let d = direction.to_homogeneous();
+
+        let (entry, exit) = match quadratic_equation::solve(d.dot(&(m * d)), 2.0 * d.dot(&(m * o)), o.dot(&(m * o))) {
+            quadratic_equation::Solutions::Two(v1, v2) => (f32::min(v1, v2), f32::max(v1, v2)),
+            quadratic_equation::Solutions::One(v) => (v, v),
+            quadratic_equation::Solutions::None => return None
+        };
+
+        let mut span = Span {
+            start: entry,
+            end: exit,
+            entry: Surface::Outer,
+            exit: Surface::Inner,
+        };
+
+        for plane in &self.clip_planes {
+            // Along the ray the plane keeps `rate * t <= limit`.
+            let rate = plane.normal.dot(direction);
+            let limit = plane.offset - plane.normal.dot(&origin.coords);
+
+            if rate > 0.0 {
+                if limit / rate < span.end {
+                    span.end = limit / rate;
+                    span.exit = Surface::Cap(-plane.normal);
+                }
+            } else if rate < 0.0 {
+                if limit / rate > span.start {
+                    span.start = limit / rate;
+                    span.entry = Surface::Cap(plane.normal);
+                }
+            } else if limit < 0.0 {
+                return None;
+            }
+        }
+
+        if span.start <= span.end {
+            Some(span)
+        } else {
+            None
+        }
+    }
+
+
+    fn clip_interval(&self, x: f32, y: f32, entry: f32, exit: f32, near_z: f32, far_z: f32) -> Option<Span> {
+        let mut span = Span {
+            start: entry,
+            end: exit,
+            entry: Surface::Outer,
+            exit: Surface::Inner,
+        };
+
+        if near_z > span.start {
+            span.start = near_z;
+            span.entry = Surface::Cap(-Vector3::z_axis());
+        }
+
+        if far_z < span.end {
+            span.end = far_z;
+            span.exit = Surface::Cap(-Vector3::z_axis());
+        }
+
+        for plane in &self.clip_planes {
+            // Along the ray the plane keeps `normal.z * z <= limit`.
+            let limit = plane.offset - plane.normal.x * x - plane.normal.y * y;
+
+            if plane.normal.z > 0.0 {
+                let plane_z = limit / plane.normal.z;
+
+                if plane_z < span.end {
+                    span.end = plane_z;
+                    span.exit = Surface::Cap(-plane.normal);
+                }
+            } else if plane.normal.z < 0.0 {
+                let plane_z = limit / plane.normal.z;
+
+                if plane_z > span.start {
+                    span.start = plane_z;
+                    span.entry = Surface::Cap(plane.normal);
+                }
+            } else if limit < 0.0 {
+                return None;
+            }
+        }
 
-            quadratic_equation::Solutions::None => HitRecord::Miss
+        if span.start <= span.end {
+            Some(span)
+        } else {
+            None
         }
     }
 
+    pub fn a(&self) -> f32 {
+        self.ellipse_m[(0, 0)]
+    }
+
+    pub fn b(&self) -> f32 {
+        self.ellipse_m[(1, 1)]
+    }
+
+    pub fn c(&self) -> f32 {
+        self.ellipse_m[(2, 2)]
+    }
+
     pub fn set_a(&mut self, a: f32) {
         self.ellipse_m[(0, 0)] = a;
     }
@@ -152,6 +653,54 @@ impl Ellipse {
 }
 
 
+fn adjugate(m: &Matrix3<f32>) -> Matrix3<f32> {
+    let cofactor = |r1: usize, r2: usize, c1: usize, c2: usize| {
+        m[(r1, c1)] * m[(r2, c2)] - m[(r1, c2)] * m[(r2, c1)]
+    };
+
+    Matrix3::new(
+        cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2),
+        -cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2),
+        cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1)
+    )
+}
+
+
+/// Angles of `rotation_matrix` producing the rotation `m`, which composes them as `ry * rx * rz`.
+fn rotation_angles(m: &Matrix3<f32>) -> Vector3<f32> {
+    let x = (-m[(1, 2)]).clamp(-1.0, 1.0).asin();
+
+    if m[(1, 2)].abs() < 0.99999 {
+        Vector3::new(x, f32::atan2(m[(0, 2)], m[(2, 2)]), f32::atan2(m[(1, 0)], m[(1, 1)]))
+    } else {
+        // Gimbal lock, only the sum of the y and z rotations is determined.
+        Vector3::new(x, f32::atan2(-m[(2, 0)], m[(0, 0)]), 0.0)
+    }
+}
+
+
+impl Hittable for Ellipse {
+    fn ray_span(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Option<Span> {
+        Ellipse::ray_span(self, origin, direction)
+    }
+
+
+    fn normal(&self, point: &Point3<f32>) -> UnitVector3<f32> {
+        Ellipse::normal(self, point)
+    }
+
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.bounding_box())
+    }
+
+
+    fn surface_uv(&self, point: &Point3<f32>) -> (f32, f32) {
+        Ellipse::surface_uv(self, point)
+    }
+}
+
+
 #[cfg(test)]
 mod ellipsoid_tests {
     use na::vector;
@@ -163,7 +712,7 @@ mod ellipsoid_tests {
     fn normal_in_the_middle() {
         let ellipoid = Ellipse::new(1.0, 2.0, 3.0,
             &Point3::new(0.0, 0.0, 0.0),
-            Color::from_rgb(0, 0, 0)
+            0
         );
 
         let x = 0.0;
@@ -172,11 +721,284 @@ mod ellipsoid_tests {
         let hit = ellipoid.hit(x, y);
 
         match hit {
-            HitRecord::Hit { z } => {
+            HitRecord::Hit { z, .. } => {
                 let point = Point3::new(x, y, z);
                 assert_eq!(UnitVector3::new_normalize(vector![0.0, 0.0, -1.0]), ellipoid.normal(&point))
             },
             _ => panic!("Invalid value of enum")
         };
     }
+
+
+    #[test]
+    fn curvature_of_sphere() {
+        let sphere = Ellipse::new(0.25, 0.25, 0.25,
+            &Point3::new(0.0, 0.0, 0.0),
+            0
+        );
+
+        let curvature = sphere.curvature(&Point3::new(0.0, 0.0, -2.0));
+
+        assert!((curvature.gaussian - 0.25).abs() < 1e-5);
+        assert!((curvature.mean - 0.5).abs() < 1e-5);
+    }
+
+
+    #[test]
+    fn curvature_at_ellipsoid_vertex() {
+        // Semi-axes 1, 2, 3. At (0, 0, -3) the principal curvatures are c/a^2 and c/b^2.
+        let ellipsoid = Ellipse::new(1.0, 0.25, 1.0 / 9.0,
+            &Point3::new(0.0, 0.0, 0.0),
+            0
+        );
+
+        let curvature = ellipsoid.curvature(&Point3::new(0.0, 0.0, -3.0));
+
+        let k1 = 3.0;
+        let k2 = 3.0 / 4.0;
+        assert!((curvature.gaussian - k1 * k2).abs() < 1e-4);
+        assert!((curvature.mean - (k1 + k2) / 2.0).abs() < 1e-4);
+    }
+
+
+    #[test]
+    fn near_clip_closes_solid_with_cap() {
+        let sphere = Ellipse::new(1.0, 1.0, 1.0,
+            &Point3::new(0.0, 0.0, 0.0),
+            0
+        );
+
+        match sphere.hit_clipped(0.0, 0.0, -0.5, f32::INFINITY, CutMode::Solid) {
+            HitRecord::Hit { z, surface } => {
+                assert_eq!(-0.5, z);
+                assert_eq!(Surface::Cap(-Vector3::z_axis()), surface);
+            },
+            _ => panic!("Invalid value of enum")
+        };
+    }
+
+
+    #[test]
+    fn plane_cut_reveals_inner_side_of_shell() {
+        let mut sphere = Ellipse::new(1.0, 1.0, 1.0,
+            &Point3::new(0.0, 0.0, 0.0),
+            0
+        );
+        sphere.clip_planes.push(ClipPlane::new(&vector![0.0, 0.0, -1.0], 0.0));
+
+        match sphere.hit_clipped(0.0, 0.0, f32::NEG_INFINITY, f32::INFINITY, CutMode::Hollow) {
+            HitRecord::Hit { z, surface } => {
+                assert!((z - 1.0).abs() < 1e-5);
+                assert_eq!(Surface::Inner, surface);
+            },
+            _ => panic!("Invalid value of enum")
+        };
+
+        match sphere.hit_clipped(0.0, 0.0, f32::NEG_INFINITY, f32::INFINITY, CutMode::Solid) {
+            HitRecord::Hit { z, surface } => {
+                assert!(z.abs() < 1e-5);
+                assert_eq!(Surface::Cap(UnitVector3::new_normalize(vector![0.0, 0.0, -1.0])), surface);
+            },
+            _ => panic!("Invalid value of enum")
+        };
+    }
+
+
+    #[test]
+    fn object_behind_far_clip_is_missed() {
+        let sphere = Ellipse::new(1.0, 1.0, 1.0,
+            &Point3::new(0.0, 0.0, 0.0),
+            0
+        );
+
+        assert!(matches!(sphere.hit_clipped(0.0, 0.0, -20.0, -2.0, CutMode::Solid), HitRecord::Miss));
+        assert!(matches!(sphere.hit_clipped(0.0, 0.0, -20.0, -2.0, CutMode::Hollow), HitRecord::Miss));
+    }
+
+
+    #[test]
+    fn visible_interval_is_chord_through_solid() {
+        let sphere = Ellipse::new(1.0, 1.0, 1.0,
+            &Point3::new(0.0, 0.0, 0.0),
+            0
+        );
+
+        let span = sphere.visible_interval(0.0, 0.0, -20.0, f32::INFINITY).unwrap();
+        assert!((span.end - span.start - 2.0).abs() < 1e-5);
+        assert_eq!(Surface::Outer, span.entry);
+        assert_eq!(Surface::Inner, span.exit);
+
+        let span = sphere.visible_interval(0.0, 0.0, -20.0, 0.5).unwrap();
+        assert!((span.end - span.start - 1.5).abs() < 1e-5);
+        assert_eq!(Surface::Cap(-Vector3::z_axis()), span.exit);
+
+        assert!(sphere.visible_interval(2.0, 0.0, -20.0, f32::INFINITY).is_none());
+    }
+
+
+    #[test]
+    fn surface_uv_of_poles_and_equator() {
+        let ellipsoid = Ellipse::new(1.0, 0.25, 1.0 / 9.0,
+            &Point3::new(0.0, 0.0, 0.0),
+            0
+        );
+
+        let (_, v) = ellipsoid.surface_uv(&Point3::new(0.0, 2.0, 0.0));
+        assert!(v.abs() < 1e-5);
+
+        let (_, v) = ellipsoid.surface_uv(&Point3::new(0.0, -2.0, 0.0));
+        assert!((v - 1.0).abs() < 1e-5);
+
+        let (u, v) = ellipsoid.surface_uv(&Point3::new(0.0, 0.0, -3.0));
+        assert!((u - 0.25).abs() < 1e-5);
+        assert!((v - 0.5).abs() < 1e-5);
+    }
+
+
+    #[test]
+    fn covariance_round_trip() {
+        let rotation = na::Rotation3::from_euler_angles(0.3, -0.7, 1.2).into_inner();
+        let covariance = rotation * Matrix3::from_diagonal(&vector![4.0, 1.0, 0.25]) * rotation.transpose();
+        let mean = Point3::new(1.0, -2.0, 3.0);
+
+        let ellipse = Ellipse::from_covariance(&mean, &covariance, 0.95, 0).unwrap();
+
+        assert!((ellipse.center() - mean).norm() < 1e-4);
+        assert!((ellipse.covariance(0.95) - covariance).abs().max() < 1e-4);
+
+        // The major axis reaches sqrt(chi2 * 4) from the mean.
+        let major = rotation.column(0) * (7.8147_f32 * 4.0).sqrt();
+        let tip = ellipse.model_inv.transform_point(&(mean + major));
+        let axes = ellipse.semi_axes();
+        let value = (tip.coords.component_div(&axes)).norm_squared();
+        assert!((value - 1.0).abs() < 1e-3);
+    }
+
+
+    #[test]
+    fn covariance_must_be_positive_definite() {
+        assert!(Ellipse::from_covariance(&Point3::origin(), &Matrix3::from_diagonal(&vector![1.0, 0.0, 1.0]), 0.9, 0).is_none());
+        assert!(Ellipse::from_covariance(&Point3::origin(), &Matrix3::from_diagonal(&vector![1.0, -1.0, 1.0]), 0.9, 0).is_none());
+    }
+
+
+    #[test]
+    fn containment_and_signed_distance() {
+        let mut ellipse = Ellipse::new(1.0 / 4.0, 1.0, 1.0 / 9.0, &Point3::new(1.0, 0.0, 0.0), 0);
+        ellipse.scale = 2.0;
+        ellipse.recalculate();
+
+        // Semi-axes 4, 2 and 6 around (2, 0, 0).
+        assert!(ellipse.contains(&Point3::new(5.9, 0.0, 0.0)));
+        assert!(!ellipse.contains(&Point3::new(2.0, 2.1, 0.0)));
+
+        assert!((ellipse.signed_distance(&Point3::new(2.0, 5.0, 0.0)) - 3.0).abs() < 1e-5);
+        assert!((ellipse.signed_distance(&Point3::new(2.0, 0.5, 0.0)) + 1.5).abs() < 1e-5);
+        assert!((ellipse.closest_point(&Point3::new(2.0, 0.0, 10.0)) - Point3::new(2.0, 0.0, 6.0)).norm() < 1e-5);
+    }
+
+
+    #[test]
+    fn rotated_bounding_box() {
+        let mut ellipse = Ellipse::new(1.0 / 9.0, 1.0, 1.0, &Point3::origin(), 0);
+        ellipse.rotation = vector![0.0, 0.0, std::f32::consts::FRAC_PI_4];
+        ellipse.recalculate();
+
+        // Long axis of length 3 along the diagonal of the xy plane.
+        let half = (4.5_f32 + 0.5).sqrt();
+        let aabb = ellipse.bounding_box();
+
+        assert!((aabb.max - Point3::new(half, half, 1.0)).norm() < 1e-5);
+        assert!((aabb.min + Vector3::new(half, half, 1.0)).coords.norm() < 1e-5);
+    }
+
+
+    #[test]
+    fn volume_and_area() {
+        let mut sphere = Ellipse::new(1.0, 1.0, 1.0, &Point3::origin(), 0);
+        sphere.scale = 2.0;
+        sphere.recalculate();
+
+        assert!((sphere.volume() - 32.0 / 3.0 * std::f32::consts::PI).abs() < 1e-4);
+        assert!((sphere.surface_area() - 16.0 * std::f32::consts::PI).abs() < 1e-4);
+
+        let ellipse = Ellipse::new(1.0, 1.0 / 4.0, 1.0 / 9.0, &Point3::origin(), 0);
+        assert!((ellipse.volume() - 8.0 * std::f32::consts::PI).abs() < 1e-4);
+        // Reference value of the area of the ellipsoid with semi-axes 1, 2 and 3.
+        assert!((ellipse.surface_area() - 48.882_146).abs() < 1e-3);
+    }
+
+
+    #[test]
+    fn ray_span_of_oblique_ray() {
+        let mut sphere = Ellipse::new(1.0, 1.0, 1.0, &Point3::new(2.0, 0.0, 0.0), 0);
+        sphere.recalculate();
+
+        // Along the x axis from the origin with a direction of length two.
+        let span = sphere.ray_span(&Point3::origin(), &vector![2.0, 0.0, 0.0]).unwrap();
+        assert!((span.start - 0.5).abs() < 1e-5 && (span.end - 1.5).abs() < 1e-5);
+
+        // Same ray as the view ray through (2.5, 0).
+        let (entry, exit) = sphere.interval(2.5, 0.0).unwrap();
+        let span = sphere.ray_span(&Point3::new(2.5, 0.0, 0.0), &Vector3::z()).unwrap();
+        assert!((span.start - entry).abs() < 1e-5 && (span.end - exit).abs() < 1e-5);
+
+        assert!(sphere.ray_span(&Point3::origin(), &vector![0.0, 1.0, 1.0]).is_none());
+    }
+
+
+    #[test]
+    fn ray_span_is_cut_by_clip_plane() {
+        let mut sphere = Ellipse::new(1.0, 1.0, 1.0, &Point3::origin(), 0);
+        sphere.clip_planes.push(ClipPlane::new(&vector![1.0, 1.0, 0.0], 0.0));
+        sphere.recalculate();
+
+        let span = sphere.ray_span(&Point3::new(-2.0, 0.0, 0.0), &Vector3::x()).unwrap();
+
+        assert!((span.start - 1.0).abs() < 1e-5);
+        assert!((span.end - 2.0).abs() < 1e-5);
+        assert!(matches!(span.exit, Surface::Cap(normal) if (normal.into_inner() + vector![1.0, 1.0, 0.0].normalize()).norm() < 1e-5));
+    }
+
+
+    #[test]
+    fn outline_touches_view_rays() {
+        let mut ellipse = Ellipse::new(1.0 / 9.0, 1.0, 1.0 / 4.0, &Point3::new(1.0, 2.0, 3.0), 0);
+        ellipse.rotation = vector![0.4, -0.9, 0.3];
+        ellipse.recalculate();
+
+        let outline = ellipse.outline().unwrap();
+        let aabb = ellipse.bounding_box();
+        let mut max_x = f32::MIN;
+
+        for i in 0..64 {
+            let point = outline.point(i as f32 / 64.0 * 2.0 * std::f32::consts::PI);
+            max_x = max_x.max(point.x);
+
+            assert!(ellipse.implicit_value(&point).abs() < 1e-4);
+            assert!(ellipse.normal(&point).z.abs() < 1e-3);
+        }
+
+        assert!((max_x - aabb.max.x).abs() < 1e-2);
+    }
+
+
+    #[test]
+    fn cross_section_lies_in_plane_and_on_surface() {
+        let mut ellipse = Ellipse::new(1.0 / 9.0, 1.0 / 4.0, 1.0, &Point3::new(-1.0, 0.0, 2.0), 0);
+        ellipse.rotation = vector![0.2, 0.5, -1.1];
+        ellipse.recalculate();
+
+        let plane = ClipPlane::new(&vector![1.0, 1.0, 0.5], 0.3);
+        let section = ellipse.cross_section(&plane).unwrap();
+
+        for i in 0..16 {
+            let point = section.point(i as f32 / 16.0 * 2.0 * std::f32::consts::PI);
+
+            assert!(plane.signed_distance(&point).abs() < 1e-4);
+            assert!(ellipse.implicit_value(&point).abs() < 1e-4);
+        }
+
+        assert!(ellipse.cross_section(&ClipPlane::new(&vector![0.0, 0.0, 1.0], 10.0)).is_none());
+    }
 }
@@ -0,0 +1,374 @@
+//! Light arriving from far away in every direction, stored as an equirectangular image.
+//! Directions are in world coordinates with `y` up, the image center looks along `+z`.
+
+extern crate nalgebra as na;
+
+use std::{error, fmt, fs, io};
+use std::f32::consts::PI;
+use std::path::Path;
+
+use na::Vector3;
+
+use crate::math::directions::{spherical_direction, spherical_uv};
+use super::{Color, Texture};
+
+
+/// Size of the image the procedural sky is drawn into.
+const SKY_SIZE: (usize, usize) = (256, 128);
+
+/// The lighting is projected from the first level at most this high.
+const HARMONICS_HEIGHT: usize = 64;
+
+/// Largest HDR image read, guarding the allocation against corrupt headers.
+const MAX_HDR_PIXELS: usize = 1 << 28;
+
+
+#[derive(Clone, Debug)]
+pub struct Environment {
+    /// The image and copies halved in size again and again, blurrier levels stand for
+    /// wider reflection lobes.
+    levels: Vec<Texture>,
+    /// Radiance projected on the spherical harmonics up to the second band.
+    harmonics: [Color; 9],
+}
+
+
+#[derive(Debug)]
+pub enum EnvironmentError {
+    Io(io::Error),
+    Png(png::DecodingError),
+    Hdr(String),
+}
+
+
+impl Environment {
+    /// Sky fading from `horizon` up to `zenith` and down to `ground`.
+    pub fn sky(zenith: Color, horizon: Color, ground: Color) -> Environment {
+        let (width, height) = SKY_SIZE;
+
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let direction = spherical_direction((x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32);
+
+                if direction.y >= 0.0 {
+                    horizon.lerp(zenith, direction.y.sqrt())
+                } else {
+                    horizon.lerp(ground, (-direction.y).sqrt())
+                }
+            })
+            .collect();
+
+        Environment::from_texture(Texture::from_pixels(String::from("sky"), width, height, pixels))
+    }
+
+
+    /// Loads an equirectangular image, Radiance HDR files are recognized by their extension
+    /// and everything else is read as PNG.
+    pub fn load(path: &Path) -> Result<Environment, EnvironmentError> {
+        let is_hdr = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+
+        let texture = if is_hdr {
+            let (width, height, pixels) = parse_hdr(&fs::read(path)?).map_err(EnvironmentError::Hdr)?;
+            Texture::from_pixels(path.to_string_lossy().into_owned(), width, height, pixels)
+        } else {
+            Texture::load(path).map_err(EnvironmentError::Png)?
+        };
+
+        Ok(Environment::from_texture(texture))
+    }
+
+
+    pub fn from_texture(texture: Texture) -> Environment {
+        let mut levels = vec![texture];
+
+        while let Some(level) = levels.last().filter(|level| level.size().1 > 1).map(downsample) {
+            levels.push(level);
+        }
+
+        let projected = levels.iter()
+            .find(|level| level.size().1 <= HARMONICS_HEIGHT)
+            .unwrap_or(&levels[levels.len() - 1]);
+
+        let harmonics = project_harmonics(projected);
+
+        Environment { levels, harmonics }
+    }
+
+
+    /// Path of the image, "sky" for the procedural sky.
+    pub fn source(&self) -> &str {
+        &self.levels[0].path
+    }
+
+
+    /// Light arriving from `direction`.
+    pub fn radiance(&self, direction: &Vector3<f32>) -> Color {
+        let (u, v) = spherical_uv(direction);
+        self.levels[0].sample(u, v)
+    }
+
+
+    /// Light arriving around `direction`, blurred like a Phong lobe with exponent `shininess`.
+    pub fn glossy(&self, direction: &Vector3<f32>, shininess: f32) -> Color {
+        let width = (2.0 / (shininess.max(0.0) + 2.0)).sqrt();
+        let level = (width * self.levels[0].size().1 as f32 / PI).log2().clamp(0.0, (self.levels.len() - 1) as f32);
+
+        let (u, v) = spherical_uv(direction);
+        let lower = level.floor() as usize;
+        let upper = (lower + 1).min(self.levels.len() - 1);
+
+        self.levels[lower].sample(u, v).lerp(self.levels[upper].sample(u, v), level - lower as f32)
+    }
+
+
+    /// Light diffusely reflected by a white surface facing `normal`, the irradiance over pi.
+    pub fn diffuse(&self, normal: &Vector3<f32>) -> Color {
+        // Convolution with the clamped cosine scales every band.
+        const BANDS: [f32; 9] = [1.0, 2.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0, 0.25, 0.25, 0.25, 0.25, 0.25];
+
+        let basis = harmonics_basis(&normal.normalize());
+
+        (0..9).fold(Color::from_rgb_f(0.0, 0.0, 0.0), |result, i| result + self.harmonics[i] * (BANDS[i] * basis[i]))
+    }
+}
+
+
+/// Image of half the size, every pixel the mean of the two by two pixels it covers.
+fn downsample(texture: &Texture) -> Texture {
+    let (width, height) = texture.size();
+    let (new_width, new_height) = ((width / 2).max(1), (height / 2).max(1));
+
+    let pixels = (0..new_height)
+        .flat_map(|y| (0..new_width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let pixel = |dx: usize, dy: usize| texture.pixel((2 * x + dx).min(width - 1), (2 * y + dy).min(height - 1));
+            (pixel(0, 0) + pixel(1, 0) + pixel(0, 1) + pixel(1, 1)) * 0.25
+        })
+        .collect();
+
+    Texture::from_pixels(texture.path.clone(), new_width, new_height, pixels)
+}
+
+
+fn project_harmonics(texture: &Texture) -> [Color; 9] {
+    let (width, height) = texture.size();
+    let mut result = [Color::from_rgb_f(0.0, 0.0, 0.0); 9];
+
+    for y in 0..height {
+        let v = (y as f32 + 0.5) / height as f32;
+        let solid_angle = (2.0 * PI / width as f32) * (PI / height as f32) * (v * PI).sin();
+
+        for x in 0..width {
+            let basis = harmonics_basis(&spherical_direction((x as f32 + 0.5) / width as f32, v));
+            let radiance = texture.pixel(x, y) * solid_angle;
+
+            for (coefficient, value) in result.iter_mut().zip(basis) {
+                *coefficient = *coefficient + radiance * value;
+            }
+        }
+    }
+
+    result
+}
+
+
+/// Real spherical harmonics of the first three bands at a unit direction.
+fn harmonics_basis(n: &Vector3<f32>) -> [f32; 9] {
+    [
+        0.282_095,
+        0.488_603 * n.y,
+        0.488_603 * n.z,
+        0.488_603 * n.x,
+        1.092_548 * n.x * n.y,
+        1.092_548 * n.y * n.z,
+        0.315_392 * (3.0 * n.z * n.z - 1.0),
+        1.092_548 * n.x * n.z,
+        0.546_274 * (n.x * n.x - n.y * n.y),
+    ]
+}
+
+
+/// Reads a Radiance RGBE image with the usual `-Y height +X width` orientation, flat or
+/// run-length encoded.
+fn parse_hdr(data: &[u8]) -> Result<(usize, usize, Vec<Color>), String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut start = 0;
+
+    // The header ends with an empty line, the resolution follows on the next one.
+    while lines.len() < 2 || !lines[lines.len() - 2].is_empty() {
+        let end = start + data[start..].iter().position(|byte| *byte == b'\n').ok_or("missing resolution")?;
+        lines.push(String::from_utf8_lossy(&data[start..end]).into_owned());
+        start = end + 1;
+    }
+
+    if !lines[0].starts_with("#?") {
+        return Err(String::from("not a Radiance HDR file"));
+    }
+
+    if lines.iter().any(|line| line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe") {
+        return Err(String::from("unsupported pixel format"));
+    }
+
+    let (height, width) = match lines[lines.len() - 1].split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => (
+            height.parse::<usize>().map_err(|_| "invalid height")?,
+            width.parse::<usize>().map_err(|_| "invalid width")?,
+        ),
+        _ => return Err(String::from("unsupported image orientation")),
+    };
+
+    if width == 0 || height == 0 {
+        return Err(String::from("empty image"));
+    }
+
+    if width.checked_mul(height).is_none_or(|pixels| pixels > MAX_HDR_PIXELS) {
+        return Err(String::from("image too large"));
+    }
+
+    // Scanlines take at least four bytes a pixel, or with run-length encoding a header and
+    // for every channel a run of up to 127 pixels, so the header is checked against the
+    // bytes left before anything is allocated.
+    let min_scanline = if (8..0x8000).contains(&width) { 4 + 8 * width.div_ceil(127) } else { 4 * width };
+
+    if min_scanline.checked_mul(height).is_none_or(|size| data.len() - start < size) {
+        return Err(String::from("file ends before the last pixel"));
+    }
+
+    let mut bytes = data[start..].iter().copied();
+    let mut next = || bytes.next().ok_or(String::from("file ends before the last pixel"));
+
+    let mut pixels = Vec::with_capacity(width * height);
+    let mut scanline = vec![[0_u8; 4]; width];
+
+    for _ in 0..height {
+        let first = [next()?, next()?, next()?, next()?];
+
+        if (8..0x8000).contains(&width) && first[0] == 2 && first[1] == 2 && first[2] & 0x80 == 0 {
+            if (first[2] as usize) << 8 | first[3] as usize != width {
+                return Err(String::from("scanline width does not match the image"));
+            }
+
+            for channel in 0..4 {
+                let mut x = 0;
+
+                while x < width {
+                    let count = next()? as usize;
+                    let (count, run) = if count > 128 { (count - 128, Some(next()?)) } else { (count, None) };
+
+                    if count == 0 || x + count > width {
+                        return Err(String::from("invalid run length"));
+                    }
+
+                    for pixel in &mut scanline[x..x + count] {
+                        pixel[channel] = match run {
+                            Some(value) => value,
+                            None => next()?,
+                        };
+                    }
+
+                    x += count;
+                }
+            }
+        } else {
+            scanline[0] = first;
+
+            for pixel in &mut scanline[1..] {
+                *pixel = [next()?, next()?, next()?, next()?];
+            }
+        }
+
+        pixels.extend(scanline.iter().map(|[r, g, b, e]| {
+            if *e == 0 {
+                return Color::from_rgb_f(0.0, 0.0, 0.0);
+            }
+
+            let scale = 2.0_f64.powi(*e as i32 - 136);
+            Color::from_rgb_f((*r as f64 + 0.5) * scale, (*g as f64 + 0.5) * scale, (*b as f64 + 0.5) * scale)
+        }));
+    }
+
+    Ok((width, height, pixels))
+}
+
+
+impl fmt::Display for EnvironmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvironmentError::Io(err) => write!(f, "{}", err),
+            EnvironmentError::Png(err) => write!(f, "{}", err),
+            EnvironmentError::Hdr(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+
+impl error::Error for EnvironmentError {}
+
+
+impl From<io::Error> for EnvironmentError {
+    fn from(err: io::Error) -> Self {
+        EnvironmentError::Io(err)
+    }
+}
+
+
+#[cfg(test)]
+mod environment_tests {
+    use super::*;
+
+
+    #[test]
+    fn uniform_light_is_reflected_unchanged() {
+        let color = Color::from_rgb_f(0.5, 1.0, 2.0);
+        let environment = Environment::sky(color, color, color);
+
+        for normal in [Vector3::y(), Vector3::new(1.0, -1.0, 0.5)] {
+            let diffuse = environment.diffuse(&normal);
+            let glossy = environment.glossy(&normal, 4.0);
+
+            for (value, expected) in [(diffuse, color), (glossy, color)] {
+                assert!((value.luminance() - expected.luminance()).abs() < 1e-2 * expected.luminance());
+            }
+        }
+    }
+
+
+    #[test]
+    fn sky_lights_upward_surfaces_more() {
+        let environment = Environment::sky(Color::from_rgb_f(1.0, 1.0, 1.0), Color::from_rgb_f(0.5, 0.5, 0.5), Color::from_rgb_f(0.0, 0.0, 0.0));
+
+        assert!(environment.diffuse(&Vector3::y()).luminance() > environment.diffuse(&Vector3::x()).luminance());
+        assert!(environment.diffuse(&Vector3::x()).luminance() > environment.diffuse(&-Vector3::y()).luminance());
+        assert!(environment.radiance(&Vector3::y()).luminance() > 0.9);
+    }
+
+
+    #[test]
+    fn hdr_flat_and_run_length_scanlines() {
+        let mut data = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 8\n".to_vec();
+        // Flat scanline: every pixel 1.0 in red.
+        for _ in 0..8 {
+            data.extend([128, 0, 0, 129]);
+        }
+        // Run-length scanline: every channel one run, 0.5 gray.
+        data.extend([2, 2, 0, 8]);
+        for value in [128, 128, 128, 128] {
+            data.extend([128 + 8, value]);
+        }
+
+        let (width, height, pixels) = parse_hdr(&data).unwrap();
+
+        assert_eq!((8, 2), (width, height));
+        assert_eq!(Color::from_rgb_f(128.5 / 128.0, 0.5 / 128.0, 0.5 / 128.0), pixels[3]);
+        assert_eq!(Color::from_rgb_f(128.5 / 256.0, 128.5 / 256.0, 128.5 / 256.0), pixels[12]);
+
+        assert!(parse_hdr(b"#?RADIANCE\n\n+Y 2 +X 8\n").is_err());
+        assert!(parse_hdr(&data[..data.len() - 1]).is_err());
+
+        assert!(parse_hdr(b"#?RADIANCE\n\n-Y 1 +X 0\n").is_err());
+        assert!(parse_hdr(b"#?RADIANCE\n\n-Y 0 +X 8\n").is_err());
+        assert!(parse_hdr(b"#?RADIANCE\n\n-Y 18446744073709551615 +X 2\n").is_err());
+        assert_eq!(Err(String::from("file ends before the last pixel")), parse_hdr(b"#?RADIANCE\n\n-Y 16384 +X 16384\n"));
+    }
+}
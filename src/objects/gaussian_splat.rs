@@ -0,0 +1,361 @@
+//! 3D Gaussian splats as written by Gaussian splatting trainers to PLY files. Every splat is
+//! projected to a 2D Gaussian on the image and the splats are composited front to back.
+
+extern crate nalgebra as na;
+
+use std::{error, fmt, fs, io};
+use std::path::Path;
+
+use na::{Matrix2, Matrix3, Point3, Quaternion, Similarity3, Translation3, UnitQuaternion, Vector2, Vector3};
+
+use super::{Camera, Color};
+use super::ply::{PlyError, PlyVertices};
+
+
+/// Zeroth spherical harmonic, scaling the `f_dc` coefficients to a color.
+const SH_C0: f32 = 0.282_094_8;
+
+/// Splats are drawn out to this many standard deviations.
+const CUTOFF_SIGMAS: f32 = 3.0;
+
+/// Variance in square pixels added to every projected splat, so splats smaller than a
+/// pixel still cover one instead of flickering between pixels.
+const SCREEN_VARIANCE: f32 = 0.3;
+
+/// A splat covering a pixel with less opacity leaves it unchanged.
+const MIN_ALPHA: f32 = 1.0 / 255.0;
+
+/// Highest opacity of a splat, splats behind always show a little.
+const MAX_ALPHA: f32 = 0.99;
+
+/// Pixels letting less light through are finished, later splats are skipped there.
+const MIN_TRANSMITTANCE: f32 = 1e-4;
+
+/// Share of the splats inside the sphere given by `SplatCloud::bounding_sphere`.
+const BOUNDING_FRACTION: f32 = 0.9;
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct GaussianSplat {
+    pub position: Point3<f32>,
+    pub covariance: Matrix3<f32>,
+    pub opacity: f32,
+    pub color: Color,
+}
+
+
+pub struct SplatCloud {
+    pub splats: Vec<GaussianSplat>,
+}
+
+
+/// Placement of a splat cloud in the scene, on top of fitting it to the view.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct SplatView {
+    /// Size relative to the fitted size.
+    pub zoom: f32,
+    /// Position of the center of the cloud.
+    pub offset: Vector3<f32>,
+    /// Euler angles around x, y and z in radians, turning the cloud around its center.
+    pub rotation: Vector3<f32>,
+}
+
+
+#[derive(Debug)]
+pub enum SplatError {
+    Io(io::Error),
+    Parse{line: usize, message: String},
+}
+
+
+impl Default for SplatView {
+    fn default() -> SplatView {
+        SplatView { zoom: 1.0, offset: Vector3::zeros(), rotation: Vector3::zeros() }
+    }
+}
+
+
+impl SplatView {
+    /// Transform moving `center` to the offset, with the cloud turned and scaled by
+    /// `scale` times the zoom around it.
+    pub fn transform(&self, center: &Point3<f32>, scale: f32) -> Similarity3<f32> {
+        let rotation = UnitQuaternion::from_euler_angles(self.rotation.x, self.rotation.y, self.rotation.z);
+
+        Similarity3::from_parts(Translation3::from(self.offset), rotation, scale * self.zoom) * Translation3::from(-center.coords)
+    }
+}
+
+
+impl SplatCloud {
+    pub fn load(path: &Path) -> Result<SplatCloud, SplatError> {
+        SplatCloud::parse_ply(&fs::read(path)?)
+    }
+
+
+    /// Reads the vertex element. The shape comes from `scale_0` to `scale_2` as logarithms
+    /// of the standard deviations, turned by the quaternion `rot_0` to `rot_3` with the real
+    /// part first, or from the covariance `cov_xx`, `cov_xy`, `cov_xz`, `cov_yy`, `cov_yz`
+    /// and `cov_zz`. `opacity` is taken before the sigmoid. The color comes from `f_dc_0` to
+    /// `f_dc_2`, the zeroth spherical harmonic, or from `red`, `green` and `blue` bytes.
+    pub fn parse_ply(data: &[u8]) -> Result<SplatCloud, SplatError> {
+        let vertices = PlyVertices::parse(data)?;
+        let [x, y, z] = [vertices.require("x")?, vertices.require("y")?, vertices.require("z")?];
+
+        let covariance = properties(&vertices, ["cov_xx", "cov_xy", "cov_xz", "cov_yy", "cov_yz", "cov_zz"]);
+        let scales = properties(&vertices, ["scale_0", "scale_1", "scale_2"]);
+        let rotation = properties(&vertices, ["rot_0", "rot_1", "rot_2", "rot_3"]);
+
+        if covariance.is_none() && scales.is_none() {
+            return Err(SplatError::Parse { line: 1, message: String::from("vertex has neither scale_0 to scale_2 nor cov_xx to cov_zz properties") });
+        }
+
+        let harmonic = properties(&vertices, ["f_dc_0", "f_dc_1", "f_dc_2"]);
+        let bytes = properties(&vertices, ["red", "green", "blue"]);
+        let opacity = vertices.property("opacity");
+
+        let splats = vertices.vertices()
+            .map(|vertex| {
+                let covariance = match (covariance, scales) {
+                    (Some([xx, xy, xz, yy, yz, zz]), _) => Matrix3::new(
+                        vertex[xx], vertex[xy], vertex[xz],
+                        vertex[xy], vertex[yy], vertex[yz],
+                        vertex[xz], vertex[yz], vertex[zz]
+                    ),
+                    (None, Some(scales)) => {
+                        let variances = Vector3::from(scales.map(|index| (2.0 * vertex[index]).exp()));
+                        let rotation = rotation
+                            .and_then(|[w, i, j, k]| UnitQuaternion::try_new(Quaternion::new(vertex[w], vertex[i], vertex[j], vertex[k]), f32::EPSILON))
+                            .unwrap_or_else(UnitQuaternion::identity)
+                            .to_rotation_matrix();
+
+                        rotation.matrix() * Matrix3::from_diagonal(&variances) * rotation.matrix().transpose()
+                    }
+                    (None, None) => unreachable!(),
+                };
+
+                let color = match (harmonic, bytes) {
+                    (Some([r, g, b]), _) => {
+                        let channel = |index: usize| (0.5 + SH_C0 * vertex[index]).clamp(0.0, 1.0);
+                        Color::from_srgb_f(channel(r), channel(g), channel(b))
+                    }
+                    (None, Some([r, g, b])) => Color::from_srgb_f(vertex[r] / 255.0, vertex[g] / 255.0, vertex[b] / 255.0),
+                    (None, None) => Color::from_rgb(255, 255, 255),
+                };
+
+                GaussianSplat {
+                    position: Point3::new(vertex[x], vertex[y], vertex[z]),
+                    covariance,
+                    opacity: opacity.map_or(1.0, |index| 1.0 / (1.0 + (-vertex[index]).exp())),
+                    color,
+                }
+            })
+            .collect();
+
+        Ok(SplatCloud { splats })
+    }
+
+
+    /// Mean position of the splats and the distance from it holding most of them, ignoring
+    /// stray splats far from the rest.
+    pub fn bounding_sphere(&self) -> (Point3<f32>, f32) {
+        if self.splats.is_empty() {
+            return (Point3::origin(), 1.0);
+        }
+
+        let center = Point3::from(self.splats.iter().map(|splat| splat.position.coords).sum::<Vector3<f32>>() / self.splats.len() as f32);
+
+        let mut distances: Vec<f32> = self.splats.iter().map(|splat| (splat.position - center).norm()).collect();
+        let index = ((distances.len() - 1) as f32 * BOUNDING_FRACTION) as usize;
+        let (_, radius, _) = distances.select_nth_unstable_by(index, f32::total_cmp);
+
+        (center, radius.max(f32::EPSILON))
+    }
+
+
+    /// Composites the splats placed in the scene by `transform` as seen by `camera` on an
+    /// image of `width` by `height` pixels, keeping splats between `near_z` and `far_z`.
+    /// Returns the color premultiplied by the coverage and the transmittance of every
+    /// pixel, rows one after another.
+    pub fn composite(
+        &self,
+        transform: &Similarity3<f32>,
+        camera: &Camera,
+        width: u32,
+        height: u32,
+        near_z: f32,
+        far_z: f32
+    ) -> Vec<(Color, f32)> {
+        let pixels_per_unit = Vector2::new(width as f32 / camera.viewport_width, height as f32 / camera.viewport_height);
+        let rotation = transform.isometry.rotation.to_rotation_matrix();
+        let scaling = transform.scaling();
+
+        let mut visible: Vec<(Point3<f32>, &GaussianSplat)> = self.splats.iter()
+            .map(|splat| (transform * splat.position, splat))
+            .filter(|(position, _)| near_z <= position.z && position.z <= far_z)
+            .collect();
+        visible.sort_by(|(first, _), (second, _)| first.z.total_cmp(&second.z));
+
+        let mut pixels = vec![(Color::from_rgb_f(0.0, 0.0, 0.0), 1.0); (width * height) as usize];
+
+        for (position, splat) in visible {
+            let covariance = rotation.matrix() * splat.covariance * rotation.matrix().transpose() * (scaling * scaling);
+
+            // Rows grow downwards, which negates the covariance of x and y.
+            let footprint = Matrix2::new(
+                covariance[(0, 0)] * pixels_per_unit.x * pixels_per_unit.x + SCREEN_VARIANCE,
+                -covariance[(0, 1)] * pixels_per_unit.x * pixels_per_unit.y,
+                -covariance[(1, 0)] * pixels_per_unit.x * pixels_per_unit.y,
+                covariance[(1, 1)] * pixels_per_unit.y * pixels_per_unit.y + SCREEN_VARIANCE
+            );
+
+            let Some(conic) = footprint.try_inverse() else {
+                continue;
+            };
+
+            let radius = CUTOFF_SIGMAS * footprint.symmetric_eigenvalues().max().sqrt();
+            let (center_x, center_y) = camera.pixel_position(&position, width, height);
+
+            let range = |center: f32, size: u32| {
+                (center - radius).floor().clamp(0.0, size as f32) as u32..(center + radius).ceil().clamp(0.0, size as f32) as u32
+            };
+
+            for row in range(center_y, height) {
+                for column in range(center_x, width) {
+                    let offset = Vector2::new(column as f32 + 0.5 - center_x, row as f32 + 0.5 - center_y);
+                    let alpha = (splat.opacity * (-0.5 * offset.dot(&(conic * offset))).exp()).min(MAX_ALPHA);
+
+                    let (color, transmittance) = &mut pixels[(row * width + column) as usize];
+
+                    if alpha < MIN_ALPHA || *transmittance < MIN_TRANSMITTANCE {
+                        continue;
+                    }
+
+                    *color = *color + splat.color * (alpha * *transmittance);
+                    *transmittance *= 1.0 - alpha;
+                }
+            }
+        }
+
+        pixels
+    }
+}
+
+
+/// Positions of the properties `names` in a vertex, `None` unless the vertex has all of them.
+fn properties<const N: usize>(vertices: &PlyVertices, names: [&str; N]) -> Option<[usize; N]> {
+    let found = names.map(|name| vertices.property(name));
+
+    found.iter().all(Option::is_some).then(|| found.map(Option::unwrap))
+}
+
+
+impl From<io::Error> for SplatError {
+    fn from(err: io::Error) -> Self {
+        SplatError::Io(err)
+    }
+}
+
+
+impl From<PlyError> for SplatError {
+    fn from(err: PlyError) -> Self {
+        SplatError::Parse { line: err.line, message: err.message }
+    }
+}
+
+
+impl fmt::Display for SplatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplatError::Io(err) => write!(f, "{}", err),
+            SplatError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+
+impl error::Error for SplatError {}
+
+
+#[cfg(test)]
+mod gaussian_splat_tests {
+    use super::*;
+
+
+    #[test]
+    fn binary_ply_with_trainer_properties() {
+        let mut data = b"ply\nformat binary_little_endian 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\n\
+                         property float f_dc_0\nproperty float f_dc_1\nproperty float f_dc_2\nproperty float opacity\n\
+                         property float scale_0\nproperty float scale_1\nproperty float scale_2\n\
+                         property float rot_0\nproperty float rot_1\nproperty float rot_2\nproperty float rot_3\nend_header\n".to_vec();
+        // A quarter turn around z swaps the first two axes.
+        let half_angle = std::f32::consts::FRAC_PI_4;
+        for value in [1.0, 2.0, 3.0, 0.0, 0.0, 0.0, 0.0, 0.0, 2.0_f32.ln(), 0.0, half_angle.cos(), 0.0, 0.0, half_angle.sin()] {
+            data.extend(value.to_le_bytes());
+        }
+
+        let cloud = SplatCloud::parse_ply(&data).unwrap();
+        let splat = cloud.splats[0];
+
+        assert_eq!(Point3::new(1.0, 2.0, 3.0), splat.position);
+        assert!((splat.covariance - Matrix3::from_diagonal(&Vector3::new(4.0, 1.0, 1.0))).norm() < 1e-5);
+        assert!((splat.opacity - 0.5).abs() < 1e-6);
+        assert_eq!(128, splat.color.red());
+    }
+
+
+    #[test]
+    fn ascii_ply_with_covariance_and_bytes() {
+        let text = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\n\
+                    property float cov_xx\nproperty float cov_xy\nproperty float cov_xz\nproperty float cov_yy\nproperty float cov_yz\nproperty float cov_zz\n\
+                    property uchar red\nproperty uchar green\nproperty uchar blue\nend_header\n0 0 0 2 0.5 0 1 0 3 255 0 10\n";
+        let splat = SplatCloud::parse_ply(text.as_bytes()).unwrap().splats[0];
+
+        assert_eq!(Matrix3::new(2.0, 0.5, 0.0, 0.5, 1.0, 0.0, 0.0, 0.0, 3.0), splat.covariance);
+        assert_eq!(1.0, splat.opacity);
+        assert_eq!((255, 0, 10), (splat.color.red(), splat.color.green(), splat.color.blue()));
+
+        let error = SplatCloud::parse_ply(b"ply\nformat ascii 1.0\nelement vertex 0\nproperty float x\nproperty float y\nproperty float z\nend_header\n");
+        assert!(error.is_err());
+
+        // Bogus vertex counts fail against the length of the body.
+        for format in ["ascii", "binary_little_endian"] {
+            let header = format!("ply\nformat {} 1.0\nelement vertex 4611686018427387904\nproperty float x\nproperty float y\nproperty float z\n\
+                                  property float scale_0\nproperty float scale_1\nproperty float scale_2\nend_header\n0 0 0 0 0 0\n", format);
+            assert!(SplatCloud::parse_ply(header.as_bytes()).is_err());
+        }
+    }
+
+
+    #[test]
+    fn view_turns_and_scales_around_center() {
+        let view = SplatView { zoom: 2.0, offset: Vector3::new(0.0, 0.0, 5.0), rotation: Vector3::new(0.0, 0.0, std::f32::consts::FRAC_PI_2) };
+        let transform = view.transform(&Point3::new(1.0, 1.0, 1.0), 0.5);
+
+        assert!((transform * Point3::new(1.0, 1.0, 1.0) - Point3::new(0.0, 0.0, 5.0)).norm() < 1e-6);
+        assert!((transform * Point3::new(2.0, 1.0, 1.0) - Point3::new(0.0, 1.0, 5.0)).norm() < 1e-6);
+    }
+
+
+    #[test]
+    fn nearer_splats_cover_farther_ones() {
+        let splat = |z: f32, color: Color| GaussianSplat {
+            // Center of pixel (5, 5) on a 10 by 10 image of the default camera.
+            position: Point3::new(0.25, -0.25, z),
+            covariance: Matrix3::identity() * 0.25,
+            opacity: 1.0,
+            color,
+        };
+
+        let cloud = SplatCloud { splats: vec![splat(2.0, Color::from_rgb(0, 0, 255)), splat(1.0, Color::from_rgb(255, 0, 0))] };
+        let pixels = cloud.composite(&Similarity3::identity(), &Camera::new(5.0, 5.0), 10, 10, f32::NEG_INFINITY, f32::INFINITY);
+
+        let (color, transmittance) = pixels[5 * 10 + 5];
+        assert!(color.red() > 250 && color.blue() < 50);
+        assert!(transmittance < 1e-3);
+        assert_eq!(1.0, pixels[0].1);
+
+        // Behind the far plane only the red splat is left.
+        let pixels = cloud.composite(&Similarity3::identity(), &Camera::new(5.0, 5.0), 10, 10, 0.0, 1.5);
+        assert_eq!(0, pixels[5 * 10 + 5].0.blue());
+    }
+}
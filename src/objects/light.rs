@@ -0,0 +1,103 @@
+extern crate nalgebra as na;
+
+use na::Point3;
+
+use crate::math::directions;
+use super::Color;
+
+
+/// Angle between successive samples of the sunflower pattern.
+const GOLDEN_ANGLE: f32 = 2.399_963;
+
+
+/// Light source in world coordinates. Intensity does not fall off with distance.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Light {
+    pub position: Point3<f32>,
+    pub color: Color,
+    pub intensity: f32,
+    /// Radius of the spherical area light, zero for a point light.
+    pub radius: f32,
+}
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ShadowMode {
+    Off,
+    /// One shadow ray towards the center of every light.
+    Hard,
+    /// Shadow rays towards `samples` points on every area light.
+    Soft { samples: u32 },
+}
+
+
+impl Light {
+    pub fn new(position: &Point3<f32>, color: Color, intensity: f32, radius: f32) -> Light {
+        Light { position: *position, color, intensity, radius }
+    }
+
+
+    /// Points of the light as seen from `from`: `count` points spread evenly over the disc
+    /// facing `from`, turned by `rotation` radians. Point lights give only their position.
+    pub fn sample_points(&self, from: &Point3<f32>, count: u32, rotation: f32) -> Vec<Point3<f32>> {
+        let axis = self.position - from;
+
+        if self.radius <= 0.0 || count <= 1 || axis.norm() == 0.0 {
+            return vec![self.position];
+        }
+
+        let (first, second) = directions::perpendicular_axes(&axis);
+
+        (0..count)
+            .map(|k| {
+                let distance = self.radius * ((k as f32 + 0.5) / count as f32).sqrt();
+                let angle = k as f32 * GOLDEN_ANGLE + rotation;
+
+                self.position + (first * angle.cos() + second * angle.sin()) * distance
+            })
+            .collect()
+    }
+}
+
+
+impl ShadowMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            ShadowMode::Off => "Off",
+            ShadowMode::Hard => "Hard",
+            ShadowMode::Soft { .. } => "Soft",
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod light_tests {
+    use super::*;
+    use na::Vector3;
+
+
+    #[test]
+    fn point_light_has_one_sample() {
+        let light = Light::new(&Point3::new(1.0, 2.0, 3.0), Color::from_rgb(255, 255, 255), 1.0, 0.0);
+
+        assert_eq!(vec![light.position], light.sample_points(&Point3::origin(), 16, 0.0));
+    }
+
+
+    #[test]
+    fn area_light_samples_lie_on_facing_disc() {
+        let light = Light::new(&Point3::new(0.0, 0.0, 10.0), Color::from_rgb(255, 255, 255), 1.0, 2.0);
+        let samples = light.sample_points(&Point3::origin(), 32, 0.3);
+
+        assert_eq!(32, samples.len());
+
+        for point in &samples {
+            assert!((point.z - 10.0).abs() < 1e-5);
+            assert!((point - light.position).norm() <= 2.0 + 1e-5);
+        }
+
+        let mean = samples.iter().map(|p| p.coords).sum::<Vector3<f32>>() / 32.0;
+        assert!((mean - light.position.coords).norm() < 0.2);
+    }
+}
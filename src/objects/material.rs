@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+extern crate nalgebra as na;
+
+use na::{UnitVector3, Vector3};
+
+use super::Color;
+
+
+/// Surface properties shared by objects, shaded with the Phong model.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub color: Color,
+    /// Image replacing `color`, wrapped around the object by its surface coordinates.
+    pub texture: Option<Texture>,
+
+    /// Fraction of the ambient light reflected.
+    pub ambient: f32,
+    pub diffuse: f32,
+    pub specular: f32,
+    /// Exponent of the specular highlight, larger values give smaller highlights.
+    pub shininess: f32,
+    /// Light given off regardless of the lighting.
+    pub emissive: Color,
+
+    /// Fraction of light stopped at the surface, the rest is refracted into the object.
+    pub opacity: f32,
+    /// Fraction of light mirrored at normal incidence, rising towards grazing angles.
+    pub reflectivity: f32,
+    pub refractive_index: f32,
+}
+
+
+/// Image sampled by surface coordinates, `u` wraps around and `v` is clamped.
+#[derive(Clone, Debug)]
+pub struct Texture {
+    /// File the image was loaded from.
+    pub path: String,
+    width: usize,
+    height: usize,
+    pixels: Arc<[Color]>,
+}
+
+
+impl Material {
+    pub fn new(name: &str, color: Color) -> Material {
+        Material {
+            name: String::from(name),
+            color,
+            texture: None,
+            ambient: 0.1,
+            diffuse: 0.9,
+            specular: 0.3,
+            shininess: 32.0,
+            emissive: Color::from_rgb(0, 0, 0),
+            opacity: 1.0,
+            reflectivity: 0.0,
+            refractive_index: 1.5,
+        }
+    }
+
+
+    /// Color of the surface at surface coordinates `(u, v)`.
+    pub fn base_color(&self, u: f32, v: f32) -> Color {
+        match &self.texture {
+            Some(texture) => texture.sample(u, v),
+            None => self.color,
+        }
+    }
+
+
+    /// Diffuse and specular light reflected towards `to_viewer` from a white light of unit
+    /// intensity in direction `to_light`, for a surface of color `base`.
+    pub fn direct_light(&self, base: Color, normal: &UnitVector3<f32>, to_light: &Vector3<f32>, to_viewer: &Vector3<f32>) -> Color {
+        let to_light = to_light.normalize();
+        let cos = normal.dot(&to_light);
+
+        if cos <= 0.0 {
+            return Color::from_rgb_f(0.0, 0.0, 0.0);
+        }
+
+        let reflected = normal.into_inner() * (2.0 * cos) - to_light;
+        let highlight = reflected.dot(&to_viewer.normalize()).max(0.0).powf(self.shininess);
+
+        base * (self.diffuse * cos) + Color::from_rgb_f(1.0, 1.0, 1.0) * (self.specular * highlight)
+    }
+}
+
+
+impl Texture {
+    /// Loads a PNG image, any bit depth and color type.
+    pub fn load(path: &Path) -> Result<Texture, png::DecodingError> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let bytes = &buffer[..info.buffer_size()];
+
+        let pixels: Vec<Color> = match info.color_type {
+            png::ColorType::Grayscale => bytes.iter().map(|v| Color::from_rgb(*v, *v, *v)).collect(),
+            png::ColorType::GrayscaleAlpha => bytes.chunks_exact(2).map(|p| Color::from_rgba(p[0], p[0], p[0], p[1])).collect(),
+            png::ColorType::Rgba => bytes.chunks_exact(4).map(|p| Color::from_rgba(p[0], p[1], p[2], p[3])).collect(),
+            _ => bytes.chunks_exact(3).map(|p| Color::from_rgb(p[0], p[1], p[2])).collect(),
+        };
+
+        Ok(Texture {
+            path: path.to_string_lossy().into_owned(),
+            width: info.width as usize,
+            height: info.height as usize,
+            pixels: pixels.into(),
+        })
+    }
+
+
+    pub(super) fn from_pixels(path: String, width: usize, height: usize, pixels: Vec<Color>) -> Texture {
+        Texture { path, width, height, pixels: pixels.into() }
+    }
+
+
+    pub(super) fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+
+    pub(super) fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+
+    /// Bilinear interpolation of the pixels, `v` zero at the top row.
+    pub fn sample(&self, u: f32, v: f32) -> Color {
+        let x = u.rem_euclid(1.0) * self.width as f32 - 0.5;
+        let y = (v.clamp(0.0, 1.0) * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let (tx, ty) = (x - x0, y - y0);
+
+        let column = |offset: f32| (x0 + offset).rem_euclid(self.width as f32) as usize;
+        let row = |offset: f32| ((y0 + offset) as usize).min(self.height - 1);
+        let pixel = |r: usize, c: usize| self.pixels[r * self.width + c];
+
+        let top = pixel(row(0.0), column(0.0)).lerp(pixel(row(0.0), column(1.0)), tx);
+        let bottom = pixel(row(1.0), column(0.0)).lerp(pixel(row(1.0), column(1.0)), tx);
+
+        top.lerp(bottom, ty)
+    }
+}
+
+
+/// Textures are the same when they hold the same pixels.
+impl PartialEq for Texture {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && Arc::ptr_eq(&self.pixels, &other.pixels)
+    }
+}
+
+
+#[cfg(test)]
+mod material_tests {
+    use super::*;
+
+
+    #[test]
+    fn texture_wraps_around_and_interpolates() {
+        let black = Color::from_rgb(0, 0, 0);
+        let white = Color::from_rgb(255, 255, 255);
+        let texture = Texture { path: String::new(), width: 2, height: 1, pixels: vec![black, white].into() };
+
+        assert_eq!(black, texture.sample(0.25, 0.5));
+        assert_eq!(white, texture.sample(0.75, 0.5));
+        // Halfway between the last and the first column across the seam.
+        assert_eq!(black.lerp(white, 0.5), texture.sample(0.0, 0.5));
+        assert_eq!(black.lerp(white, 0.5), texture.sample(1.0, 0.0));
+    }
+
+
+    #[test]
+    fn phong_terms() {
+        let mut material = Material::new("test", Color::from_rgb(255, 0, 0));
+        material.diffuse = 0.5;
+        material.specular = 0.25;
+        material.shininess = 8.0;
+
+        let normal = Vector3::z_axis();
+
+        // Light and viewer along the normal: full diffuse and highlight.
+        let color = material.direct_light(material.color, &normal, &Vector3::z(), &Vector3::z());
+        assert_eq!(Color::from_rgb_f(0.75, 0.25, 0.25), color);
+
+        // Light behind the surface.
+        let color = material.direct_light(material.color, &normal, &-Vector3::z(), &Vector3::z());
+        assert_eq!(Color::from_rgb_f(0.0, 0.0, 0.0), color);
+    }
+}
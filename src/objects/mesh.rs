@@ -0,0 +1,498 @@
+extern crate nalgebra as na;
+
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use na::{Point3, Vector3};
+
+use crate::math::directions;
+use super::Ellipse;
+use super::primitive::{Primitive, PrimitiveKind};
+
+
+const MAX_RINGS: usize = 1024;
+const MAX_SEGMENTS: usize = 2048;
+
+
+/// Indexed triangle mesh with per-vertex normals, triangles are counter-clockwise seen from outside.
+#[derive(Default)]
+pub struct Mesh {
+    pub vertices: Vec<Point3<f32>>,
+    pub normals: Vec<Vector3<f32>>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+
+/// How densely the surface is sampled.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Tessellation {
+    /// Fixed number of latitude bands and longitude segments.
+    Uniform{latitudes: u32, longitudes: u32},
+    /// Sampling density follows curvature, so that no edge deviates from the
+    /// surface by more than `tolerance` world units.
+    Adaptive{tolerance: f32},
+}
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum MeshFormat {
+    Obj,
+    /// Binary STL.
+    Stl,
+    /// ASCII PLY.
+    Ply,
+}
+
+
+impl MeshFormat {
+    pub fn from_path(path: &Path) -> Option<MeshFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "obj" => Some(MeshFormat::Obj),
+            "stl" => Some(MeshFormat::Stl),
+            "ply" => Some(MeshFormat::Ply),
+            _ => None
+        }
+    }
+}
+
+
+impl Mesh {
+    /// Appends `other`, keeping both meshes as separate components.
+    pub fn append(&mut self, other: Mesh) {
+        let offset = self.vertices.len() as u32;
+
+        self.vertices.extend(other.vertices);
+        self.normals.extend(other.normals);
+        self.triangles.extend(other.triangles.into_iter().map(|t| t.map(|i| i + offset)));
+    }
+
+
+    /// Saves the mesh in the format matching the file extension.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let format = MeshFormat::from_path(path).ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unknown mesh format, use .obj, .stl or .ply"
+        ))?;
+
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        match format {
+            MeshFormat::Obj => self.write_obj(&mut writer)?,
+            MeshFormat::Stl => self.write_stl(&mut writer)?,
+            MeshFormat::Ply => self.write_ply(&mut writer)?,
+        }
+
+        writer.flush()
+    }
+
+
+    pub fn write_obj(&self, writer: &mut impl Write) -> io::Result<()> {
+        for v in &self.vertices {
+            writeln!(writer, "v {} {} {}", v.x, v.y, v.z)?;
+        }
+
+        for n in &self.normals {
+            writeln!(writer, "vn {} {} {}", n.x, n.y, n.z)?;
+        }
+
+        for [a, b, c] in &self.triangles {
+            writeln!(writer, "f {0}//{0} {1}//{1} {2}//{2}", a + 1, b + 1, c + 1)?;
+        }
+
+        Ok(())
+    }
+
+
+    pub fn write_stl(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut header = [0_u8; 80];
+        let title = b"ellipsoid_ray_casting";
+        header[..title.len()].copy_from_slice(title);
+
+        writer.write_all(&header)?;
+        writer.write_all(&(self.triangles.len() as u32).to_le_bytes())?;
+
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.map(|i| self.vertices[i as usize]);
+            let normal = (b - a).cross(&(c - a)).try_normalize(0.0).unwrap_or(Vector3::zeros());
+
+            for value in [normal.x, normal.y, normal.z, a.x, a.y, a.z, b.x, b.y, b.z, c.x, c.y, c.z] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+
+            writer.write_all(&0_u16.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+
+    pub fn write_ply(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format ascii 1.0")?;
+        writeln!(writer, "element vertex {}", self.vertices.len())?;
+        writeln!(writer, "property float x")?;
+        writeln!(writer, "property float y")?;
+        writeln!(writer, "property float z")?;
+        writeln!(writer, "property float nx")?;
+        writeln!(writer, "property float ny")?;
+        writeln!(writer, "property float nz")?;
+        writeln!(writer, "element face {}", self.triangles.len())?;
+        writeln!(writer, "property list uchar int vertex_indices")?;
+        writeln!(writer, "end_header")?;
+
+        for (v, n) in self.vertices.iter().zip(&self.normals) {
+            writeln!(writer, "{} {} {} {} {} {}", v.x, v.y, v.z, n.x, n.y, n.z)?;
+        }
+
+        for [a, b, c] in &self.triangles {
+            writeln!(writer, "3 {} {} {}", a, b, c)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+impl Ellipse {
+    /// Triangle mesh of the surface in world coordinates, with the current transform applied.
+    pub fn tessellate(&self, tessellation: Tessellation) -> Mesh {
+        let rings: Vec<(f32, usize)> = match tessellation {
+            Tessellation::Uniform { latitudes, longitudes } => {
+                let latitudes = usize::clamp(latitudes as usize, 2, MAX_RINGS);
+                let longitudes = usize::clamp(longitudes as usize, 3, MAX_SEGMENTS);
+
+                (0..=latitudes)
+                    .map(|i| {
+                        let theta = PI * i as f32 / latitudes as f32;
+                        let count = if i == 0 || i == latitudes { 1 } else { longitudes };
+                        (theta, count)
+                    })
+                    .collect()
+            }
+
+            Tessellation::Adaptive { tolerance } => self.adaptive_rings(f32::max(tolerance, 1e-6)),
+        };
+
+        let mut mesh = Mesh::default();
+        let mut ring_starts = Vec::with_capacity(rings.len());
+
+        for (theta, count) in &rings {
+            ring_starts.push(mesh.vertices.len() as u32);
+
+            for j in 0..*count {
+                let phi = 2.0 * PI * j as f32 / *count as f32;
+                let point = self.surface_point(*theta, phi);
+
+                mesh.normals.push(self.normal(&point).into_inner());
+                mesh.vertices.push(point);
+            }
+        }
+
+        for i in 1..rings.len() {
+            stitch_rings(&mut mesh, (ring_starts[i - 1], rings[i - 1].1), (ring_starts[i], rings[i].1));
+        }
+
+        mesh
+    }
+
+
+    /// Largest principal curvature at a surface point.
+    fn max_curvature(&self, point: &Point3<f32>) -> f32 {
+        let curvature = self.curvature(point);
+        let discriminant = f32::max(curvature.mean * curvature.mean - curvature.gaussian, 0.0);
+
+        (curvature.mean.abs() + discriminant.sqrt()).max(1e-6)
+    }
+
+
+    /// Rings of the adaptive tessellation. Edge length `s` on a curve with curvature `k`
+    /// deviates from it by about `k s^2 / 8`, which bounds the spacing of samples.
+    fn adaptive_rings(&self, tolerance: f32) -> Vec<(f32, usize)> {
+        const PROBES: usize = 16;
+
+        let max_edge = |k: f32| (8.0 * tolerance / k).sqrt();
+
+        let ring_info = |theta: f32| {
+            let mut curvature: f32 = 0.0;
+            let mut circumference = 0.0;
+            let mut prev = self.surface_point(theta, 0.0);
+
+            for j in 1..=PROBES {
+                let point = self.surface_point(theta, 2.0 * PI * j as f32 / PROBES as f32);
+                curvature = curvature.max(self.max_curvature(&point));
+                circumference += (point - prev).norm();
+                prev = point;
+            }
+
+            (curvature, circumference)
+        };
+
+        let mut rings = vec![(0.0, 1)];
+        let mut theta = 0.0;
+
+        while theta < PI && rings.len() < MAX_RINGS {
+            let (curvature, _) = ring_info(theta);
+
+            // Length of the meridian per unit of `theta` around the current ring.
+            let step_probe = 1e-3;
+            let speed = (self.surface_point(theta + step_probe, 0.0) - self.surface_point(theta, 0.0)).norm()
+                .max((self.surface_point(theta + step_probe, PI / 2.0) - self.surface_point(theta, PI / 2.0)).norm())
+                / step_probe;
+
+            let step = (max_edge(curvature) / speed.max(1e-6)).clamp(PI / MAX_RINGS as f32, PI / 4.0);
+            theta = f32::min(theta + step, PI);
+
+            if theta >= PI - 1e-4 {
+                break;
+            }
+
+            let (curvature, circumference) = ring_info(theta);
+            let segments = (circumference / max_edge(curvature)).ceil() as usize;
+            rings.push((theta, usize::clamp(segments, 3, MAX_SEGMENTS)));
+        }
+
+        rings.push((PI, 1));
+        rings
+    }
+}
+
+
+impl Primitive {
+    /// Triangle mesh of the quadric primitives: spheres, discs and bounded planes. `None`
+    /// for the other kinds and for unbounded planes.
+    pub fn tessellate(&self, tessellation: Tessellation) -> Option<Mesh> {
+        match self.kind {
+            PrimitiveKind::Sphere { center, radius } => {
+                let coefficient = 1.0 / (radius * radius);
+                Some(Ellipse::new(coefficient, coefficient, coefficient, &center, self.material).tessellate(tessellation))
+            }
+
+            PrimitiveKind::Disc { center, normal, radius } => {
+                let segments = match tessellation {
+                    Tessellation::Uniform { longitudes, .. } => longitudes as usize,
+                    // A chord over the angle `a` stays within `radius (1 - cos(a / 2))` of the rim.
+                    Tessellation::Adaptive { tolerance } => {
+                        let ratio = f32::clamp(1.0 - tolerance / radius, -1.0, 1.0);
+                        (PI / ratio.acos().max(1e-6)).ceil() as usize
+                    }
+                };
+                let segments = usize::clamp(segments, 3, MAX_SEGMENTS);
+                let (first, second) = directions::perpendicular_axes(&normal);
+
+                let rim = (0..segments).map(|j| {
+                    let phi = 2.0 * PI * j as f32 / segments as f32;
+                    center + (first * phi.cos() + second * phi.sin()) * radius
+                });
+
+                Some(flat_mesh(std::iter::once(center).chain(rim).collect(), &normal, |index| {
+                    [0, index + 1, (index + 1) % segments as u32 + 1]
+                }, segments))
+            }
+
+            PrimitiveKind::Plane { point, normal, half_size: Some(half_size) } => {
+                let (first, second) = directions::perpendicular_axes(&normal);
+                let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                    .map(|(u, v)| point + (first * u + second * v) * half_size);
+
+                Some(flat_mesh(corners.to_vec(), &normal, |index| [0, index + 1, index + 2], 2))
+            }
+
+            _ => None,
+        }
+    }
+}
+
+
+/// Mesh of `vertices` in the plane facing `normal`, with `count` triangles given by `triangle`.
+fn flat_mesh(vertices: Vec<Point3<f32>>, normal: &Vector3<f32>, triangle: impl Fn(u32) -> [u32; 3], count: usize) -> Mesh {
+    let mut mesh = Mesh {
+        normals: vec![normal.normalize(); vertices.len()],
+        vertices,
+        triangles: Vec::with_capacity(count),
+    };
+
+    for index in 0..count as u32 {
+        push_outward(&mut mesh, triangle(index));
+    }
+
+    mesh
+}
+
+
+/// Connects two rings of vertices evenly spread around the longitude, rings may differ in size.
+fn stitch_rings(mesh: &mut Mesh, upper: (u32, usize), lower: (u32, usize)) {
+    let (upper_start, upper_count) = upper;
+    let (lower_start, lower_count) = lower;
+
+    let upper_index = |i: usize| upper_start + (i % upper_count) as u32;
+    let lower_index = |j: usize| lower_start + (j % lower_count) as u32;
+
+    let mut i = 0;
+    let mut j = 0;
+
+    while i < upper_count || j < lower_count {
+        // Advance along the ring whose next vertex comes first in longitude.
+        let next_upper = (i + 1) as f32 / upper_count as f32;
+        let next_lower = (j + 1) as f32 / lower_count as f32;
+
+        let advance_upper = j >= lower_count || (i < upper_count && next_upper <= next_lower);
+
+        let triangle = if advance_upper {
+            let t = [upper_index(i), lower_index(j), upper_index(i + 1)];
+            i += 1;
+            t
+        } else {
+            let t = [upper_index(i), lower_index(j), lower_index(j + 1)];
+            j += 1;
+            t
+        };
+
+        if triangle[0] != triangle[2] && triangle[1] != triangle[2] {
+            push_outward(mesh, triangle);
+        }
+    }
+}
+
+
+/// Adds the triangle, flipped if needed so that its winding agrees with the vertex normals.
+fn push_outward(mesh: &mut Mesh, [a, b, c]: [u32; 3]) {
+    let pa = mesh.vertices[a as usize];
+    let pb = mesh.vertices[b as usize];
+    let pc = mesh.vertices[c as usize];
+
+    let face_normal = (pb - pa).cross(&(pc - pa));
+
+    if face_normal.dot(&mesh.normals[a as usize]) < 0.0 {
+        mesh.triangles.push([a, c, b]);
+    } else {
+        mesh.triangles.push([a, b, c]);
+    }
+}
+
+
+#[cfg(test)]
+mod mesh_tests {
+    use super::*;
+
+
+    fn unit_sphere() -> Ellipse {
+        Ellipse::new(1.0, 1.0, 1.0, &Point3::origin(), 0)
+    }
+
+
+    /// Volume enclosed by a closed, outward oriented mesh.
+    fn volume(mesh: &Mesh) -> f32 {
+        mesh.triangles.iter()
+            .map(|[a, b, c]| {
+                let a = mesh.vertices[*a as usize].coords;
+                let b = mesh.vertices[*b as usize].coords;
+                let c = mesh.vertices[*c as usize].coords;
+                a.dot(&b.cross(&c)) / 6.0
+            })
+            .sum()
+    }
+
+
+    #[test]
+    fn uniform_sphere_counts() {
+        let mesh = unit_sphere().tessellate(Tessellation::Uniform { latitudes: 4, longitudes: 8 });
+
+        assert_eq!(2 + 3 * 8, mesh.vertices.len());
+        assert_eq!(2 * 8 + 2 * 2 * 8, mesh.triangles.len());
+    }
+
+
+    #[test]
+    fn mesh_is_closed_and_oriented_outwards() {
+        let mut ellipse = Ellipse::new(0.25, 1.0, 1.0 / 9.0, &Point3::new(1.0, 0.0, 0.0), 0);
+        ellipse.scale = 2.0;
+        ellipse.recalculate();
+
+        // Exact volume is 4/3 pi a b c scaled by scale^3.
+        let exact = 4.0 / 3.0 * PI * 2.0 * 1.0 * 3.0 * 8.0;
+
+        let uniform = ellipse.tessellate(Tessellation::Uniform { latitudes: 64, longitudes: 128 });
+        assert!((volume(&uniform) - exact).abs() / exact < 0.01);
+
+        let adaptive = ellipse.tessellate(Tessellation::Adaptive { tolerance: 0.01 });
+        assert!((volume(&adaptive) - exact).abs() / exact < 0.01);
+    }
+
+
+    #[test]
+    fn quadric_primitives() {
+        let tessellation = Tessellation::Uniform { latitudes: 64, longitudes: 128 };
+
+        let sphere = Primitive::new(PrimitiveKind::Sphere { center: Point3::new(1.0, 2.0, 3.0), radius: 2.0 }, 0);
+        let mesh = sphere.tessellate(tessellation).unwrap();
+        assert!((volume(&mesh) - 4.0 / 3.0 * PI * 8.0).abs() < 0.05 * 4.0 / 3.0 * PI * 8.0);
+
+        let normal = Vector3::new(0.0, 1.0, 1.0);
+        let disc = Primitive::new(PrimitiveKind::Disc { center: Point3::origin(), normal, radius: 1.0 }, 0);
+        let mesh = disc.tessellate(tessellation).unwrap();
+        assert_eq!((129, 128), (mesh.vertices.len(), mesh.triangles.len()));
+
+        for [a, b, c] in &mesh.triangles {
+            let [a, b, c] = [a, b, c].map(|index| mesh.vertices[*index as usize]);
+            assert!((b - a).cross(&(c - a)).dot(&normal) > 0.0);
+        }
+
+        let plane = Primitive::new(PrimitiveKind::Plane { point: Point3::origin(), normal, half_size: Some(2.0) }, 0);
+        let mesh = plane.tessellate(tessellation).unwrap();
+        let area: f32 = mesh.triangles.iter()
+            .map(|[a, b, c]| {
+                let [a, b, c] = [a, b, c].map(|index| mesh.vertices[*index as usize]);
+                (b - a).cross(&(c - a)).norm() / 2.0
+            })
+            .sum();
+        assert!((area - 16.0).abs() < 1e-4);
+
+        assert!(Primitive::ground(0.0, 0).tessellate(tessellation).is_none());
+    }
+
+
+    #[test]
+    fn adaptive_mode_refines_with_tolerance() {
+        let sphere = unit_sphere();
+
+        let coarse = sphere.tessellate(Tessellation::Adaptive { tolerance: 0.05 });
+        let fine = sphere.tessellate(Tessellation::Adaptive { tolerance: 0.001 });
+
+        assert!(fine.triangles.len() > coarse.triangles.len());
+    }
+
+
+    #[test]
+    fn obj_output() {
+        let mesh = unit_sphere().tessellate(Tessellation::Uniform { latitudes: 2, longitudes: 3 });
+        let mut output = Vec::new();
+        mesh.write_obj(&mut output).unwrap();
+
+        let text = String::from_utf8(output).unwrap();
+        assert_eq!(5, text.lines().filter(|line| line.starts_with("v ")).count());
+        assert_eq!(5, text.lines().filter(|line| line.starts_with("vn ")).count());
+        assert_eq!(6, text.lines().filter(|line| line.starts_with("f ")).count());
+    }
+
+
+    #[test]
+    fn binary_stl_size() {
+        let mesh = unit_sphere().tessellate(Tessellation::Uniform { latitudes: 2, longitudes: 3 });
+        let mut output = Vec::new();
+        mesh.write_stl(&mut output).unwrap();
+
+        assert_eq!(84 + 50 * 6, output.len());
+    }
+
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(Some(MeshFormat::Stl), MeshFormat::from_path(Path::new("shape.STL")));
+        assert_eq!(Some(MeshFormat::Ply), MeshFormat::from_path(Path::new("dir/shape.ply")));
+        assert_eq!(None, MeshFormat::from_path(Path::new("shape.png")));
+    }
+}
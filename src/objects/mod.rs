@@ -1,10 +1,38 @@
+pub mod collision;
+pub mod bvh;
+pub mod csg;
+pub mod curve;
 pub mod ellipse;
+pub mod gaussian_splat;
+pub mod mesh;
+pub mod point_cloud;
+pub mod primitive;
+pub mod sdf;
+pub mod svg;
+pub mod tensor_field;
+mod aabb;
+mod background;
 mod camera;
 mod canvas;
+mod clip_plane;
 mod color;
+mod colormap;
+mod environment;
+mod light;
+mod material;
+mod ply;
+mod tone_mapping;
 
 
+pub use aabb::Aabb;
+pub use background::Background;
 pub use camera::{Camera, CAMERA_CENTER};
 pub use ellipse::Ellipse;
 pub use canvas::Canvas;
-pub use color::Color;
\ No newline at end of file
+pub use clip_plane::ClipPlane;
+pub use color::{Color, ParseColorError};
+pub use colormap::Colormap;
+pub use environment::{Environment, EnvironmentError};
+pub use light::{Light, ShadowMode};
+pub use material::{Material, Texture};
+pub use tone_mapping::ToneMapping;
@@ -0,0 +1,175 @@
+//! Vertex element of ASCII and binary little endian PLY files, shared by the loaders of
+//! point clouds and Gaussian splats.
+
+use std::fmt;
+
+
+/// Scalar properties of every vertex, read as `f32`.
+pub struct PlyVertices {
+    names: Vec<String>,
+    /// Values of the vertices one after another, each in the order of `names`.
+    values: Vec<f32>,
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct PlyError {
+    pub line: usize,
+    pub message: String,
+}
+
+
+impl PlyVertices {
+    /// Reads the vertex element, which has to come first. Elements after it are ignored.
+    pub fn parse(data: &[u8]) -> Result<PlyVertices, PlyError> {
+        let error = |line: usize, message: &str| PlyError { line, message: String::from(message) };
+
+        // Files written on Windows end their lines with "\r\n", which `lines` also strips.
+        let header_end = [&b"end_header\n"[..], b"end_header\r\n"].into_iter()
+            .filter_map(|marker| find(data, marker).map(|start| start + marker.len()))
+            .min()
+            .ok_or(error(1, "missing end_header"))?;
+        let header = String::from_utf8_lossy(&data[..header_end]);
+
+        let mut binary = false;
+        let mut vertex_count = None;
+        let mut properties: Vec<(String, String)> = Vec::new();
+
+        for (line_index, line) in header.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            match words[..] {
+                ["ply"] | ["end_header"] | [] => (),
+                ["comment", ..] | ["obj_info", ..] => (),
+                ["format", "ascii", _] => binary = false,
+                ["format", "binary_little_endian", _] => binary = true,
+                ["format", format, _] => return Err(error(line_index + 1, &format!("unsupported format {}", format))),
+                ["element", "vertex", count] if vertex_count.is_none() => {
+                    vertex_count = Some(count.parse::<usize>().map_err(|_| error(line_index + 1, "invalid vertex count"))?);
+                }
+                ["element", ..] if vertex_count.is_none() => return Err(error(line_index + 1, "vertex must be the first element")),
+                ["element", ..] => properties.push((String::from("end"), String::new())),
+                ["property", "list", ..] => properties.push((String::from("list"), String::new())),
+                ["property", kind, name] => properties.push((String::from(kind), String::from(name))),
+                _ => return Err(error(line_index + 1, &format!("unexpected \"{}\"", line))),
+            }
+        }
+
+        let vertex_count = vertex_count.ok_or(error(1, "missing vertex element"))?;
+
+        // Only properties of the vertex element matter.
+        if let Some(end) = properties.iter().position(|(kind, _)| kind == "end") {
+            properties.truncate(end);
+        }
+
+        if properties.is_empty() {
+            return Err(error(1, "vertex has no properties"));
+        }
+
+        let value_count = vertex_count.checked_mul(properties.len()).ok_or(error(1, "invalid vertex count"))?;
+        let body = &data[header_end..];
+        let mut values;
+
+        if binary {
+            let sizes = properties.iter()
+                .map(|(kind, _)| property_size(kind).ok_or(error(1, &format!("unsupported vertex property type {}", kind))))
+                .collect::<Result<Vec<usize>, PlyError>>()?;
+            let stride: usize = sizes.iter().sum();
+
+            // The header is checked against the body before anything is allocated.
+            if stride.checked_mul(vertex_count).is_none_or(|size| body.len() < size) {
+                return Err(error(1, "file ends before the last vertex"));
+            }
+
+            values = Vec::with_capacity(value_count);
+
+            for vertex in body.chunks_exact(stride).take(vertex_count) {
+                let mut offset = 0;
+
+                for ((kind, _), size) in properties.iter().zip(&sizes) {
+                    values.push(read_binary(kind, &vertex[offset..offset + size]));
+                    offset += size;
+                }
+            }
+        } else {
+            let header_lines = header.lines().count();
+            let lines = String::from_utf8_lossy(body);
+            let mut count = 0;
+
+            // Every value takes at least one byte of the body.
+            values = Vec::with_capacity(value_count.min(body.len()));
+
+            for (line_index, line) in lines.lines().take(vertex_count).enumerate() {
+                let vertex = line.split_whitespace()
+                    .take(properties.len())
+                    .map(|value| value.parse::<f32>().ok())
+                    .collect::<Option<Vec<f32>>>()
+                    .filter(|vertex| vertex.len() == properties.len())
+                    .ok_or(error(header_lines + line_index + 1, "invalid vertex"))?;
+
+                values.extend(vertex);
+                count += 1;
+            }
+
+            if count < vertex_count {
+                return Err(error(header_lines + count + 1, "file ends before the last vertex"));
+            }
+        }
+
+        Ok(PlyVertices { names: properties.into_iter().map(|(_, name)| name).collect(), values })
+    }
+
+
+    /// Position of the property `name` within a vertex.
+    pub fn property(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|property| property == name)
+    }
+
+
+    /// Like `property`, failing when the vertex has no such property.
+    pub fn require(&self, name: &str) -> Result<usize, PlyError> {
+        self.property(name).ok_or(PlyError { line: 1, message: format!("vertex has no {} property", name) })
+    }
+
+
+    pub fn vertices(&self) -> impl Iterator<Item = &[f32]> {
+        self.values.chunks_exact(self.names.len())
+    }
+}
+
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+
+fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+    data.windows(pattern.len()).position(|window| window == pattern)
+}
+
+
+fn property_size(kind: &str) -> Option<usize> {
+    match kind {
+        "char" | "uchar" | "int8" | "uint8" => Some(1),
+        "short" | "ushort" | "int16" | "uint16" => Some(2),
+        "int" | "uint" | "int32" | "uint32" | "float" | "float32" => Some(4),
+        "double" | "float64" => Some(8),
+        _ => None
+    }
+}
+
+
+fn read_binary(kind: &str, bytes: &[u8]) -> f32 {
+    match kind {
+        "char" | "int8" => bytes[0] as i8 as f32,
+        "uchar" | "uint8" => bytes[0] as f32,
+        "short" | "int16" => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+        "ushort" | "uint16" => u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+        "int" | "int32" => i32::from_le_bytes(bytes.try_into().unwrap()) as f32,
+        "uint" | "uint32" => u32::from_le_bytes(bytes.try_into().unwrap()) as f32,
+        "float" | "float32" => f32::from_le_bytes(bytes.try_into().unwrap()),
+        _ => f64::from_le_bytes(bytes.try_into().unwrap()) as f32,
+    }
+}
@@ -0,0 +1,224 @@
+//! Point sets read from text files with one point per line (`.csv`, `.xyz`, `.txt`)
+//! or from ASCII and binary little endian PLY files.
+
+extern crate nalgebra as na;
+
+use std::{error, fmt, fs, io};
+use std::path::Path;
+
+use na::{Point3, Vector3};
+
+use crate::math::ellipsoid_fit;
+use super::Ellipse;
+use super::ply::{PlyError, PlyVertices};
+
+
+pub struct PointCloud {
+    pub points: Vec<Point3<f32>>,
+}
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum FitMethod {
+    /// Algebraic least-squares fit, suited to points sampled on the surface.
+    LeastSquares,
+    /// Smallest ellipsoid containing every point.
+    MinimumVolume,
+}
+
+
+#[derive(Debug)]
+pub enum PointCloudError {
+    Io(io::Error),
+    Parse{line: usize, message: String},
+}
+
+
+impl FitMethod {
+    pub const ALL: [FitMethod; 2] = [FitMethod::LeastSquares, FitMethod::MinimumVolume];
+
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FitMethod::LeastSquares => "Least squares",
+            FitMethod::MinimumVolume => "Minimum volume enclosing",
+        }
+    }
+}
+
+
+impl PointCloud {
+    /// Loads points, PLY files are recognized by their extension.
+    pub fn load(path: &Path) -> Result<PointCloud, PointCloudError> {
+        let data = fs::read(path)?;
+        let is_ply = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ply"));
+
+        if is_ply {
+            PointCloud::parse_ply(&data)
+        } else {
+            PointCloud::parse_text(&String::from_utf8_lossy(&data))
+        }
+    }
+
+
+    /// Reads the first three numbers of every line, separated by commas, semicolons or
+    /// whitespace. Further columns are ignored, a header line and `#` comments are skipped.
+    pub fn parse_text(text: &str) -> Result<PointCloud, PointCloudError> {
+        let mut points = Vec::new();
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match parse_coordinates(line.split(|c: char| c == ',' || c == ';' || c.is_whitespace()).filter(|value| !value.is_empty())) {
+                Some(point) => points.push(point),
+                None if points.is_empty() && line_index == first_content_line(text) => (),
+                None => return Err(PointCloudError::Parse {
+                    line: line_index + 1,
+                    message: format!("expected three coordinates, found \"{}\"", line)
+                }),
+            }
+        }
+
+        Ok(PointCloud { points })
+    }
+
+
+    /// Reads the `x`, `y` and `z` properties of the vertex element, which has to come first.
+    pub fn parse_ply(data: &[u8]) -> Result<PointCloud, PointCloudError> {
+        let vertices = PlyVertices::parse(data)?;
+        let [x, y, z] = [vertices.require("x")?, vertices.require("y")?, vertices.require("z")?];
+
+        let points = vertices.vertices()
+            .map(|vertex| Point3::new(vertex[x], vertex[y], vertex[z]))
+            .collect();
+
+        Ok(PointCloud { points })
+    }
+
+
+    /// Ellipsoid fitted to the points, `None` when the points do not determine one.
+    pub fn fit(&self, method: FitMethod, material: usize) -> Option<Ellipse> {
+        let points: Vec<Vector3<f64>> = self.points.iter().map(|p| p.coords.cast()).collect();
+
+        let fit = match method {
+            FitMethod::LeastSquares => ellipsoid_fit::least_squares(&points),
+            FitMethod::MinimumVolume => ellipsoid_fit::minimum_volume_enclosing(&points, 1e-4),
+        }?;
+
+        Ellipse::from_shape_matrix(&Point3::from(fit.center.cast()), &fit.shape.cast(), material)
+    }
+}
+
+
+fn parse_coordinates<'a>(mut values: impl Iterator<Item = &'a str>) -> Option<Point3<f32>> {
+    let mut coordinate = || values.next()?.parse::<f32>().ok();
+
+    Some(Point3::new(coordinate()?, coordinate()?, coordinate()?))
+}
+
+
+fn first_content_line(text: &str) -> usize {
+    text.lines()
+        .position(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
+        .unwrap_or(0)
+}
+
+
+impl fmt::Display for PointCloudError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PointCloudError::Io(err) => write!(f, "{}", err),
+            PointCloudError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+
+impl error::Error for PointCloudError {}
+
+
+impl From<io::Error> for PointCloudError {
+    fn from(err: io::Error) -> Self {
+        PointCloudError::Io(err)
+    }
+}
+
+
+impl From<PlyError> for PointCloudError {
+    fn from(err: PlyError) -> Self {
+        PointCloudError::Parse { line: err.line, message: err.message }
+    }
+}
+
+
+#[cfg(test)]
+mod point_cloud_tests {
+    use super::*;
+
+
+    #[test]
+    fn csv_with_header_and_extra_columns() {
+        let cloud = PointCloud::parse_text("x,y,z,intensity\n1,2,3,0.5\n\n# comment\n-1, 0.5 ,2e1,7\n").unwrap();
+
+        assert_eq!(vec![Point3::new(1.0, 2.0, 3.0), Point3::new(-1.0, 0.5, 20.0)], cloud.points);
+    }
+
+
+    #[test]
+    fn text_errors_report_line() {
+        let error = PointCloud::parse_text("1 2 3\n4 5\n").err().unwrap();
+
+        assert_eq!("line 2: expected three coordinates, found \"4 5\"", error.to_string());
+    }
+
+
+    #[test]
+    fn ascii_ply() {
+        let text = "ply\nformat ascii 1.0\nelement vertex 2\nproperty float z\nproperty float x\nproperty float y\n\
+                    element face 0\nproperty list uchar int vertex_indices\nend_header\n3 1 2\n6 4 5\n";
+        let cloud = PointCloud::parse_ply(text.as_bytes()).unwrap();
+
+        assert_eq!(vec![Point3::new(1.0, 2.0, 3.0), Point3::new(4.0, 5.0, 6.0)], cloud.points);
+
+        let cloud = PointCloud::parse_ply(text.replace('\n', "\r\n").as_bytes()).unwrap();
+        assert_eq!(vec![Point3::new(1.0, 2.0, 3.0), Point3::new(4.0, 5.0, 6.0)], cloud.points);
+    }
+
+
+    #[test]
+    fn binary_ply() {
+        let mut data = b"ply\r\nformat binary_little_endian 1.0\r\nelement vertex 1\r\nproperty double x\r\nproperty uchar red\r\n\
+                         property float y\r\nproperty float z\r\nend_header\r\n".to_vec();
+        data.extend(1.5_f64.to_le_bytes());
+        data.push(255);
+        data.extend((-2.0_f32).to_le_bytes());
+        data.extend(4.0_f32.to_le_bytes());
+
+        let cloud = PointCloud::parse_ply(&data).unwrap();
+
+        assert_eq!(vec![Point3::new(1.5, -2.0, 4.0)], cloud.points);
+    }
+
+
+    #[test]
+    fn fitted_ellipse_passes_through_points() {
+        let points: Vec<Point3<f32>> = (0..40)
+            .map(|i| {
+                let theta = std::f32::consts::PI * ((i % 8) as f32 + 0.5) / 8.0;
+                let phi = 2.0 * std::f32::consts::PI * (i / 8) as f32 / 5.0;
+                Point3::new(1.0 + 2.0 * theta.sin() * phi.cos(), -1.0 + theta.cos(), 0.5 * theta.sin() * phi.sin())
+            })
+            .collect();
+
+        let cloud = PointCloud { points };
+
+        for method in FitMethod::ALL {
+            let ellipse = cloud.fit(method, 0).unwrap();
+
+            assert!((ellipse.center() - Point3::new(1.0, -1.0, 0.0)).norm() < 1e-2, "{}", method.name());
+        }
+    }
+}
@@ -0,0 +1,490 @@
+//! Simple objects shown next to the ellipsoids: planes, spheres, boxes and discs, tori and
+//! superellipsoids to compare the ellipsoids with, and implicit surfaces.
+
+extern crate nalgebra as na;
+
+use std::f32::consts::PI;
+
+use na::{Point3, UnitVector3, Vector3};
+
+use crate::math::{directions, quartic_equation};
+use super::{Aabb, Color};
+use super::ellipse::{Span, Surface};
+use super::sdf::SdfNode;
+
+
+/// Steps taken through the bounding box of a superellipsoid looking for a change of sign.
+const SUPERELLIPSOID_STEPS: usize = 64;
+
+const BISECTION_ITERATIONS: usize = 32;
+
+
+/// Geometry shared by every kind of object the renderer traces rays against.
+pub trait Hittable {
+    /// Part of the ray `origin + t * direction` inside the object, `start` and `end` given as
+    /// values of `t`. Flat objects have no inside and give a span of zero length. Objects
+    /// that are not convex give the first of their `ray_spans`.
+    fn ray_span(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Option<Span>;
+
+    /// Every part of the ray inside the object in increasing order.
+    fn ray_spans(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Vec<Span> {
+        self.ray_span(origin, direction).into_iter().collect()
+    }
+
+    /// Outward normal at a point of the surface.
+    fn normal(&self, point: &Point3<f32>) -> UnitVector3<f32>;
+
+    /// Box holding the whole object, `None` for unbounded ones.
+    fn bounds(&self) -> Option<Aabb>;
+
+    /// Texture coordinates of a point of the surface.
+    fn surface_uv(&self, point: &Point3<f32>) -> (f32, f32);
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum PrimitiveKind {
+    /// Plane through `point`, limited to a square of `2 * half_size` sides when bounded.
+    Plane { point: Point3<f32>, normal: Vector3<f32>, half_size: Option<f32> },
+    Sphere { center: Point3<f32>, radius: f32 },
+    /// Axis aligned box.
+    Box { min: Point3<f32>, max: Point3<f32> },
+    Disc { center: Point3<f32>, normal: Vector3<f32>, radius: f32 },
+    /// Ring around `axis` through `center`, the tube of radius `minor_radius` follows a circle
+    /// of radius `major_radius`.
+    Torus { center: Point3<f32>, axis: Vector3<f32>, major_radius: f32, minor_radius: f32 },
+    /// Axis aligned superellipsoid with the y axis as its pole. The exponents shape the vertical
+    /// and horizontal cross sections: 1 gives an ellipsoid, values towards 0 a box and 2 a
+    /// double cone.
+    Superellipsoid { center: Point3<f32>, radii: Vector3<f32>, vertical: f32, horizontal: f32 },
+    /// Surface where the signed distance function is zero, found by sphere tracing.
+    Implicit(SdfNode),
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Primitive {
+    pub kind: PrimitiveKind,
+    /// Index of the material in the scene's material library.
+    pub material: usize,
+    /// Density used by X-ray rendering, scales the absorption per unit of length.
+    pub density: f32,
+    /// Per channel absorption coefficients used by X-ray rendering.
+    pub absorption: Color,
+}
+
+
+impl PrimitiveKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PrimitiveKind::Plane { .. } => "plane",
+            PrimitiveKind::Sphere { .. } => "sphere",
+            PrimitiveKind::Box { .. } => "box",
+            PrimitiveKind::Disc { .. } => "disc",
+            PrimitiveKind::Torus { .. } => "torus",
+            PrimitiveKind::Superellipsoid { .. } => "superellipsoid",
+            PrimitiveKind::Implicit(_) => "implicit",
+        }
+    }
+}
+
+
+impl Primitive {
+    pub fn new(kind: PrimitiveKind, material: usize) -> Primitive {
+        Primitive { kind, material, density: 0.5, absorption: Color::from_rgb_f(1.0, 1.0, 1.0) }
+    }
+
+
+    /// Unbounded horizontal plane at height `y`.
+    pub fn ground(y: f32, material: usize) -> Primitive {
+        Primitive::new(PrimitiveKind::Plane { point: Point3::new(0.0, y, 0.0), normal: Vector3::y(), half_size: None }, material)
+    }
+}
+
+
+impl Hittable for Primitive {
+    fn ray_span(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Option<Span> {
+        match self.kind {
+            PrimitiveKind::Plane { point, normal, half_size } => {
+                let t = flat_hit(origin, direction, &point, &normal)?;
+
+                if let Some(half_size) = half_size {
+                    let (first, second) = directions::perpendicular_axes(&normal);
+                    let offset = origin + direction * t - point;
+
+                    if offset.dot(&first).abs() > half_size || offset.dot(&second).abs() > half_size {
+                        return None;
+                    }
+                }
+
+                Some(flat_span(t, direction, &normal))
+            }
+
+            PrimitiveKind::Disc { center, normal, radius } => {
+                let t = flat_hit(origin, direction, &center, &normal)?;
+                let offset = origin + direction * t - center;
+
+                (offset.norm_squared() <= radius * radius).then(|| flat_span(t, direction, &normal))
+            }
+
+            PrimitiveKind::Sphere { center, radius } => {
+                let offset = origin - center;
+                let a = direction.norm_squared();
+                let half_b = offset.dot(direction);
+                let discriminant = half_b * half_b - a * (offset.norm_squared() - radius * radius);
+
+                if discriminant < 0.0 || a == 0.0 {
+                    return None;
+                }
+
+                let root = discriminant.sqrt();
+
+                Some(Span { start: (-half_b - root) / a, end: (-half_b + root) / a, entry: Surface::Outer, exit: Surface::Inner })
+            }
+
+            PrimitiveKind::Box { min, max } => Aabb { min, max }.ray_range(origin, direction)
+                .map(|(start, end)| Span { start, end, entry: Surface::Outer, exit: Surface::Inner }),
+
+            PrimitiveKind::Torus { .. } | PrimitiveKind::Superellipsoid { .. } | PrimitiveKind::Implicit(_) =>
+                self.ray_spans(origin, direction).into_iter().next(),
+        }
+    }
+
+
+    fn ray_spans(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Vec<Span> {
+        let roots = match self.kind {
+            PrimitiveKind::Torus { center, axis, major_radius, minor_radius } =>
+                torus_roots(origin, direction, &center, &axis.normalize(), major_radius, minor_radius),
+            PrimitiveKind::Superellipsoid { center, radii, vertical, horizontal } => {
+                // In coordinates where the superellipsoid fills the cube from -1 to 1.
+                let origin = Point3::from((origin - center).component_div(&radii));
+                superellipsoid_roots(&origin, &direction.component_div(&radii), vertical, horizontal)
+            }
+            PrimitiveKind::Implicit(ref root) => root.ray_roots(origin, direction),
+            _ => return self.ray_span(origin, direction).into_iter().collect(),
+        };
+
+        // The ray enters and leaves the solid in turns, an unpaired root only touches it.
+        roots.chunks_exact(2)
+            .map(|pair| Span { start: pair[0], end: pair[1], entry: Surface::Outer, exit: Surface::Inner })
+            .collect()
+    }
+
+
+    fn normal(&self, point: &Point3<f32>) -> UnitVector3<f32> {
+        match self.kind {
+            PrimitiveKind::Plane { normal, .. } | PrimitiveKind::Disc { normal, .. } => UnitVector3::new_normalize(normal),
+            PrimitiveKind::Sphere { center, .. } => UnitVector3::new_normalize(point - center),
+            PrimitiveKind::Box { min, max } => {
+                // The face whose plane is closest to the point relative to the box size.
+                let center = na::center(&min, &max);
+                let relative = (point - center).component_div(&(0.5 * (max - min)));
+                let axis = relative.iamax();
+
+                let mut normal = Vector3::zeros();
+                normal[axis] = relative[axis].signum();
+
+                UnitVector3::new_unchecked(normal)
+            }
+            PrimitiveKind::Torus { center, axis, major_radius, .. } => {
+                // Away from the closest point of the circle running inside the tube.
+                let offset = point - center;
+                let axis = axis.normalize();
+                let radial = offset - axis * offset.dot(&axis);
+
+                UnitVector3::new_normalize(offset - radial.try_normalize(0.0).unwrap_or(radial) * major_radius)
+            }
+            PrimitiveKind::Superellipsoid { center, radii, vertical, horizontal } => {
+                let local = (point - center).component_div(&radii);
+                let gradient = superellipsoid_gradient(&local, vertical, horizontal);
+
+                // Normals transform with the inverse of the scaling.
+                UnitVector3::try_new(gradient.component_div(&radii), 0.0)
+                    .unwrap_or_else(|| UnitVector3::new_normalize(local.component_div(&radii)))
+            }
+            PrimitiveKind::Implicit(ref root) => root.normal(point),
+        }
+    }
+
+
+    fn bounds(&self) -> Option<Aabb> {
+        match self.kind {
+            PrimitiveKind::Plane { point, normal, half_size } => {
+                let (first, second) = directions::perpendicular_axes(&normal);
+                let half = half_size?;
+
+                Some(Aabb::from_center(&point, &((first.abs() + second.abs()) * half)))
+            }
+            PrimitiveKind::Sphere { center, radius } => Some(Aabb::from_center(&center, &Vector3::repeat(radius))),
+            PrimitiveKind::Box { min, max } => Some(Aabb { min, max }),
+            PrimitiveKind::Disc { center, normal, radius } => {
+                // Half extent along axis `i` is the radius times the sine of the angle to the normal.
+                let normal = normal.normalize();
+                let half_size = Vector3::from_fn(|i, _| radius * (1.0 - normal[i] * normal[i]).max(0.0).sqrt());
+
+                Some(Aabb::from_center(&center, &half_size))
+            }
+            PrimitiveKind::Torus { center, axis, major_radius, minor_radius } => {
+                let axis = axis.normalize();
+                let half_size = Vector3::from_fn(|i, _| major_radius * (1.0 - axis[i] * axis[i]).max(0.0).sqrt() + minor_radius);
+
+                Some(Aabb::from_center(&center, &half_size))
+            }
+            PrimitiveKind::Superellipsoid { center, radii, .. } => Some(Aabb::from_center(&center, &radii.abs())),
+            PrimitiveKind::Implicit(ref root) => Some(root.bounds()),
+        }
+    }
+
+
+    fn surface_uv(&self, point: &Point3<f32>) -> (f32, f32) {
+        match self.kind {
+            PrimitiveKind::Plane { point: origin, normal, half_size } => {
+                let (first, second) = directions::perpendicular_axes(&normal);
+                let offset = point - origin;
+                // Bounded planes are covered once, unbounded ones repeat the texture every unit.
+                let size = half_size.map_or(1.0, |half_size| 2.0 * half_size);
+
+                ((offset.dot(&first) / size + 0.5).rem_euclid(1.0), (offset.dot(&second) / size + 0.5).rem_euclid(1.0))
+            }
+            PrimitiveKind::Disc { center, normal, radius } => {
+                let (first, second) = directions::perpendicular_axes(&normal);
+                let offset = point - center;
+
+                (0.5 + offset.dot(&second).atan2(offset.dot(&first)) / (2.0 * PI), offset.norm() / radius)
+            }
+            PrimitiveKind::Sphere { center, .. } => {
+                directions::spherical_uv(&(point - center))
+            }
+            PrimitiveKind::Box { min, max } => {
+                // Coordinates across the face the point lies on.
+                let relative = (point - min).component_div(&(max - min));
+                let axis = self.normal(point).iamax();
+
+                match axis {
+                    0 => (relative.z, 1.0 - relative.y),
+                    1 => (relative.x, relative.z),
+                    _ => (relative.x, 1.0 - relative.y),
+                }
+            }
+            PrimitiveKind::Torus { center, axis, major_radius, .. } => {
+                // Angle around the axis, then around the tube.
+                let (first, second) = directions::perpendicular_axes(&axis);
+                let axis = axis.normalize();
+                let offset = point - center;
+                let radial = offset - axis * offset.dot(&axis);
+
+                (
+                    0.5 + offset.dot(&second).atan2(offset.dot(&first)) / (2.0 * PI),
+                    0.5 + offset.dot(&axis).atan2(radial.norm() - major_radius) / (2.0 * PI)
+                )
+            }
+            PrimitiveKind::Superellipsoid { center, radii, .. } => {
+                directions::spherical_uv(&(point - center).component_div(&radii))
+            }
+            PrimitiveKind::Implicit(ref root) => {
+                directions::spherical_uv(&(point - root.bounds().center()))
+            }
+        }
+    }
+}
+
+
+/// Parameter where the ray meets the plane through `point`, `None` for rays along it.
+fn flat_hit(origin: &Point3<f32>, direction: &Vector3<f32>, point: &Point3<f32>, normal: &Vector3<f32>) -> Option<f32> {
+    let rate = direction.dot(normal);
+
+    (rate != 0.0).then(|| (point - origin).dot(normal) / rate)
+}
+
+
+/// Span of a flat surface met at `t`, the side facing the ray is seen.
+fn flat_span(t: f32, direction: &Vector3<f32>, normal: &Vector3<f32>) -> Span {
+    let (entry, exit) = if direction.dot(normal) < 0.0 { (Surface::Outer, Surface::Inner) } else { (Surface::Inner, Surface::Outer) };
+
+    Span { start: t, end: t, entry, exit }
+}
+
+
+/// Values of `t` where the ray meets the torus, in increasing order.
+fn torus_roots(origin: &Point3<f32>, direction: &Vector3<f32>, center: &Point3<f32>, axis: &Vector3<f32>, major_radius: f32, minor_radius: f32) -> Vec<f32> {
+    let length = direction.norm() as f64;
+
+    if length == 0.0 {
+        return Vec::new();
+    }
+
+    // The quartic is solved in f64 along a unit direction, measured from the point of the
+    // ray closest to the center to keep the coefficients small.
+    let direction = direction.cast::<f64>() / length;
+    let axis = axis.cast::<f64>();
+    let closest = (center - origin).cast::<f64>().dot(&direction);
+    let offset = (origin - center).cast::<f64>() + direction * closest;
+
+    let (major, minor) = (major_radius as f64, minor_radius as f64);
+    let along = offset.dot(&direction);
+    let height = offset.dot(&axis);
+    let rate = direction.dot(&axis);
+    let k = offset.norm_squared() + major * major - minor * minor;
+    let four_major = 4.0 * major * major;
+
+    // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (|p|^2 - (p . axis)^2) for p = offset + s * direction.
+    quartic_equation::solve(
+        1.0,
+        4.0 * along,
+        4.0 * along * along + 2.0 * k - four_major * (1.0 - rate * rate),
+        4.0 * along * k - 2.0 * four_major * (along - height * rate),
+        k * k - four_major * (offset.norm_squared() - height * height),
+    )
+        .into_iter()
+        .map(|s| ((closest + s) / length) as f32)
+        .collect()
+}
+
+
+/// Negative inside the unit superellipsoid, positive outside.
+fn superellipsoid_value(point: &Point3<f32>, vertical: f32, horizontal: f32) -> f32 {
+    let around = point.x.abs().powf(2.0 / horizontal) + point.z.abs().powf(2.0 / horizontal);
+
+    around.powf(horizontal / vertical) + point.y.abs().powf(2.0 / vertical) - 1.0
+}
+
+
+fn superellipsoid_gradient(point: &Vector3<f32>, vertical: f32, horizontal: f32) -> Vector3<f32> {
+    // Kept away from zero where the negative powers of steep exponents blow up.
+    let magnitude = point.map(|coordinate| coordinate.abs().max(1e-6));
+    let around = magnitude.x.powf(2.0 / horizontal) + magnitude.z.powf(2.0 / horizontal);
+    let scale = 2.0 / vertical * around.powf(horizontal / vertical - 1.0);
+
+    Vector3::new(
+        scale * magnitude.x.powf(2.0 / horizontal - 1.0) * point.x.signum(),
+        2.0 / vertical * magnitude.y.powf(2.0 / vertical - 1.0) * point.y.signum(),
+        scale * magnitude.z.powf(2.0 / horizontal - 1.0) * point.z.signum(),
+    )
+}
+
+
+/// Values of `t` where the ray meets the unit superellipsoid, in increasing order. The part of
+/// the ray inside the cube around it is walked in steps and each change of sign is bisected.
+fn superellipsoid_roots(origin: &Point3<f32>, direction: &Vector3<f32>, vertical: f32, horizontal: f32) -> Vec<f32> {
+    let cube = Aabb::from_center(&Point3::origin(), &Vector3::repeat(1.0));
+    let Some((start, end)) = cube.ray_range(origin, direction) else {
+        return Vec::new();
+    };
+
+    let inside = |t: f32| superellipsoid_value(&(origin + direction * t), vertical, horizontal) < 0.0;
+    let step = (end - start) / SUPERELLIPSOID_STEPS as f32;
+    let mut roots = Vec::new();
+    let mut was_inside = false;
+
+    // The surface stays within the cube, so the walk starts and ends outside.
+    for i in 1..=SUPERELLIPSOID_STEPS {
+        let t = if i == SUPERELLIPSOID_STEPS { end } else { start + step * i as f32 };
+        let is_inside = i != SUPERELLIPSOID_STEPS && inside(t);
+
+        if is_inside != was_inside {
+            let (mut low, mut high) = (t - step, t);
+
+            for _ in 0..BISECTION_ITERATIONS {
+                let middle = 0.5 * (low + high);
+
+                if inside(middle) == was_inside {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+
+            roots.push(0.5 * (low + high));
+            was_inside = is_inside;
+        }
+    }
+
+    roots
+}
+
+
+/// Two unit vectors spanning the plane with `normal`, along the world axes for axis aligned planes.
+#[cfg(test)]
+mod primitive_tests {
+    use super::*;
+
+
+    #[test]
+    fn sphere_and_box_spans() {
+        let sphere = Primitive::new(PrimitiveKind::Sphere { center: Point3::new(0.0, 0.0, 5.0), radius: 1.0 }, 0);
+        let span = sphere.ray_span(&Point3::origin(), &Vector3::z()).unwrap();
+        assert_eq!((4.0, 6.0), (span.start, span.end));
+        assert_eq!(-Vector3::z(), sphere.normal(&Point3::new(0.0, 0.0, 4.0)).into_inner());
+
+        let cube = Primitive::new(PrimitiveKind::Box { min: Point3::new(-1.0, -1.0, 2.0), max: Point3::new(1.0, 1.0, 3.0) }, 0);
+        let span = cube.ray_span(&Point3::new(0.5, 0.0, 0.0), &Vector3::z()).unwrap();
+        assert_eq!((2.0, 3.0), (span.start, span.end));
+        assert_eq!(Vector3::z(), cube.normal(&Point3::new(0.5, 0.0, 3.0)).into_inner());
+        assert!(cube.ray_span(&Point3::new(1.5, 0.0, 0.0), &Vector3::z()).is_none());
+    }
+
+
+    #[test]
+    fn flat_surfaces_show_the_side_facing_the_ray() {
+        let ground = Primitive::ground(-1.0, 0);
+        let span = ground.ray_span(&Point3::origin(), &Vector3::new(0.0, -1.0, 1.0)).unwrap();
+
+        assert_eq!((1.0, 1.0), (span.start, span.end));
+        assert_eq!(Surface::Outer, span.entry);
+        assert_eq!(Surface::Inner, ground.ray_span(&Point3::new(0.0, -2.0, 0.0), &Vector3::y()).unwrap().entry);
+        assert!(ground.ray_span(&Point3::origin(), &Vector3::x()).is_none());
+        assert!(ground.bounds().is_none());
+
+        let tile = Primitive::new(PrimitiveKind::Plane { point: Point3::new(0.0, -1.0, 0.0), normal: Vector3::y(), half_size: Some(1.0) }, 0);
+        assert!(tile.ray_span(&Point3::new(0.5, 0.0, 0.5), &-Vector3::y()).is_some());
+        assert!(tile.ray_span(&Point3::new(1.5, 0.0, 0.5), &-Vector3::y()).is_none());
+        assert_eq!(Aabb { min: Point3::new(-1.0, -1.0, -1.0), max: Point3::new(1.0, -1.0, 1.0) }, tile.bounds().unwrap());
+
+        let disc = Primitive::new(PrimitiveKind::Disc { center: Point3::new(0.0, 0.0, 3.0), normal: -Vector3::z(), radius: 2.0 }, 0);
+        assert_eq!(3.0, disc.ray_span(&Point3::new(1.0, 1.0, 0.0), &Vector3::z()).unwrap().start);
+        assert!(disc.ray_span(&Point3::new(2.0, 1.0, 0.0), &Vector3::z()).is_none());
+        assert_eq!(Aabb { min: Point3::new(-2.0, -2.0, 3.0), max: Point3::new(2.0, 2.0, 3.0) }, disc.bounds().unwrap());
+    }
+
+
+    #[test]
+    fn torus_has_two_spans_through_the_hole() {
+        let torus = Primitive::new(PrimitiveKind::Torus { center: Point3::new(0.0, 0.0, 5.0), axis: Vector3::y(), major_radius: 2.0, minor_radius: 0.5 }, 0);
+        let spans = torus.ray_spans(&Point3::origin(), &Vector3::z());
+
+        assert_eq!(2, spans.len());
+        for (expected, span) in [(2.5, 3.5), (6.5, 7.5)].iter().zip(&spans) {
+            assert!((expected.0 - span.start).abs() < 1e-4 && (expected.1 - span.end).abs() < 1e-4);
+        }
+        assert_eq!(spans[0], torus.ray_span(&Point3::origin(), &Vector3::z()).unwrap());
+
+        let normal = torus.normal(&Point3::new(0.0, 0.0, 2.5));
+        assert!((normal.into_inner() + Vector3::z()).norm() < 1e-6);
+
+        assert!(torus.ray_spans(&Point3::new(0.0, 1.0, 0.0), &Vector3::z()).is_empty());
+        assert_eq!(Aabb { min: Point3::new(-2.5, -0.5, 2.5), max: Point3::new(2.5, 0.5, 7.5) }, torus.bounds().unwrap());
+    }
+
+
+    #[test]
+    fn superellipsoid_between_ellipsoid_and_box() {
+        let center = Point3::new(0.0, 0.0, 5.0);
+        let diagonal = Vector3::new(1.0, 1.0, 0.0).normalize();
+        let origin = Point3::new(0.0, 0.0, 5.0) - 3.0 * diagonal;
+
+        // Along the diagonal the surface moves out from the sphere towards the corner of the cube.
+        let entry = |exponent: f32| {
+            let shape = Primitive::new(PrimitiveKind::Superellipsoid { center, radii: Vector3::repeat(1.0), vertical: exponent, horizontal: exponent }, 0);
+            3.0 - shape.ray_span(&origin, &diagonal).unwrap().start
+        };
+
+        assert!((entry(1.0) - 1.0).abs() < 1e-3);
+        assert!((entry(0.25) - 2.0_f32.sqrt() * 0.5_f32.powf(0.125)).abs() < 1e-3);
+
+        let rounded = Primitive::new(PrimitiveKind::Superellipsoid { center, radii: Vector3::new(1.0, 2.0, 1.0), vertical: 0.5, horizontal: 0.5 }, 0);
+        let span = rounded.ray_span(&Point3::new(0.0, 0.0, 0.0), &Vector3::z()).unwrap();
+        assert!((span.start - 4.0).abs() < 1e-4 && (span.end - 6.0).abs() < 1e-4);
+        assert!((rounded.normal(&Point3::new(0.0, 0.0, 4.0)).into_inner() + Vector3::z()).norm() < 1e-4);
+        assert!(rounded.ray_span(&Point3::new(1.5, 0.0, 0.0), &Vector3::z()).is_none());
+    }
+}
@@ -0,0 +1,408 @@
+//! Implicit surfaces given by signed distance functions, built from a few shapes and
+//! combinators and rendered by sphere tracing.
+
+extern crate nalgebra as na;
+
+use std::fmt;
+use std::str::FromStr;
+
+use na::{Point3, UnitVector3, Vector2, Vector3};
+
+use super::Aabb;
+
+
+/// Distance below which sphere tracing takes fixed steps, also the smallest detail resolved.
+const HIT_DISTANCE: f32 = 1e-3;
+
+const MAX_STEPS: usize = 512;
+
+const BISECTION_ITERATIONS: usize = 16;
+
+/// Deepest nesting of parsed expressions, keeping the recursion off the end of the stack.
+const MAX_DEPTH: usize = 64;
+
+
+/// Signed distance function, negative inside the surface.
+#[derive(PartialEq, Clone, Debug)]
+pub enum SdfNode {
+    Sphere { center: Point3<f32>, radius: f32 },
+    Box { center: Point3<f32>, half_size: Vector3<f32> },
+    /// Ring around the y axis through `center`.
+    Torus { center: Point3<f32>, major_radius: f32, minor_radius: f32 },
+    /// Union rounded over a distance of about `smoothness`.
+    SmoothUnion { left: Box<SdfNode>, right: Box<SdfNode>, smoothness: f32 },
+    /// Interpolation between the two shapes, `amount` 0 gives the left one.
+    Blend { left: Box<SdfNode>, right: Box<SdfNode>, amount: f32 },
+    /// Shape turned around the y axis by `rate` radians per unit of height.
+    Twist { child: Box<SdfNode>, rate: f32 },
+    /// Surface pushed in and out by a sine pattern.
+    Displace { child: Box<SdfNode>, amplitude: f32, frequency: f32 },
+}
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct ParseSdfError(pub String);
+
+
+impl SdfNode {
+    pub fn smooth_union(left: SdfNode, right: SdfNode, smoothness: f32) -> SdfNode {
+        SdfNode::SmoothUnion { left: Box::new(left), right: Box::new(right), smoothness }
+    }
+
+
+    pub fn blend(left: SdfNode, right: SdfNode, amount: f32) -> SdfNode {
+        SdfNode::Blend { left: Box::new(left), right: Box::new(right), amount }
+    }
+
+
+    pub fn twist(child: SdfNode, rate: f32) -> SdfNode {
+        SdfNode::Twist { child: Box::new(child), rate }
+    }
+
+
+    pub fn displace(child: SdfNode, amplitude: f32, frequency: f32) -> SdfNode {
+        SdfNode::Displace { child: Box::new(child), amplitude, frequency }
+    }
+
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SdfNode::Sphere { .. } => "sphere",
+            SdfNode::Box { .. } => "box",
+            SdfNode::Torus { .. } => "torus",
+            SdfNode::SmoothUnion { .. } => "smooth_union",
+            SdfNode::Blend { .. } => "blend",
+            SdfNode::Twist { .. } => "twist",
+            SdfNode::Displace { .. } => "displace",
+        }
+    }
+
+
+    pub fn distance(&self, point: &Point3<f32>) -> f32 {
+        match self {
+            SdfNode::Sphere { center, radius } => (point - center).norm() - radius,
+            SdfNode::Box { center, half_size } => {
+                let outside = (point - center).abs() - half_size;
+
+                outside.sup(&Vector3::zeros()).norm() + outside.max().min(0.0)
+            }
+            SdfNode::Torus { center, major_radius, minor_radius } => {
+                let offset = point - center;
+
+                Vector2::new(offset.xz().norm() - major_radius, offset.y).norm() - minor_radius
+            }
+            SdfNode::SmoothUnion { left, right, smoothness } => {
+                let (left, right) = (left.distance(point), right.distance(point));
+                let overlap = (smoothness - (left - right).abs()).max(0.0) / smoothness.max(f32::EPSILON);
+
+                left.min(right) - overlap * overlap * smoothness * 0.25
+            }
+            SdfNode::Blend { left, right, amount } =>
+                (1.0 - amount) * left.distance(point) + amount * right.distance(point),
+            SdfNode::Twist { child, rate } => {
+                let (sin, cos) = (-rate * point.y).sin_cos();
+
+                child.distance(&Point3::new(cos * point.x - sin * point.z, point.y, sin * point.x + cos * point.z))
+            }
+            SdfNode::Displace { child, amplitude, frequency } => {
+                let wave = point.coords.map(|coordinate| (frequency * coordinate).sin());
+
+                child.distance(point) + amplitude * wave.x * wave.y * wave.z
+            }
+        }
+    }
+
+
+    /// Box holding the inside of the surface.
+    pub fn bounds(&self) -> Aabb {
+        match self {
+            SdfNode::Sphere { center, radius } => Aabb::from_center(center, &Vector3::repeat(radius.abs())),
+            SdfNode::Box { center, half_size } => Aabb::from_center(center, &half_size.abs()),
+            SdfNode::Torus { center, major_radius, minor_radius } => {
+                let radius = major_radius.abs() + minor_radius.abs();
+
+                Aabb::from_center(center, &Vector3::new(radius, minor_radius.abs(), radius))
+            }
+            // The smooth minimum is at most a quarter of the smoothness below the plain one.
+            SdfNode::SmoothUnion { left, right, smoothness } => left.bounds().union(&right.bounds()).expanded(0.25 * smoothness.abs()),
+            // Both distances are positive outside both shapes and so is their interpolation.
+            SdfNode::Blend { left, right, .. } => left.bounds().union(&right.bounds()),
+            SdfNode::Twist { child, .. } => {
+                let bounds = child.bounds();
+                let radius = radial_extent(&bounds);
+
+                Aabb { min: Point3::new(-radius, bounds.min.y, -radius), max: Point3::new(radius, bounds.max.y, radius) }
+            }
+            SdfNode::Displace { child, amplitude, .. } => child.bounds().expanded(amplitude.abs()),
+        }
+    }
+
+
+    /// Bound on how fast the distance changes, steps are shortened by it where the
+    /// combinators make the distance inexact.
+    pub fn lipschitz(&self) -> f32 {
+        match self {
+            SdfNode::Sphere { .. } | SdfNode::Box { .. } | SdfNode::Torus { .. } => 1.0,
+            SdfNode::SmoothUnion { left, right, .. } | SdfNode::Blend { left, right, .. } => left.lipschitz().max(right.lipschitz()),
+            SdfNode::Twist { child, rate } => child.lipschitz() * (1.0 + rate.abs() * radial_extent(&child.bounds())),
+            SdfNode::Displace { child, amplitude, frequency } => child.lipschitz() + (amplitude * frequency).abs() * 3.0_f32.sqrt(),
+        }
+    }
+
+
+    /// Values of `t` where the ray `origin + t * direction` enters and leaves the surface, in
+    /// increasing order. Every step goes as far as the distance allows, at least `HIT_DISTANCE`,
+    /// and a change of sign between two steps is bisected.
+    pub fn ray_roots(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Vec<f32> {
+        let length = direction.norm();
+        let Some((start, end)) = self.bounds().ray_range(origin, direction).filter(|_| length > 0.0) else {
+            return Vec::new();
+        };
+
+        // Marching along a unit direction keeps the distance and the step in the same units.
+        let unit = direction / length;
+        let (start, end) = (start * length, end * length);
+        let lipschitz = self.lipschitz();
+        let distance = |s: f32| self.distance(&(origin + unit * s)) / lipschitz;
+
+        let mut roots = Vec::new();
+        let mut previous = (start, distance(start));
+
+        for _ in 0..MAX_STEPS {
+            let s = previous.0 + previous.1.abs().max(HIT_DISTANCE);
+
+            if s > end {
+                break;
+            }
+
+            let current = (s, distance(s));
+
+            if (current.1 < 0.0) != (previous.1 < 0.0) {
+                let (mut low, mut high) = (previous.0, current.0);
+
+                for _ in 0..BISECTION_ITERATIONS {
+                    let middle = 0.5 * (low + high);
+
+                    if (distance(middle) < 0.0) == (previous.1 < 0.0) {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+
+                roots.push(0.5 * (low + high) / length);
+            }
+
+            previous = current;
+        }
+
+        // Stopped inside, which only happens when the steps run out.
+        if roots.len() % 2 == 1 {
+            roots.push(end / length);
+        }
+
+        roots
+    }
+
+
+    /// Outward normal from the gradient of the distance.
+    pub fn normal(&self, point: &Point3<f32>) -> UnitVector3<f32> {
+        let gradient = Vector3::from_fn(|i, _| {
+            let mut offset = Vector3::zeros();
+            offset[i] = HIT_DISTANCE;
+
+            self.distance(&(point + offset)) - self.distance(&(point - offset))
+        });
+
+        UnitVector3::try_new(gradient, 0.0).unwrap_or(Vector3::y_axis())
+    }
+}
+
+
+/// Largest distance of the box from the y axis.
+fn radial_extent(bounds: &Aabb) -> f32 {
+    let x = bounds.min.x.abs().max(bounds.max.x.abs());
+    let z = bounds.min.z.abs().max(bounds.max.z.abs());
+
+    x.hypot(z)
+}
+
+
+/// Written as the expression `FromStr` reads.
+impl fmt::Display for SdfNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let point = |point: &Point3<f32>| format!("{}, {}, {}", point.x, point.y, point.z);
+
+        match self {
+            SdfNode::Sphere { center, radius } => write!(f, "sphere({}, {})", point(center), radius),
+            SdfNode::Box { center, half_size } => write!(f, "box({}, {})", point(center), point(&Point3::from(*half_size))),
+            SdfNode::Torus { center, major_radius, minor_radius } => write!(f, "torus({}, {}, {})", point(center), major_radius, minor_radius),
+            SdfNode::SmoothUnion { left, right, smoothness } => write!(f, "smooth_union({}, {}, {})", smoothness, left, right),
+            SdfNode::Blend { left, right, amount } => write!(f, "blend({}, {}, {})", amount, left, right),
+            SdfNode::Twist { child, rate } => write!(f, "twist({}, {})", rate, child),
+            SdfNode::Displace { child, amplitude, frequency } => write!(f, "displace({}, {}, {})", amplitude, frequency, child),
+        }
+    }
+}
+
+
+/// Parses expressions like `smooth_union(0.5, sphere(0, 0, 0, 1), twist(1, box(0, 0, 0, 1, 0.5, 1)))`:
+/// `sphere(x, y, z, radius)`, `box(x, y, z, half_x, half_y, half_z)`, `torus(x, y, z, major, minor)`,
+/// `smooth_union(smoothness, a, b)`, `blend(amount, a, b)`, `twist(rate, a)` and
+/// `displace(amplitude, frequency, a)`.
+impl FromStr for SdfNode {
+    type Err = ParseSdfError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = SdfParser { text: s.trim(), depth: 0 };
+        let node = parser.node()?;
+
+        if !parser.text.is_empty() {
+            return Err(ParseSdfError(format!("unexpected \"{}\"", parser.text)));
+        }
+
+        Ok(node)
+    }
+}
+
+
+impl fmt::Display for ParseSdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+
+/// Recursive descent parser of distance function expressions.
+struct SdfParser<'a> {
+    text: &'a str,
+    depth: usize,
+}
+
+
+impl<'a> SdfParser<'a> {
+    fn node(&mut self) -> Result<SdfNode, ParseSdfError> {
+        if self.depth == MAX_DEPTH {
+            return Err(ParseSdfError(format!("expression nested deeper than {} levels", MAX_DEPTH)));
+        }
+
+        let end = self.text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(self.text.len());
+        let (word, rest) = self.text.split_at(end);
+        self.text = rest.trim_start();
+
+        // Numbers come first, then the shapes combined.
+        self.expect('(')?;
+        let mut numbers = Vec::new();
+        let mut children = Vec::new();
+
+        loop {
+            if self.text.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c)) {
+                let end = self.text.find([',', ')']).unwrap_or(self.text.len());
+                let (number, rest) = self.text.split_at(end);
+                numbers.push(number.trim().parse::<f32>().map_err(|_| ParseSdfError(format!("invalid number \"{}\"", number.trim())))?);
+                self.text = rest;
+            } else {
+                self.depth += 1;
+                children.push(self.node()?);
+                self.depth -= 1;
+            }
+
+            if self.text.starts_with(')') {
+                break;
+            }
+            self.expect(',')?;
+        }
+        self.expect(')')?;
+
+        let point = |i: usize| Point3::new(numbers[i], numbers[i + 1], numbers[i + 2]);
+        let mut children = children.into_iter();
+
+        let node = match (word, numbers.len(), children.len()) {
+            ("sphere", 4, 0) => SdfNode::Sphere { center: point(0), radius: numbers[3] },
+            ("box", 6, 0) => SdfNode::Box { center: point(0), half_size: point(3).coords },
+            ("torus", 5, 0) => SdfNode::Torus { center: point(0), major_radius: numbers[3], minor_radius: numbers[4] },
+            ("smooth_union", 1, 2) => SdfNode::smooth_union(children.next().unwrap(), children.next().unwrap(), numbers[0]),
+            ("blend", 1, 2) => SdfNode::blend(children.next().unwrap(), children.next().unwrap(), numbers[0]),
+            ("twist", 1, 1) => SdfNode::twist(children.next().unwrap(), numbers[0]),
+            ("displace", 2, 1) => SdfNode::displace(children.next().unwrap(), numbers[0], numbers[1]),
+            ("sphere" | "box" | "torus" | "smooth_union" | "blend" | "twist" | "displace", _, _) =>
+                return Err(ParseSdfError(format!("wrong arguments of \"{}\"", word))),
+            _ => return Err(ParseSdfError(format!("unknown distance function \"{}\"", word))),
+        };
+
+        Ok(node)
+    }
+
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseSdfError> {
+        match self.text.strip_prefix(expected) {
+            Some(rest) => {
+                self.text = rest.trim_start();
+                Ok(())
+            }
+            None => Err(ParseSdfError(format!("expected \"{}\" at \"{}\"", expected, self.text)))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod sdf_tests {
+    use super::*;
+
+
+    #[test]
+    fn sphere_tracing_finds_the_surface() {
+        let sphere = SdfNode::Sphere { center: Point3::new(0.0, 0.0, 5.0), radius: 1.0 };
+        let roots = sphere.ray_roots(&Point3::origin(), &Vector3::new(0.0, 0.0, 2.0));
+
+        assert_eq!(2, roots.len());
+        assert!((roots[0] - 2.0).abs() < 1e-4 && (roots[1] - 3.0).abs() < 1e-4);
+        assert!((sphere.normal(&Point3::new(0.0, 0.0, 4.0)).into_inner() + Vector3::z()).norm() < 1e-3);
+        assert!(sphere.ray_roots(&Point3::new(1.5, 0.0, 0.0), &Vector3::z()).is_empty());
+    }
+
+
+    #[test]
+    fn combinators() {
+        let left = SdfNode::Sphere { center: Point3::new(-1.0, 0.0, 0.0), radius: 1.0 };
+        let right = SdfNode::Sphere { center: Point3::new(1.0, 0.0, 0.0), radius: 1.0 };
+
+        // The spheres touch at the origin, the smooth union fills in around it.
+        let union = SdfNode::smooth_union(left.clone(), right.clone(), 0.5);
+        assert!(union.distance(&Point3::new(0.0, 0.2, 0.0)) < 0.0);
+        assert!(left.distance(&Point3::new(0.0, 0.2, 0.0)).min(right.distance(&Point3::new(0.0, 0.2, 0.0))) > 0.0);
+        assert!(union.bounds().contains(&Point3::new(2.1, 0.0, 0.0)));
+
+        let small = SdfNode::Sphere { center: Point3::origin(), radius: 1.0 };
+        let large = SdfNode::Sphere { center: Point3::origin(), radius: 2.0 };
+        assert!(SdfNode::blend(small, large, 0.5).distance(&Point3::new(1.5, 0.0, 0.0)).abs() < 1e-6);
+
+        // A quarter turn at height 1 moves the long side of the box from x to z.
+        let bar = SdfNode::Box { center: Point3::origin(), half_size: Vector3::new(2.0, 1.0, 0.25) };
+        let twisted = SdfNode::twist(bar, std::f32::consts::FRAC_PI_2);
+        assert!(twisted.distance(&Point3::new(1.5, 0.0, 0.0)) < 0.0);
+        assert!(twisted.distance(&Point3::new(0.0, 1.0, 1.5)) < 1e-5);
+        assert!(twisted.distance(&Point3::new(1.5, 1.0, 0.0)) > 0.0);
+
+        let bumpy = SdfNode::displace(SdfNode::Sphere { center: Point3::origin(), radius: 1.0 }, 0.1, 10.0);
+        assert!(bumpy.lipschitz() > 1.0);
+        let roots = bumpy.ray_roots(&Point3::new(0.3, 0.2, -5.0), &Vector3::z());
+        assert!(roots.iter().all(|t| bumpy.distance(&Point3::new(0.3, 0.2, t - 5.0)).abs() < 1e-3));
+    }
+
+
+    #[test]
+    fn parse_and_display() {
+        let text = "smooth_union(0.5, sphere(0, 0, 0, 1), twist(1, displace(0.1, 4, box(0, 1, 0, 1, 0.5, 1))))";
+        let node = text.parse::<SdfNode>().unwrap();
+
+        assert_eq!(text, node.to_string());
+        assert_eq!(node, " smooth_union( 0.5,sphere(0,0,0,1) , twist(1, displace(0.1, 4, box(0, 1, 0, 1, 0.5, 1)))) ".parse().unwrap());
+        assert_eq!("wrong arguments of \"sphere\"", "sphere(1, 2)".parse::<SdfNode>().unwrap_err().to_string());
+        assert_eq!("unknown distance function \"cone\"", "cone(1)".parse::<SdfNode>().unwrap_err().to_string());
+
+        let deep = format!("{}sphere(0, 0, 0, 1){}", "twist(1, ".repeat(100_000), ")".repeat(100_000));
+        assert_eq!("expression nested deeper than 64 levels", deep.parse::<SdfNode>().unwrap_err().to_string());
+    }
+}
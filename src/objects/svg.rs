@@ -0,0 +1,383 @@
+//! Vector drawing of the view: outlines of the objects, latitude and longitude lines and
+//! cross-sections by clipping planes, written as SVG in image coordinates.
+
+extern crate nalgebra as na;
+
+use std::f32::consts::PI;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use na::{Matrix2, Point2, Point3, Vector2};
+
+use super::{Camera, ClipPlane, Ellipse};
+use super::csg::CsgNode;
+use super::curve::EllipseCurve;
+
+
+/// Visibility samples along every curve.
+const SAMPLES: usize = 360;
+const EDGE_BISECTIONS: usize = 24;
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct SvgOptions {
+    /// Embeds the rendered image below the curves.
+    pub raster: bool,
+    pub outlines: bool,
+    /// Number of latitude and longitude lines drawn on every object.
+    pub latitudes: u32,
+    pub longitudes: u32,
+    /// Sections by the clipping planes of every object and by the near clipping plane.
+    pub cross_sections: bool,
+    /// Leaves out the parts of curves hidden behind objects.
+    pub hidden_line_removal: bool,
+    /// Width of the outlines in pixels, other curves are half as wide.
+    pub stroke_width: f32,
+}
+
+
+pub struct SvgDocument {
+    width: u32,
+    height: u32,
+    body: String,
+}
+
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            raster: false,
+            outlines: true,
+            latitudes: 0,
+            longitudes: 0,
+            cross_sections: true,
+            hidden_line_removal: true,
+            stroke_width: 1.5,
+        }
+    }
+}
+
+
+impl SvgDocument {
+    pub fn new(width: u32, height: u32) -> SvgDocument {
+        SvgDocument { width, height, body: String::new() }
+    }
+
+
+    /// Adds a PNG image covering the whole document.
+    pub fn add_image(&mut self, png: &[u8]) {
+        let _ = writeln!(
+            self.body,
+            "<image x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" xlink:href=\"data:image/png;base64,{}\"/>",
+            self.width, self.height, base64(png)
+        );
+    }
+
+
+    /// Adds a group of stroked, unfilled elements.
+    pub fn add_group(&mut self, id: &str, stroke: &str, stroke_width: f32, elements: &[String]) {
+        if elements.is_empty() {
+            return;
+        }
+
+        let _ = writeln!(
+            self.body,
+            "<g id=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\">",
+            id, stroke, stroke_width
+        );
+
+        for element in elements {
+            let _ = writeln!(self.body, "  {}", element);
+        }
+
+        self.body.push_str("</g>\n");
+    }
+
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+
+impl std::fmt::Display for SvgDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            f,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">",
+            self.width, self.height
+        )?;
+        write!(f, "{}", self.body)?;
+        writeln!(f, "</svg>")
+    }
+}
+
+
+/// Draws the curves selected in `options` on the objects of every shape as seen by `camera`
+/// on an image of `width` by `height` pixels. `visible(shape, index, point)` tells whether a
+/// point on object `index` of `shape` is seen, `near_plane` is the near clipping plane when
+/// it cuts the objects.
+pub fn draw_curves(
+    document: &mut SvgDocument,
+    ellipses: &[Ellipse],
+    shapes: &[CsgNode],
+    camera: &Camera,
+    options: &SvgOptions,
+    near_plane: Option<ClipPlane>,
+    visible: impl Fn(&CsgNode, usize, &Point3<f32>) -> bool
+) {
+    let project = |point: &Point3<f32>| {
+        let (x, y) = camera.pixel_position(point, document.width, document.height);
+        Point2::new(x, y)
+    };
+
+    let mut grid = Vec::new();
+    let mut sections = Vec::new();
+    let mut outlines = Vec::new();
+
+    for (shape, index) in shapes.iter().flat_map(|shape| shape.objects().into_iter().map(move |index| (shape, index))) {
+        let ellipse = &ellipses[index];
+        let visible = |point: &Point3<f32>| visible(shape, index, point);
+
+        for k in 1..=options.latitudes {
+            let theta = PI * k as f32 / (options.latitudes + 1) as f32;
+            let curve = |phi: f32| ellipse.surface_point(theta, phi);
+
+            for run in visible_runs(&curve, &visible, (0.0, 2.0 * PI), true) {
+                grid.push(polyline_path(&curve, run, 2.0 * PI, &project));
+            }
+        }
+
+        for k in 0..options.longitudes {
+            let phi = 2.0 * PI * k as f32 / options.longitudes as f32;
+            let curve = |theta: f32| ellipse.surface_point(theta, phi);
+
+            for run in visible_runs(&curve, &visible, (0.0, PI), false) {
+                grid.push(polyline_path(&curve, run, PI, &project));
+            }
+        }
+
+        if options.cross_sections {
+            for plane in ellipse.clip_planes.iter().chain(near_plane.as_ref()) {
+                if let Some(section) = ellipse.cross_section(plane) {
+                    sections.extend(ellipse_elements(&section, &visible, &project));
+                }
+            }
+        }
+
+        if options.outlines {
+            if let Some(outline) = ellipse.outline() {
+                outlines.extend(ellipse_elements(&outline, &visible, &project));
+            }
+        }
+    }
+
+    let width = options.stroke_width;
+    document.add_group("grid", "#606060", 0.5 * width, &grid);
+    document.add_group("cross-sections", "#000000", 0.5 * width, &sections);
+    document.add_group("outlines", "#000000", width, &outlines);
+}
+
+
+/// Visible parts of `curve` between the parameters in `range` as parameter intervals.
+/// The ends of the parts are located by bisection between the samples.
+fn visible_runs(
+    curve: &impl Fn(f32) -> Point3<f32>,
+    visible: &impl Fn(&Point3<f32>) -> bool,
+    range: (f32, f32),
+    closed: bool
+) -> Vec<(f32, f32)> {
+    let step = (range.1 - range.0) / SAMPLES as f32;
+    let flags: Vec<bool> = (0..=SAMPLES).map(|i| visible(&curve(range.0 + step * i as f32))).collect();
+
+    if flags.iter().all(|flag| *flag) {
+        return vec![range];
+    }
+
+    // Closed curves are walked from a hidden sample, so no part is split at the seam.
+    let first = if closed { flags.iter().position(|flag| !flag).unwrap_or(0) } else { 0 };
+    let parameter = |i: usize| range.0 + step * i as f32;
+    let flag = |i: usize| flags[if closed { i % SAMPLES } else { i }];
+
+    let boundary = |i: usize| {
+        let (mut low, mut high) = (parameter(i), parameter(i + 1));
+
+        for _ in 0..EDGE_BISECTIONS {
+            let middle = 0.5 * (low + high);
+
+            if visible(&curve(middle)) == flag(i) {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+
+        0.5 * (low + high)
+    };
+
+    let mut runs = Vec::new();
+    let mut start = flag(first).then(|| parameter(first));
+
+    for i in first..first + SAMPLES {
+        match (flag(i), flag(i + 1)) {
+            (false, true) => start = Some(boundary(i)),
+            (true, false) => runs.extend(start.take().map(|start| (start, boundary(i)))),
+            _ => (),
+        }
+    }
+
+    runs.extend(start.map(|start| (start, parameter(first + SAMPLES))));
+
+    runs
+}
+
+
+/// Path through points of `curve` over `run`, sampled as densely as the visibility test.
+fn polyline_path(
+    curve: &impl Fn(f32) -> Point3<f32>,
+    run: (f32, f32),
+    length: f32,
+    project: &impl Fn(&Point3<f32>) -> Point2<f32>
+) -> String {
+    let count = ((run.1 - run.0) / length * SAMPLES as f32).ceil().max(1.0) as usize;
+    let mut path = String::new();
+
+    for i in 0..=count {
+        let point = project(&curve(run.0 + (run.1 - run.0) * i as f32 / count as f32));
+        let _ = write!(path, "{}{:.2} {:.2}", if i == 0 { "M" } else { " L" }, point.x, point.y);
+    }
+
+    format!("<path d=\"{}\"/>", path)
+}
+
+
+/// An `ellipse` element when the whole curve is seen, otherwise a path of elliptical arcs
+/// for every visible part.
+fn ellipse_elements(
+    curve: &EllipseCurve,
+    visible: &impl Fn(&Point3<f32>) -> bool,
+    project: &impl Fn(&Point3<f32>) -> Point2<f32>
+) -> Vec<String> {
+    let center = project(&curve.center);
+    let axes = Matrix2::from_columns(&[
+        project(&(curve.center + curve.axes[0])) - center,
+        project(&(curve.center + curve.axes[1])) - center,
+    ]);
+
+    let (rx, ry, angle) = arc_radii(&axes);
+    let runs = visible_runs(&|t| curve.point(t), visible, (0.0, 2.0 * PI), true);
+
+    if runs == [(0.0, 2.0 * PI)] {
+        return vec![format!(
+            "<ellipse cx=\"{:.2}\" cy=\"{:.2}\" rx=\"{:.2}\" ry=\"{:.2}\" transform=\"rotate({:.3} {:.2} {:.2})\"/>",
+            center.x, center.y, rx, ry, angle, center.x, center.y
+        )];
+    }
+
+    // Increasing parameters turn from the first axis towards the second one.
+    let sweep = if axes.determinant() > 0.0 { 1 } else { 0 };
+    let point = |t: f32| center + axes * Vector2::new(t.cos(), t.sin());
+
+    runs.into_iter()
+        .map(|(start, end)| {
+            // Two arcs of at most half a turn each, so the large arc flag is never needed.
+            let middle = 0.5 * (start + end);
+            let [a, b, c] = [point(start), point(middle), point(end)];
+
+            format!(
+                "<path d=\"M{:.2} {:.2} A{:.2} {:.2} {:.3} 0 {} {:.2} {:.2} A{:.2} {:.2} {:.3} 0 {} {:.2} {:.2}\"/>",
+                a.x, a.y, rx, ry, angle, sweep, b.x, b.y, rx, ry, angle, sweep, c.x, c.y
+            )
+        })
+        .collect()
+}
+
+
+/// Radii and rotation in degrees of the ellipse spanned by the columns of `axes`.
+fn arc_radii(axes: &Matrix2<f32>) -> (f32, f32, f32) {
+    let svd = axes.svd(true, false);
+    let u = svd.u.unwrap_or(Matrix2::identity());
+
+    (svd.singular_values[0], svd.singular_values[1], u[(1, 0)].atan2(u[(0, 0)]).to_degrees())
+}
+
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let value = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[(value >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+
+    result
+}
+
+
+#[cfg(test)]
+mod svg_tests {
+    use super::*;
+
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9v", base64(b"foo"));
+        assert_eq!("Zm9vYmFy", base64(b"foobar"));
+    }
+
+
+    #[test]
+    fn runs_of_closed_curve_join_across_seam() {
+        let curve = |t: f32| Point3::new(t.cos(), t.sin(), 0.0);
+        let runs = visible_runs(&curve, &|p: &Point3<f32>| p.x > 0.5, (0.0, 2.0 * PI), true);
+
+        assert_eq!(1, runs.len());
+
+        let (start, end) = runs[0];
+        assert!((start - 5.0 / 3.0 * PI).abs() < 1e-4);
+        assert!((end - 7.0 / 3.0 * PI).abs() < 1e-4);
+    }
+
+
+    #[test]
+    fn runs_of_open_curve() {
+        let curve = |t: f32| Point3::new(t, 0.0, 0.0);
+        let runs = visible_runs(&curve, &|p: &Point3<f32>| (p.x - 0.5).abs() > 0.25, (0.0, 1.0), false);
+
+        assert_eq!(2, runs.len());
+        assert_eq!(0.0, runs[0].0);
+        assert!((runs[0].1 - 0.25).abs() < 1e-4);
+        assert!((runs[1].0 - 0.75).abs() < 1e-4);
+        assert_eq!(1.0, runs[1].1);
+    }
+
+
+    #[test]
+    fn outline_of_sphere_is_circle() {
+        let sphere = Ellipse::new(0.25, 0.25, 0.25, &Point3::new(1.0, 0.0, 0.0), 0);
+        let camera = Camera::new(10.0, 10.0);
+        let mut document = SvgDocument::new(100, 100);
+
+        let options = SvgOptions { cross_sections: false, ..SvgOptions::default() };
+        draw_curves(&mut document, &[sphere], &[CsgNode::Object(0)], &camera, &options, None, |_, _, _| true);
+
+        let text = document.to_string();
+        assert!(text.contains("<ellipse cx=\"60.00\" cy=\"50.00\" rx=\"20.00\" ry=\"20.00\""), "{}", text);
+    }
+}
@@ -0,0 +1,465 @@
+//! Fields of symmetric 3×3 tensors such as diffusion or stress, read from text files with one
+//! sample per line or from NumPy `.npy` arrays, and shown as one ellipsoid glyph per sample.
+
+extern crate nalgebra as na;
+
+use std::{error, fmt, fs, io};
+use std::path::Path;
+
+use na::{Matrix3, Point3, Vector3};
+
+use super::{Color, Colormap, Ellipse};
+
+
+/// Smallest glyph axis relative to the longest, keeping flat and linear tensors visible.
+const MIN_AXIS_RATIO: f32 = 0.02;
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct TensorSample {
+    pub position: Point3<f32>,
+    pub tensor: Matrix3<f32>,
+}
+
+
+pub struct TensorField {
+    pub samples: Vec<TensorSample>,
+}
+
+
+/// Scalar the glyphs are colored by.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum GlyphColoring {
+    FractionalAnisotropy,
+    Trace,
+    /// Absolute components of the principal eigenvector as red, green and blue, dimmed by
+    /// the fractional anisotropy.
+    PrincipalDirection,
+}
+
+
+/// How the glyphs of a field are built and which samples get one.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct GlyphOptions {
+    /// Length of a glyph semi-axis per unit of eigenvalue.
+    pub scale: f32,
+    pub coloring: GlyphColoring,
+    pub colormap: Colormap,
+    /// Samples with fractional anisotropy outside of the range are left out.
+    pub anisotropy_range: (f32, f32),
+    /// Samples with trace outside of the range are left out, the range also spans the colormap.
+    pub trace_range: (f32, f32),
+}
+
+
+/// Ellipsoid standing for one sample of a tensor field.
+pub struct Glyph {
+    pub ellipse: Ellipse,
+    pub color: Color,
+    /// Index of the sample in `TensorField::samples`.
+    pub sample: usize,
+}
+
+
+#[derive(Debug)]
+pub enum TensorFieldError {
+    Io(io::Error),
+    Parse{line: usize, message: String},
+    Npy(String),
+}
+
+
+impl GlyphColoring {
+    pub const ALL: [GlyphColoring; 3] = [GlyphColoring::FractionalAnisotropy, GlyphColoring::Trace, GlyphColoring::PrincipalDirection];
+
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GlyphColoring::FractionalAnisotropy => "Fractional anisotropy",
+            GlyphColoring::Trace => "Trace",
+            GlyphColoring::PrincipalDirection => "Principal direction",
+        }
+    }
+}
+
+
+impl Default for GlyphOptions {
+    fn default() -> GlyphOptions {
+        GlyphOptions {
+            scale: 1.0,
+            coloring: GlyphColoring::FractionalAnisotropy,
+            colormap: Colormap::Viridis,
+            anisotropy_range: (0.0, 1.0),
+            trace_range: (f32::NEG_INFINITY, f32::INFINITY),
+        }
+    }
+}
+
+
+impl TensorField {
+    /// Loads a field, `.npy` files are recognized by their extension.
+    pub fn load(path: &Path) -> Result<TensorField, TensorFieldError> {
+        let data = fs::read(path)?;
+        let is_npy = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("npy"));
+
+        if is_npy {
+            TensorField::parse_npy(&data)
+        } else {
+            TensorField::parse_text(&String::from_utf8_lossy(&data))
+        }
+    }
+
+
+    /// Reads lines of `x, y, z` followed by the upper triangle `xx, xy, xz, yy, yz, zz` or all
+    /// nine components row by row, separated by commas, semicolons or whitespace. A header
+    /// line and `#` comments are skipped.
+    pub fn parse_text(text: &str) -> Result<TensorField, TensorFieldError> {
+        let mut samples = Vec::new();
+        let mut first_line = true;
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let is_first_line = std::mem::replace(&mut first_line, false);
+
+            let values: Result<Vec<f32>, _> = line.split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+                .filter(|value| !value.is_empty())
+                .map(str::parse::<f32>)
+                .collect();
+
+            let sample = values.ok().and_then(|values| {
+                let position = Point3::new(*values.first()?, *values.get(1)?, *values.get(2)?);
+                tensor_from_components(&values[3..]).map(|tensor| TensorSample { position, tensor })
+            });
+
+            match sample {
+                Some(sample) => samples.push(sample),
+                None if is_first_line => (),
+                None => return Err(TensorFieldError::Parse {
+                    line: line_index + 1,
+                    message: format!("expected a position and 6 or 9 tensor components, found \"{}\"", line)
+                }),
+            }
+        }
+
+        if samples.is_empty() {
+            return Err(TensorFieldError::Parse { line: 1, message: String::from("no tensors found") });
+        }
+
+        Ok(TensorField { samples })
+    }
+
+
+    /// Reads a little endian `f4` or `f8` array in C order shaped like a grid of up to three
+    /// dimensions followed by `(3, 3)` or `(6,)` for the upper triangle. Samples are placed one
+    /// unit apart, the grid centered on the origin.
+    pub fn parse_npy(data: &[u8]) -> Result<TensorField, TensorFieldError> {
+        let error = |message: &str| TensorFieldError::Npy(String::from(message));
+
+        if data.len() < 10 || &data[..6] != b"\x93NUMPY" {
+            return Err(error("not a NumPy array file"));
+        }
+
+        let (header_start, header_length) = match data[6] {
+            1 => (10, u16::from_le_bytes([data[8], data[9]]) as usize),
+            2 | 3 if data.len() >= 12 => (12, u32::from_le_bytes([data[8], data[9], data[10], data[11]]) as usize),
+            _ => return Err(error("unsupported format version")),
+        };
+
+        let body_start = header_start + header_length;
+        let header = data.get(header_start..body_start).ok_or(error("file ends inside the header"))?;
+        let header = String::from_utf8_lossy(header);
+
+        let descr = npy_value(&header, "descr").ok_or(error("missing descr"))?;
+        let value_size = match descr.split(',').next().unwrap_or("").trim().trim_matches(|c| c == '\'' || c == '"') {
+            "<f4" => 4,
+            "<f8" => 8,
+            other => return Err(TensorFieldError::Npy(format!("unsupported data type {}", other))),
+        };
+
+        if npy_value(&header, "fortran_order").is_some_and(|order| order.starts_with("True")) {
+            return Err(error("Fortran ordered arrays are not supported"));
+        }
+
+        let shape = npy_value(&header, "shape").ok_or(error("missing shape"))?;
+        let shape = shape.trim_start_matches('(').split(')').next().unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|dimension| !dimension.is_empty())
+            .map(|dimension| dimension.parse::<usize>().map_err(|_| error("invalid shape")))
+            .collect::<Result<Vec<usize>, TensorFieldError>>()?;
+
+        let (grid, components) = match shape[..] {
+            [ref grid @ .., 3, 3] => (grid, 9),
+            [ref grid @ .., 6] => (grid, 6),
+            _ => return Err(error("the last dimensions must be (3, 3) or (6,)")),
+        };
+
+        if grid.len() > 3 {
+            return Err(error("grids have at most three dimensions"));
+        }
+
+        let count = grid.iter().try_fold(1_usize, |count, dimension| count.checked_mul(*dimension)).ok_or(error("invalid shape"))?;
+        let body = &data[body_start..];
+
+        if count == 0 {
+            return Err(error("no tensors found"));
+        }
+
+        if count.checked_mul(components * value_size).is_none_or(|size| body.len() < size) {
+            return Err(error("file ends before the last tensor"));
+        }
+
+        let values: Vec<f32> = body.chunks_exact(value_size).take(count * components)
+            .map(|bytes| match value_size {
+                4 => f32::from_le_bytes(bytes.try_into().unwrap()),
+                _ => f64::from_le_bytes(bytes.try_into().unwrap()) as f32,
+            })
+            .collect();
+
+        // Grid dimensions padded to three, the last one varying fastest.
+        let mut size = [1; 3];
+        size[..grid.len()].copy_from_slice(grid);
+        let center = Vector3::new(size[0] as f32 - 1.0, size[1] as f32 - 1.0, size[2] as f32 - 1.0) * 0.5;
+
+        let samples = values.chunks_exact(components).enumerate()
+            .map(|(index, components)| {
+                let (i, j, k) = (index / (size[1] * size[2]), index / size[2] % size[1], index % size[2]);
+                TensorSample {
+                    position: Point3::new(i as f32, j as f32, k as f32) - center,
+                    tensor: tensor_from_components(components).unwrap(),
+                }
+            })
+            .collect();
+
+        Ok(TensorField { samples })
+    }
+
+
+    /// Smallest and largest trace among the samples.
+    pub fn trace_range(&self) -> (f32, f32) {
+        self.samples.iter()
+            .map(|sample| sample.tensor.trace())
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), trace| (min.min(trace), max.max(trace)))
+    }
+
+
+    /// Scale making the largest glyph about as long as the spacing of the samples.
+    pub fn suggested_scale(&self) -> f32 {
+        let largest = self.samples.iter()
+            .map(|sample| sample.tensor.symmetric_eigenvalues().amax())
+            .fold(0.0, f32::max);
+
+        if largest > 0.0 { 0.5 * self.spacing() / largest } else { 1.0 }
+    }
+
+
+    /// Options showing every sample with glyphs of `suggested_scale`.
+    pub fn default_options(&self) -> GlyphOptions {
+        GlyphOptions { scale: self.suggested_scale(), trace_range: self.trace_range(), ..GlyphOptions::default() }
+    }
+
+
+    /// Glyphs of the samples passing the thresholds of `options`. Semi-axes point along the
+    /// eigenvectors and are as long as the absolute eigenvalues times the scale.
+    pub fn glyphs(&self, options: &GlyphOptions) -> Vec<Glyph> {
+        let (trace_min, trace_max) = options.trace_range;
+        let (anisotropy_min, anisotropy_max) = options.anisotropy_range;
+
+        self.samples.iter().enumerate()
+            .filter_map(|(index, sample)| {
+                let eigen = sample.tensor.symmetric_eigen();
+                let values = eigen.eigenvalues;
+                let anisotropy = fractional_anisotropy(&values);
+                let trace = values.sum();
+
+                if !(anisotropy_min..=anisotropy_max).contains(&anisotropy) || !(trace_min..=trace_max).contains(&trace) {
+                    return None;
+                }
+
+                let longest = values.amax() * options.scale;
+                if longest <= 0.0 || !longest.is_finite() {
+                    return None;
+                }
+
+                let axes = values.map(|value| (value.abs() * options.scale).max(longest * MIN_AXIS_RATIO));
+                let vectors = eigen.eigenvectors;
+                let shape = vectors * Matrix3::from_diagonal(&axes.map(|axis| 1.0 / (axis * axis))) * vectors.transpose();
+
+                let color = match options.coloring {
+                    GlyphColoring::FractionalAnisotropy => options.colormap.map(anisotropy, 0.0, 1.0),
+                    GlyphColoring::Trace => options.colormap.map(trace, trace_min, trace_max),
+                    GlyphColoring::PrincipalDirection => {
+                        let principal = vectors.column(values.imax()).abs() * anisotropy;
+                        Color::from_srgb_f(principal.x, principal.y, principal.z)
+                    }
+                };
+
+                Ellipse::from_shape_matrix(&sample.position, &shape, 0)
+                    .map(|ellipse| Glyph { ellipse, color, sample: index })
+            })
+            .collect()
+    }
+
+
+    /// Typical distance between neighbouring samples, from the volume, area or length their
+    /// bounding box spans.
+    fn spacing(&self) -> f32 {
+        let first = self.samples[0].position;
+        let (low, high) = self.samples.iter()
+            .fold((first, first), |(low, high), sample| (low.inf(&sample.position), high.sup(&sample.position)));
+
+        let extents: Vec<f32> = (high - low).iter().copied().filter(|extent| *extent > 0.0).collect();
+        let cells = (self.samples.len() as f32).powf(1.0 / extents.len().max(1) as f32) - 1.0;
+
+        if extents.is_empty() || cells <= 0.0 {
+            return 1.0;
+        }
+
+        extents.iter().product::<f32>().powf(1.0 / extents.len() as f32) / cells
+    }
+}
+
+
+/// Fractional anisotropy of a tensor with eigenvalues `values`: 0 for isotropic tensors,
+/// approaching 1 as one eigenvalue dominates.
+pub fn fractional_anisotropy(values: &Vector3<f32>) -> f32 {
+    let norm = values.norm();
+
+    if norm == 0.0 {
+        return 0.0;
+    }
+
+    let mean = values.mean();
+    (1.5_f32).sqrt() * (values - Vector3::repeat(mean)).norm() / norm
+}
+
+
+/// Symmetric tensor from the upper triangle `xx, xy, xz, yy, yz, zz` or nine components row by
+/// row, which are symmetrized.
+fn tensor_from_components(components: &[f32]) -> Option<Matrix3<f32>> {
+    match *components {
+        [xx, xy, xz, yy, yz, zz] => Some(Matrix3::new(
+            xx, xy, xz,
+            xy, yy, yz,
+            xz, yz, zz,
+        )),
+        [_, _, _, _, _, _, _, _, _] => {
+            let full = Matrix3::from_row_slice(components);
+            Some((full + full.transpose()) * 0.5)
+        }
+        _ => None,
+    }
+}
+
+
+/// Text of `key` in the Python dictionary literal of a `.npy` header.
+fn npy_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{}'", key))? + key.len() + 2;
+    let rest = header[start..].trim_start().strip_prefix(':')?;
+
+    Some(rest.trim_start())
+}
+
+
+impl From<io::Error> for TensorFieldError {
+    fn from(err: io::Error) -> TensorFieldError {
+        TensorFieldError::Io(err)
+    }
+}
+
+
+impl fmt::Display for TensorFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TensorFieldError::Io(err) => write!(f, "{}", err),
+            TensorFieldError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            TensorFieldError::Npy(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+
+impl error::Error for TensorFieldError {}
+
+
+#[cfg(test)]
+mod tensor_field_tests {
+    use super::*;
+
+
+    fn npy(shape: &str, values: &[f32]) -> Vec<u8> {
+        let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}", shape);
+        while (10 + header.len() + 1) % 64 != 0 {
+            header.push(' ');
+        }
+        header.push('\n');
+
+        let mut data = b"\x93NUMPY\x01\x00".to_vec();
+        data.extend((header.len() as u16).to_le_bytes());
+        data.extend(header.as_bytes());
+        data.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+        data
+    }
+
+
+    #[test]
+    fn text_with_header_and_both_layouts() {
+        let field = TensorField::parse_text("x,y,z,xx,xy,xz,yy,yz,zz\n0,0,0,3,0,0,1,0,1\n# comment\n1 2 3  1 0 0 0 2 0 0 0 3\n").unwrap();
+
+        assert_eq!(2, field.samples.len());
+        assert_eq!(Point3::new(1.0, 2.0, 3.0), field.samples[1].position);
+        assert_eq!(Matrix3::from_diagonal(&Vector3::new(1.0, 2.0, 3.0)), field.samples[1].tensor);
+        assert_eq!((5.0, 6.0), field.trace_range());
+
+        let error = TensorField::parse_text("0,0,0,1,0,0,1,0,1\n0,0,0,1,0\n").err().unwrap();
+        assert!(error.to_string().starts_with("line 2:"));
+    }
+
+
+    #[test]
+    fn npy_grid() {
+        // A 2 by 1 grid of upper triangles.
+        let field = TensorField::parse_npy(&npy("(2, 1, 6)", &[1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 2.0, 0.5, 0.0, 1.0, 0.0, 1.0])).unwrap();
+
+        assert_eq!(2, field.samples.len());
+        assert_eq!(Point3::new(-0.5, 0.0, 0.0), field.samples[0].position);
+        assert_eq!(Point3::new(0.5, 0.0, 0.0), field.samples[1].position);
+        assert_eq!(0.5, field.samples[1].tensor[(1, 0)]);
+
+        let full = TensorField::parse_npy(&npy("(1, 3, 3)", &[1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 3.0])).unwrap();
+        assert_eq!(3.0, full.samples[0].tensor[(2, 2)]);
+
+        assert!(TensorField::parse_npy(&npy("(2, 4)", &[0.0; 8])).is_err());
+        assert!(TensorField::parse_npy(&npy("(0, 6)", &[])).is_err());
+        assert!(TensorField::parse_npy(&npy("(4294967296, 4294967296, 6)", &[0.0; 6])).is_err());
+    }
+
+
+    #[test]
+    fn glyphs_follow_eigenvectors_and_thresholds() {
+        let field = TensorField { samples: vec![
+            TensorSample { position: Point3::origin(), tensor: Matrix3::from_diagonal(&Vector3::new(1.0, 1.0, 1.0)) },
+            TensorSample { position: Point3::new(2.0, 0.0, 0.0), tensor: Matrix3::from_diagonal(&Vector3::new(0.25, 2.0, 0.25)) },
+        ] };
+
+        assert!(fractional_anisotropy(&Vector3::repeat(1.0)).abs() < 1e-6);
+        assert!((fractional_anisotropy(&Vector3::new(1.0, 0.0, 0.0)) - 1.0).abs() < 1e-6);
+
+        let options = GlyphOptions { scale: 0.5, coloring: GlyphColoring::PrincipalDirection, ..GlyphOptions::default() };
+        let glyphs = field.glyphs(&options);
+        assert_eq!(2, glyphs.len());
+
+        let bounds = glyphs[1].ellipse.bounding_box();
+        assert!((bounds.size() - Vector3::new(0.25, 2.0, 0.25)).norm() < 1e-4);
+        // Principal direction along y.
+        let color = glyphs[1].color;
+        assert!(color.green() > color.red() && color.red() == color.blue());
+
+        let anisotropic = GlyphOptions { anisotropy_range: (0.5, 1.0), ..options };
+        assert_eq!(vec![1], field.glyphs(&anisotropic).iter().map(|glyph| glyph.sample).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,84 @@
+use super::Color;
+
+
+/// Operator compressing linear HDR colors into the displayable [0, 1] range.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ToneMapping {
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+
+impl ToneMapping {
+    pub const ALL: [ToneMapping; 3] = [ToneMapping::Clamp, ToneMapping::Reinhard, ToneMapping::Aces];
+
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMapping::Clamp => "clamp",
+            ToneMapping::Reinhard => "Reinhard",
+            ToneMapping::Aces => "ACES",
+        }
+    }
+
+
+    /// Scales `color` by `2^exposure` and maps the result into [0, 1].
+    pub fn apply(self, color: Color, exposure: f32) -> Color {
+        let color = color * exposure.exp2();
+
+        match self {
+            ToneMapping::Clamp => color.map(|c| c.clamp(0.0, 1.0)),
+            ToneMapping::Reinhard => color.map(|c| {
+                let c = f32::max(c, 0.0);
+                c / (1.0 + c)
+            }),
+            ToneMapping::Aces => color.map(aces_filmic),
+        }
+    }
+}
+
+
+// Narkowicz's fit of the ACES filmic curve.
+fn aces_filmic(x: f32) -> f32 {
+    let x = f32::max(x, 0.0);
+
+    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+}
+
+
+#[cfg(test)]
+mod tone_mapping_tests {
+    use super::*;
+
+
+    #[test]
+    fn clamp_keeps_ldr_colors() {
+        let color = Color::from_rgb_f(0.25, 0.5, 0.75);
+
+        assert_eq!(color, ToneMapping::Clamp.apply(color, 0.0));
+    }
+
+
+    #[test]
+    fn exposure_doubles_intensity_per_stop() {
+        let color = Color::from_rgb_f(0.125, 0.25, 0.0);
+
+        assert_eq!(Color::from_rgb_f(0.25, 0.5, 0.0), ToneMapping::Clamp.apply(color, 1.0));
+    }
+
+
+    #[test]
+    fn operators_stay_in_display_range() {
+        let color = Color::from_rgb_f(100.0, 1.0, 0.0);
+
+        for operator in ToneMapping::ALL {
+            let mapped = operator.apply(color, 0.0);
+
+            assert!(mapped.red() > 250);
+            assert_eq!(0, mapped.blue());
+        }
+
+        assert_eq!(Color::from_rgb_f(0.5, 0.5, 0.0), ToneMapping::Reinhard.apply(Color::from_rgb_f(1.0, 1.0, 0.0), 0.0));
+    }
+}
@@ -0,0 +1,745 @@
+//! Plain text scene format. Every line holds one record, `#` at the start of a word starts a comment:
+//!
+//! ```text
+//! plane point=0,-2,0 normal=0,1,0 material=floor
+//! material name=glass color=#ffffff specular=0.8 shininess=64 opacity=0.1 ior=1.5
+//! ellipsoid a=0.25 b=1 c=0.111 position=0,0,0 rotation=0,0,0 scale=1 material=glass
+//! clip normal=0,0,-1 offset=0
+//! light position=5,5,-10 color=#ffffff intensity=1 radius=0.5
+//! shape difference(0, union(1, 2))
+//! ```
+//!
+//! `material` adds a named material to the library, `texture=path` loads a PNG image as its color.
+//! An ellipsoid with `color=` instead of `material=` gets a plain material named after the color.
+//! `plane` (with `size=` for a square of that half size), `sphere center= radius=`,
+//! `box min= max=`, `disc center= normal= radius=`, `torus center= axis= major= minor=`,
+//! `superellipsoid center= radii= vertical= horizontal=` and `implicit` followed by a distance
+//! function expression such as `smooth_union(0.5, sphere(0, 0, 0, 1), box(1, 0, 0, 1, 1, 1))` add
+//! primitives, which take `material=` or `color=` too, and like ellipsoids `density=` and
+//! `absorption=` for X-ray rendering.
+//! `clip` adds a clipping plane to the last ellipsoid, `light` adds a light to the scene. `shape` adds a CSG tree
+//! referring to ellipsoids by their order in the file, when there is no `shape`
+//! record every ellipsoid is shown on its own.
+
+extern crate nalgebra as na;
+
+use std::{error, fmt, fs, io};
+use std::path::Path;
+use std::str::FromStr;
+
+use na::{Point3, Vector3};
+
+use crate::objects::{ClipPlane, Color, Ellipse, Light, Material, Texture, csg::{CsgNode, CsgOperation}};
+use crate::objects::primitive::{Primitive, PrimitiveKind};
+use crate::objects::sdf::ParseSdfError;
+
+
+/// Color of ellipsoids given neither a material nor a color.
+const DEFAULT_COLOR: &str = "#eff542";
+
+/// Deepest nesting of `shape` expressions, keeping the recursion off the end of the stack.
+const MAX_SHAPE_DEPTH: usize = 64;
+
+
+pub struct SceneDescription {
+    pub ellipses: Vec<Ellipse>,
+    pub primitives: Vec<Primitive>,
+    pub shapes: Vec<CsgNode>,
+    pub lights: Vec<Light>,
+    pub materials: Vec<Material>,
+}
+
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(io::Error),
+    Parse{line: usize, message: String},
+}
+
+
+impl SceneDescription {
+    /// Every object shown on its own.
+    pub fn default_shapes(object_count: usize) -> Vec<CsgNode> {
+        (0..object_count).map(CsgNode::Object).collect()
+    }
+}
+
+
+pub fn save(
+    path: &Path,
+    ellipses: &[Ellipse],
+    primitives: &[Primitive],
+    shapes: &[CsgNode],
+    lights: &[Light],
+    materials: &[Material]
+) -> Result<(), SceneFileError> {
+    fs::write(path, to_string(ellipses, primitives, shapes, lights, materials))?;
+    Ok(())
+}
+
+
+pub fn load(path: &Path) -> Result<SceneDescription, SceneFileError> {
+    parse(&fs::read_to_string(path)?)
+}
+
+
+pub fn to_string(ellipses: &[Ellipse], primitives: &[Primitive], shapes: &[CsgNode], lights: &[Light], materials: &[Material]) -> String {
+    let mut result = String::from("# ellipsoid_ray_casting scene\n");
+    let names = material_names(materials);
+
+    for (material, name) in materials.iter().zip(&names) {
+        result += &format!(
+            "material name={} color={} ambient={} diffuse={} specular={} shininess={} emissive={} opacity={} reflectivity={} ior={}",
+            name,
+            material.color.to_hex(),
+            material.ambient,
+            material.diffuse,
+            material.specular,
+            material.shininess,
+            material.emissive.to_hex(),
+            material.opacity,
+            material.reflectivity,
+            material.refractive_index
+        );
+
+        if let Some(texture) = &material.texture {
+            result += &format!(" texture={}", texture.path);
+        }
+
+        result += "\n";
+    }
+
+    for ellipse in ellipses {
+        result += &format!(
+            "ellipsoid a={} b={} c={} position={} rotation={} scale={} material={} density={} absorption={}\n",
+            ellipse.a(), ellipse.b(), ellipse.c(),
+            format_vector(&ellipse.position.coords),
+            format_vector(&ellipse.rotation),
+            ellipse.scale,
+            names.get(ellipse.material).map_or("", String::as_str),
+            ellipse.density,
+            ellipse.absorption.to_hex()
+        );
+
+        for plane in &ellipse.clip_planes {
+            result += &format!("clip normal={} offset={}\n", format_vector(&plane.normal), plane.offset);
+        }
+    }
+
+    for primitive in primitives {
+        let geometry = match primitive.kind {
+            PrimitiveKind::Plane { point, normal, half_size } => format!(
+                "plane point={} normal={}{}",
+                format_vector(&point.coords),
+                format_vector(&normal),
+                half_size.map_or(String::new(), |half_size| format!(" size={}", half_size))
+            ),
+            PrimitiveKind::Sphere { center, radius } =>
+                format!("sphere center={} radius={}", format_vector(&center.coords), radius),
+            PrimitiveKind::Box { min, max } =>
+                format!("box min={} max={}", format_vector(&min.coords), format_vector(&max.coords)),
+            PrimitiveKind::Disc { center, normal, radius } =>
+                format!("disc center={} normal={} radius={}", format_vector(&center.coords), format_vector(&normal), radius),
+            PrimitiveKind::Torus { center, axis, major_radius, minor_radius } => format!(
+                "torus center={} axis={} major={} minor={}",
+                format_vector(&center.coords),
+                format_vector(&axis),
+                major_radius,
+                minor_radius
+            ),
+            PrimitiveKind::Superellipsoid { center, radii, vertical, horizontal } => format!(
+                "superellipsoid center={} radii={} vertical={} horizontal={}",
+                format_vector(&center.coords),
+                format_vector(&radii),
+                vertical,
+                horizontal
+            ),
+            PrimitiveKind::Implicit(ref root) => format!("implicit {}", root),
+        };
+
+        result += &format!(
+            "{} material={} density={} absorption={}\n",
+            geometry,
+            names.get(primitive.material).map_or("", String::as_str),
+            primitive.density,
+            primitive.absorption.to_hex()
+        );
+    }
+
+    for light in lights {
+        result += &format!(
+            "light position={} color={} intensity={} radius={}\n",
+            format_vector(&light.position.coords),
+            light.color.to_hex(),
+            light.intensity,
+            light.radius
+        );
+    }
+
+    for shape in shapes {
+        result += &format!("shape {}\n", format_node(shape));
+    }
+
+    result
+}
+
+
+pub fn parse(text: &str) -> Result<SceneDescription, SceneFileError> {
+    let mut ellipses: Vec<Ellipse> = Vec::new();
+    let mut primitives: Vec<Primitive> = Vec::new();
+    let mut shapes: Vec<(usize, CsgNode)> = Vec::new();
+    let mut lights: Vec<Light> = Vec::new();
+    let mut materials: Vec<Material> = Vec::new();
+    // Lines and material names of the ellipsoids and primitives, resolved once every material is read.
+    let mut ellipse_materials: Vec<(usize, String)> = Vec::new();
+    let mut primitive_materials: Vec<(usize, String)> = Vec::new();
+
+    for (line_index, line) in text.lines().enumerate() {
+        let error = |message: String| SceneFileError::Parse { line: line_index + 1, message };
+
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+        match keyword {
+            "material" => {
+                let material = parse_material(rest).map_err(error)?;
+
+                match materials.iter_mut().find(|other| other.name == material.name) {
+                    Some(other) => *other = material,
+                    None => materials.push(material),
+                }
+            }
+
+            "ellipsoid" => {
+                let (ellipse, material) = parse_ellipsoid(rest).map_err(error)?;
+
+                ellipse_materials.push((line_index + 1, material_name(material, &mut materials)));
+                ellipses.push(ellipse);
+            }
+
+            "plane" | "sphere" | "box" | "disc" | "torus" | "superellipsoid" | "implicit" => {
+                let (primitive, material) = parse_primitive(keyword, rest).map_err(error)?;
+
+                primitive_materials.push((line_index + 1, material_name(material, &mut materials)));
+                primitives.push(primitive);
+            }
+
+            "clip" => {
+                let plane = parse_clip_plane(rest).map_err(error)?;
+                ellipses.last_mut()
+                    .ok_or(error(String::from("clip plane before any ellipsoid")))?
+                    .clip_planes.push(plane);
+            }
+
+            "light" => lights.push(parse_light(rest).map_err(error)?),
+
+            "shape" => {
+                let mut parser = NodeParser { text: rest.trim(), depth: 0 };
+                let node = parser.node().map_err(error)?;
+
+                if !parser.text.is_empty() {
+                    return Err(error(format!("unexpected \"{}\"", parser.text)));
+                }
+
+                shapes.push((line_index + 1, node));
+            }
+
+            _ => return Err(error(format!("unknown record \"{}\"", keyword)))
+        }
+    }
+
+    let find_material = |(line, name): &(usize, String)| {
+        materials.iter().position(|material| material.name == *name)
+            .ok_or(SceneFileError::Parse { line: *line, message: format!("unknown material \"{}\"", name) })
+    };
+
+    for (reference, ellipse) in ellipse_materials.iter().zip(&mut ellipses) {
+        ellipse.material = find_material(reference)?;
+    }
+
+    for (reference, primitive) in primitive_materials.iter().zip(&mut primitives) {
+        primitive.material = find_material(reference)?;
+    }
+
+    for (line, shape) in &shapes {
+        if let Some(index) = shape.objects().into_iter().find(|index| *index >= ellipses.len()) {
+            return Err(SceneFileError::Parse { line: *line, message: format!("shape refers to missing ellipsoid {}", index) });
+        }
+    }
+
+    let shapes = if shapes.is_empty() {
+        SceneDescription::default_shapes(ellipses.len())
+    } else {
+        shapes.into_iter().map(|(_, shape)| shape).collect()
+    };
+
+    Ok(SceneDescription { ellipses, primitives, shapes, lights, materials })
+}
+
+
+fn strip_comment(line: &str) -> &str {
+    let mut after_whitespace = true;
+
+    for (i, c) in line.char_indices() {
+        if c == '#' && after_whitespace {
+            return &line[..i];
+        }
+        after_whitespace = c.is_whitespace();
+    }
+
+    line
+}
+
+
+/// Material of an object as written in the file.
+enum MaterialRef {
+    Name(String),
+    Color(Color),
+}
+
+
+/// Name of the material referred to, a plain material is added for a color.
+fn material_name(material: MaterialRef, materials: &mut Vec<Material>) -> String {
+    match material {
+        MaterialRef::Name(name) => name,
+        MaterialRef::Color(color) => {
+            let name = color.to_hex().trim_start_matches('#').to_string();
+
+            if !materials.iter().any(|material| material.name == name) {
+                materials.push(Material::new(&name, color));
+            }
+
+            name
+        }
+    }
+}
+
+
+fn parse_ellipsoid(text: &str) -> Result<(Ellipse, MaterialRef), String> {
+    let mut ellipse = Ellipse::new(1.0, 1.0, 1.0, &Point3::origin(), 0);
+    let mut material = MaterialRef::Color(parse_color(DEFAULT_COLOR)?);
+
+    // Sizes of zero would leave the object transformation without an inverse.
+    let positive = |key: &str, value: &str| match parse_number(value)? {
+        number if number > 0.0 => Ok(number),
+        _ => Err(format!("ellipsoid {} must be positive", key)),
+    };
+
+    for (key, value) in parse_attributes(text)? {
+        match key {
+            "a" => ellipse.set_a(positive(key, value)?),
+            "b" => ellipse.set_b(positive(key, value)?),
+            "c" => ellipse.set_c(positive(key, value)?),
+            "position" => ellipse.position = Point3::from(parse_vector(value)?),
+            "rotation" => ellipse.rotation = parse_vector(value)?,
+            "scale" => ellipse.scale = positive(key, value)?,
+            "material" => material = MaterialRef::Name(String::from(value)),
+            "color" => material = MaterialRef::Color(parse_color(value)?),
+            "density" => ellipse.density = parse_number(value)?,
+            "absorption" => ellipse.absorption = parse_color(value)?,
+            _ => return Err(format!("unknown ellipsoid attribute \"{}\"", key))
+        }
+    }
+
+    ellipse.recalculate();
+
+    Ok((ellipse, material))
+}
+
+
+fn parse_primitive(keyword: &str, text: &str) -> Result<(Primitive, MaterialRef), String> {
+    let (mut kind, text) = match keyword {
+        "implicit" => {
+            // The expression runs up to its last closing parenthesis, attributes follow.
+            let end = text.rfind(')').map_or(text.len(), |end| end + 1);
+            let (expression, rest) = text.split_at(end);
+
+            (PrimitiveKind::Implicit(expression.parse().map_err(|err: ParseSdfError| err.to_string())?), rest)
+        }
+        _ => (default_primitive(keyword), text),
+    };
+    let mut material = MaterialRef::Color(parse_color(DEFAULT_COLOR)?);
+    let (mut density, mut absorption) = (0.5, Color::from_rgb_f(1.0, 1.0, 1.0));
+
+    for (key, value) in parse_attributes(text)? {
+        match (&mut kind, key) {
+            (_, "material") => material = MaterialRef::Name(String::from(value)),
+            (_, "color") => material = MaterialRef::Color(parse_color(value)?),
+            (_, "density") => density = parse_number(value)?,
+            (_, "absorption") => absorption = parse_color(value)?,
+            (PrimitiveKind::Plane { point, .. }, "point") => *point = Point3::from(parse_vector(value)?),
+            (PrimitiveKind::Plane { half_size, .. }, "size") => *half_size = Some(parse_number(value)?),
+            (PrimitiveKind::Plane { normal, .. } | PrimitiveKind::Disc { normal, .. }, "normal") => *normal = parse_vector(value)?,
+            (PrimitiveKind::Sphere { center, .. } | PrimitiveKind::Disc { center, .. } | PrimitiveKind::Torus { center, .. } |
+             PrimitiveKind::Superellipsoid { center, .. }, "center") => *center = Point3::from(parse_vector(value)?),
+            (PrimitiveKind::Sphere { radius, .. } | PrimitiveKind::Disc { radius, .. }, "radius") => *radius = parse_number(value)?,
+            (PrimitiveKind::Box { min, .. }, "min") => *min = Point3::from(parse_vector(value)?),
+            (PrimitiveKind::Box { max, .. }, "max") => *max = Point3::from(parse_vector(value)?),
+            (PrimitiveKind::Torus { axis, .. }, "axis") => *axis = parse_vector(value)?,
+            (PrimitiveKind::Torus { major_radius, .. }, "major") => *major_radius = parse_number(value)?,
+            (PrimitiveKind::Torus { minor_radius, .. }, "minor") => *minor_radius = parse_number(value)?,
+            (PrimitiveKind::Superellipsoid { radii, .. }, "radii") => *radii = parse_vector(value)?,
+            (PrimitiveKind::Superellipsoid { vertical, .. }, "vertical") => *vertical = parse_number(value)?,
+            (PrimitiveKind::Superellipsoid { horizontal, .. }, "horizontal") => *horizontal = parse_number(value)?,
+            _ => return Err(format!("unknown {} attribute \"{}\"", keyword, key))
+        }
+    }
+
+    if let PrimitiveKind::Plane { normal, .. } | PrimitiveKind::Disc { normal, .. } | PrimitiveKind::Torus { axis: normal, .. } = kind {
+        if normal.norm() == 0.0 {
+            return Err(format!("{} normal must not be zero", keyword));
+        }
+    }
+
+    if let PrimitiveKind::Torus { major_radius, minor_radius, .. } = kind {
+        if major_radius <= 0.0 || minor_radius <= 0.0 {
+            return Err(String::from("torus radii must be positive"));
+        }
+    }
+
+    if let PrimitiveKind::Superellipsoid { radii, vertical, horizontal, .. } = kind {
+        if radii.min() <= 0.0 {
+            return Err(String::from("superellipsoid radii must be positive"));
+        }
+        if vertical <= 0.0 || horizontal <= 0.0 {
+            return Err(String::from("superellipsoid exponents must be positive"));
+        }
+    }
+
+    Ok((Primitive { density, absorption, ..Primitive::new(kind, 0) }, material))
+}
+
+
+fn default_primitive(keyword: &str) -> PrimitiveKind {
+    match keyword {
+        "plane" => PrimitiveKind::Plane { point: Point3::origin(), normal: Vector3::y(), half_size: None },
+        "sphere" => PrimitiveKind::Sphere { center: Point3::origin(), radius: 1.0 },
+        "box" => PrimitiveKind::Box { min: Point3::new(-1.0, -1.0, -1.0), max: Point3::new(1.0, 1.0, 1.0) },
+        "disc" => PrimitiveKind::Disc { center: Point3::origin(), normal: Vector3::y(), radius: 1.0 },
+        "torus" => PrimitiveKind::Torus { center: Point3::origin(), axis: Vector3::y(), major_radius: 1.0, minor_radius: 0.25 },
+        _ => PrimitiveKind::Superellipsoid { center: Point3::origin(), radii: Vector3::repeat(1.0), vertical: 1.0, horizontal: 1.0 },
+    }
+}
+
+
+fn parse_material(text: &str) -> Result<Material, String> {
+    let mut material = Material::new("", Color::from_rgb(255, 255, 255));
+
+    for (key, value) in parse_attributes(text)? {
+        match key {
+            "name" => material.name = String::from(value),
+            "color" => material.color = parse_color(value)?,
+            "texture" => material.texture = Some(
+                Texture::load(Path::new(value)).map_err(|err| format!("cannot load texture \"{}\": {}", value, err))?
+            ),
+            "ambient" => material.ambient = parse_number(value)?,
+            "diffuse" => material.diffuse = parse_number(value)?,
+            "specular" => material.specular = parse_number(value)?,
+            "shininess" => material.shininess = parse_number(value)?,
+            "emissive" => material.emissive = parse_color(value)?,
+            "opacity" => material.opacity = parse_number(value)?,
+            "reflectivity" => material.reflectivity = parse_number(value)?,
+            "ior" => material.refractive_index = parse_number(value)?,
+            _ => return Err(format!("unknown material attribute \"{}\"", key))
+        }
+    }
+
+    if material.name.is_empty() {
+        return Err(String::from("material needs a name"));
+    }
+
+    Ok(material)
+}
+
+
+fn parse_clip_plane(text: &str) -> Result<ClipPlane, String> {
+    let mut normal = -Vector3::z();
+    let mut offset = 0.0;
+
+    for (key, value) in parse_attributes(text)? {
+        match key {
+            "normal" => normal = parse_vector(value)?,
+            "offset" => offset = parse_number(value)?,
+            _ => return Err(format!("unknown clip attribute \"{}\"", key))
+        }
+    }
+
+    if normal.norm() == 0.0 {
+        return Err(String::from("clip plane normal must not be zero"));
+    }
+
+    Ok(ClipPlane::new(&normal, offset))
+}
+
+
+fn parse_light(text: &str) -> Result<Light, String> {
+    let mut light = Light::new(&Point3::new(0.0, 0.0, -10.0), Color::from_rgb(255, 255, 255), 1.0, 0.0);
+
+    for (key, value) in parse_attributes(text)? {
+        match key {
+            "position" => light.position = Point3::from(parse_vector(value)?),
+            "color" => light.color = parse_color(value)?,
+            "intensity" => light.intensity = parse_number(value)?,
+            "radius" => light.radius = parse_number(value)?,
+            _ => return Err(format!("unknown light attribute \"{}\"", key))
+        }
+    }
+
+    Ok(light)
+}
+
+
+fn parse_attributes(text: &str) -> Result<Vec<(&str, &str)>, String> {
+    text.split_whitespace()
+        .map(|attribute| attribute.split_once('=').ok_or(format!("expected key=value, found \"{}\"", attribute)))
+        .collect()
+}
+
+
+fn parse_number(text: &str) -> Result<f32, String> {
+    f32::from_str(text).ok()
+        .filter(|number| number.is_finite())
+        .ok_or(format!("invalid number \"{}\"", text))
+}
+
+
+fn parse_vector(text: &str) -> Result<Vector3<f32>, String> {
+    let values = text.split(',').map(parse_number).collect::<Result<Vec<f32>, String>>()?;
+
+    match values[..] {
+        [x, y, z] => Ok(Vector3::new(x, y, z)),
+        _ => Err(format!("expected three comma separated numbers, found \"{}\"", text))
+    }
+}
+
+
+fn parse_color(text: &str) -> Result<Color, String> {
+    Color::from_str(text).map_err(|err| err.to_string())
+}
+
+
+/// Names of the materials usable in the file: without whitespace and unique.
+fn material_names(materials: &[Material]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+
+    for (index, material) in materials.iter().enumerate() {
+        let mut name = material.name.split_whitespace().collect::<Vec<_>>().join("_");
+
+        if name.is_empty() || names.contains(&name) {
+            name = format!("{}_{}", if name.is_empty() { "material" } else { &name }, index + 1);
+        }
+
+        names.push(name);
+    }
+
+    names
+}
+
+
+fn format_vector(vector: &Vector3<f32>) -> String {
+    format!("{},{},{}", vector.x, vector.y, vector.z)
+}
+
+
+fn format_node(node: &CsgNode) -> String {
+    match node {
+        CsgNode::Object(index) => index.to_string(),
+        CsgNode::Operation { operation, left, right } =>
+            format!("{}({}, {})", operation.name(), format_node(left), format_node(right)),
+    }
+}
+
+
+/// Recursive descent parser of `shape` expressions.
+struct NodeParser<'a> {
+    text: &'a str,
+    depth: usize,
+}
+
+
+impl<'a> NodeParser<'a> {
+    fn node(&mut self) -> Result<CsgNode, String> {
+        let end = self.text.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(self.text.len());
+        let (word, rest) = self.text.split_at(end);
+        self.text = rest.trim_start();
+
+        if let Ok(index) = word.parse::<usize>() {
+            return Ok(CsgNode::Object(index));
+        }
+
+        let operation = CsgOperation::ALL.into_iter()
+            .find(|operation| operation.name() == word)
+            .ok_or(format!("unknown shape operation \"{}\"", word))?;
+
+        if self.depth == MAX_SHAPE_DEPTH {
+            return Err(format!("shape nested deeper than {} levels", MAX_SHAPE_DEPTH));
+        }
+
+        self.depth += 1;
+        self.expect('(')?;
+        let left = self.node()?;
+        self.expect(',')?;
+        let right = self.node()?;
+        self.expect(')')?;
+        self.depth -= 1;
+
+        Ok(CsgNode::operation(operation, left, right))
+    }
+
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.text.strip_prefix(expected) {
+            Some(rest) => {
+                self.text = rest.trim_start();
+                Ok(())
+            }
+            None => Err(format!("expected '{}' at \"{}\"", expected, self.text))
+        }
+    }
+}
+
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::Io(err) => write!(f, "{}", err),
+            SceneFileError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+
+impl error::Error for SceneFileError {}
+
+
+impl From<io::Error> for SceneFileError {
+    fn from(err: io::Error) -> Self {
+        SceneFileError::Io(err)
+    }
+}
+
+
+#[cfg(test)]
+mod scene_file_tests {
+    use super::*;
+
+
+    #[test]
+    fn round_trip() {
+        let mut first = Ellipse::new(0.25, 1.0, 0.5, &Point3::new(1.0, -2.0, 0.5), 1);
+        first.rotation = Vector3::new(0.5, 0.0, -1.0);
+        first.clip_planes.push(ClipPlane::new(&Vector3::new(0.0, 0.0, -1.0), 0.25));
+        let second = Ellipse::new(1.0, 1.0, 1.0, &Point3::origin(), 0);
+
+        let mut glass = Material::new("clear glass", Color::from_rgb(255, 0, 0));
+        glass.reflectivity = 0.25;
+        glass.opacity = 0.5;
+        glass.refractive_index = 1.33;
+        let materials = vec![Material::new("blue", Color::from_rgb(0, 0, 255)), glass];
+
+        let shapes = vec![CsgNode::operation(CsgOperation::Difference, CsgNode::Object(0), CsgNode::Object(1))];
+
+        let lights = vec![Light::new(&Point3::new(5.0, 5.0, -10.0), Color::from_rgb(255, 255, 0), 0.75, 0.5)];
+
+        let primitives = vec![
+            Primitive::ground(-2.0, 0),
+            Primitive {
+                density: 2.0,
+                absorption: Color::from_rgb(255, 0, 0),
+                ..Primitive::new(PrimitiveKind::Disc { center: Point3::new(0.0, 1.0, 0.0), normal: Vector3::z(), radius: 0.5 }, 1)
+            },
+            Primitive::new(PrimitiveKind::Torus { center: Point3::new(2.0, 0.0, 0.0), axis: Vector3::x(), major_radius: 1.5, minor_radius: 0.25 }, 0),
+            Primitive::new(PrimitiveKind::Superellipsoid { center: Point3::origin(), radii: Vector3::new(1.0, 2.0, 1.0), vertical: 0.25, horizontal: 2.0 }, 1),
+            Primitive::new(PrimitiveKind::Implicit("twist(0.5, smooth_union(0.25, sphere(0, 0, 0, 1), box(1, 0, 0, 0.5, 2, 0.5)))".parse().unwrap()), 0),
+        ];
+
+        let text = to_string(&[first, second], &primitives, &shapes, &lights, &materials);
+        let scene = parse(&text).unwrap();
+
+        assert_eq!(shapes, scene.shapes);
+        assert_eq!(2, scene.ellipses.len());
+        assert_eq!(0.25, scene.ellipses[0].a());
+        assert_eq!(Point3::new(1.0, -2.0, 0.5), scene.ellipses[0].position);
+        assert_eq!(Vector3::new(0.5, 0.0, -1.0), scene.ellipses[0].rotation);
+        assert_eq!((1, 0), (scene.ellipses[0].material, scene.ellipses[1].material));
+        assert_eq!("clear_glass", scene.materials[1].name);
+        assert_eq!(Material { name: String::from("clear_glass"), ..materials[1].clone() }, scene.materials[1]);
+        assert_eq!(1, scene.ellipses[0].clip_planes.len());
+        assert_eq!(0.25, scene.ellipses[0].clip_planes[0].offset);
+        assert_eq!(lights, scene.lights);
+        assert_eq!(primitives, scene.primitives);
+        assert_eq!(text, to_string(&scene.ellipses, &scene.primitives, &scene.shapes, &scene.lights, &scene.materials));
+    }
+
+
+    #[test]
+    fn nested_shapes() {
+        let scene = parse("ellipsoid\nellipsoid\nellipsoid\nshape intersection( union(0,1) , 2 ) # lens\n").unwrap();
+
+        assert_eq!(vec![CsgNode::operation(
+            CsgOperation::Intersection,
+            CsgNode::operation(CsgOperation::Union, CsgNode::Object(0), CsgNode::Object(1)),
+            CsgNode::Object(2)
+        )], scene.shapes);
+    }
+
+
+    #[test]
+    fn objects_without_shapes_are_shown_separately() {
+        let scene = parse("ellipsoid color=red\nellipsoid color=blue\n").unwrap();
+
+        assert_eq!(vec![CsgNode::Object(0), CsgNode::Object(1)], scene.shapes);
+    }
+
+
+    #[test]
+    fn colors_become_materials() {
+        let scene = parse("ellipsoid color=red\nellipsoid material=red\nellipsoid\nmaterial name=red color=#800000\n").unwrap();
+
+        assert_eq!(3, scene.materials.len());
+        assert_eq!(vec![0, 2, 1], scene.ellipses.iter().map(|ellipse| ellipse.material).collect::<Vec<_>>());
+        assert_eq!(("ff0000", "eff542"), (scene.materials[0].name.as_str(), scene.materials[1].name.as_str()));
+        assert_eq!("#800000", scene.materials[2].color.to_hex());
+
+        let scene = parse("box min=0,0,0 max=1,2,3 color=red\nplane size=5\n").unwrap();
+        assert_eq!(vec![0, 1], scene.primitives.iter().map(|primitive| primitive.material).collect::<Vec<_>>());
+        assert_eq!(PrimitiveKind::Box { min: Point3::origin(), max: Point3::new(1.0, 2.0, 3.0) }, scene.primitives[0].kind);
+
+        let error = parse("ellipsoid material=gold\n").err().unwrap();
+        assert_eq!("line 1: unknown material \"gold\"", error.to_string());
+    }
+
+
+    #[test]
+    fn errors_report_line() {
+        let error = parse("ellipsoid\nellipsoid colour=red\n").err().unwrap();
+        assert_eq!("line 2: unknown ellipsoid attribute \"colour\"", error.to_string());
+
+        let error = parse("ellipsoid\nshape union(0, 1)\n").err().unwrap();
+        assert_eq!("line 2: shape refers to missing ellipsoid 1", error.to_string());
+
+        let deep = format!("ellipsoid\nshape {}0{}", "union(0, ".repeat(100_000), ")".repeat(100_000));
+        assert_eq!("line 2: shape nested deeper than 64 levels", parse(&deep).err().unwrap().to_string());
+
+        let error = parse("ellipsoid\nellipsoid scale=0\n").err().unwrap();
+        assert_eq!("line 2: ellipsoid scale must be positive", error.to_string());
+        let error = parse("ellipsoid a=-1\n").err().unwrap();
+        assert_eq!("line 1: ellipsoid a must be positive", error.to_string());
+        let error = parse("ellipsoid b=nan\n").err().unwrap();
+        assert_eq!("line 1: invalid number \"nan\"", error.to_string());
+        assert!(parse("ellipsoid position=inf,0,0\n").is_err());
+
+        assert!(parse("shape xor(0, 1)").is_err());
+        assert!(parse("clip normal=0,0,1").is_err());
+        assert!(parse("sphere min=0,0,0").is_err());
+
+        let error = parse("torus minor=0").err().unwrap();
+        assert_eq!("line 1: torus radii must be positive", error.to_string());
+        let error = parse("superellipsoid radii=1,-1,1").err().unwrap();
+        assert_eq!("line 1: superellipsoid radii must be positive", error.to_string());
+    }
+}
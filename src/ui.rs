@@ -1,7 +1,8 @@
 use std::ops::RangeInclusive;
 
-use egui::{ClippedPrimitive, Context, TexturesDelta, Ui};
+use egui::{ClippedPrimitive, Color32, Context, TexturesDelta, Ui};
 use egui_wgpu::renderer::{Renderer, ScreenDescriptor};
+use ellipsoid_ray_casting::{Background, ClipPlane, Color, Colormap, Contact, CsgNode, CsgOperation, CutMode, Ellipse, Environment, FitMethod, GlyphColoring, GlyphOptions, Light, Material, ObjectId, PickResult, Primitive, PrimitiveKind, SdfNode, ShadingMode, SplatView, ShadowMode, SvgOptions, Tessellation, ToneMapping};
 use pixels::{wgpu, PixelsContext};
 use winit::event_loop::EventLoopWindowTarget;
 use winit::window::Window;
@@ -21,10 +22,52 @@ pub(crate) struct Gui {
 }
 
 
+#[derive(PartialEq, Clone, Copy)]
+enum BackgroundMode {
+    Solid,
+    Gradient,
+    Transparent,
+}
+
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum EnvironmentMode {
+    Off,
+    Sky,
+    Image,
+}
+
+
+struct ClipPlaneControls {
+    enabled: bool,
+    /// Angles of the plane normal in degrees.
+    azimuth: f32,
+    elevation: f32,
+    offset: f32,
+}
+
+
+/// Edit of the CSG shapes requested in the GUI.
+pub enum CsgCommand {
+    Combine{first: usize, second: usize, operation: CsgOperation},
+    Ungroup(usize),
+    SetOperation{shape: usize, path: Vec<usize>, operation: CsgOperation},
+    Swap{shape: usize, path: Vec<usize>},
+}
+
+
 pub struct GuiState {
     /// Only show the egui window when true.
     window_open: bool,
 
+    pub old_selected: usize,
+    pub selected: usize,
+    pub object_count: usize,
+    /// Set when the user asks to add an object.
+    pub add_object: bool,
+    /// Set when the user asks to remove the selected object.
+    pub remove_object: bool,
+
     pub old_a: f32,
     pub a: f32,
 
@@ -34,14 +77,198 @@ pub struct GuiState {
     pub old_c: f32,
     pub c: f32,
 
-    pub old_m: f32,
-    pub m: f32,
-
     pub old_scale: f32,
     pub scale: f32,
 
+    pub old_density: f32,
+    pub density: f32,
+
+    pub old_absorption: [u8; 3],
+    pub absorption: [u8; 3],
+
+    pub old_material: usize,
+    pub material: usize,
+
+    pub old_materials: Vec<Material>,
+    pub materials: Vec<Material>,
+    /// Set when the user asks to remove a material.
+    pub remove_material: Option<usize>,
+    pub texture_path: String,
+    /// Set when the user asks to load `texture_path` into a material.
+    pub load_texture: Option<usize>,
+    pub material_status: String,
+
+    pub old_max_depth: u32,
+    pub max_depth: u32,
+
+    pub old_exposure: f32,
+    pub exposure: f32,
+
+    pub old_primitives: Vec<Primitive>,
+    pub primitives: Vec<Primitive>,
+    /// Distance function expression of the next implicit surface added.
+    pub implicit_expression: String,
+    pub implicit_status: String,
+
+    pub old_lights: Vec<Light>,
+    pub lights: Vec<Light>,
+
+    pub old_shadow_mode: ShadowMode,
+    pub shadow_mode: ShadowMode,
+    /// Samples per light of soft shadows, kept while other modes are chosen.
+    shadow_samples: u32,
+
+    pub old_ambient: f32,
+    pub ambient: f32,
+
+    pub old_tone_mapping: ToneMapping,
+    pub tone_mapping: ToneMapping,
+
+    pub old_shading_mode: ShadingMode,
+    pub shading_mode: ShadingMode,
+
+    pub old_colormap: Colormap,
+    pub colormap: Colormap,
+
+    pub old_curvature_min: f32,
+    pub curvature_min: f32,
+
+    pub old_curvature_max: f32,
+    pub curvature_max: f32,
+
+    /// Set when the user asks to fit the colormap range to the visible curvature.
+    pub fit_curvature_range: bool,
+
     pub old_max_block_size: u32,
     pub max_block_size: u32,
+
+    pub old_near: f32,
+    pub near: f32,
+
+    pub old_far: f32,
+    pub far: f32,
+
+    pub old_cut_mode: CutMode,
+    pub cut_mode: CutMode,
+
+    pub old_clip_planes: Option<Vec<ClipPlane>>,
+    clip_planes: Vec<ClipPlaneControls>,
+
+    pub old_background: Option<Background>,
+    background_mode: BackgroundMode,
+    background_top: [u8; 3],
+    background_bottom: [u8; 3],
+
+    pub old_environment: Option<(EnvironmentMode, [[u8; 3]; 3])>,
+    pub environment_mode: EnvironmentMode,
+    /// Zenith, horizon and ground colors of the sky.
+    sky_colors: [[u8; 3]; 3],
+    pub environment_path: String,
+    /// Set when the user asks to load the environment image.
+    pub load_environment: bool,
+    pub environment_status: String,
+
+    /// Turn of the environment in degrees.
+    pub old_environment_rotation: f32,
+    pub environment_rotation: f32,
+
+    pub old_environment_intensity: f32,
+    pub environment_intensity: f32,
+
+    pub export_path: String,
+    /// Set when the user asks to save the image.
+    pub save_png: bool,
+    pub export_status: String,
+
+    pub mean: [f32; 3],
+    pub covariance: [[f32; 3]; 3],
+    pub confidence: f32,
+    covariance_text: String,
+    /// Set when the user asks to rebuild the object from the covariance.
+    pub apply_covariance: bool,
+    pub covariance_status: String,
+
+    pub old_highlight_overlaps: bool,
+    pub highlight_overlaps: bool,
+    /// Copy of the scene contacts shown in the overlap list.
+    pub contacts: Vec<(usize, usize, Contact)>,
+
+    pub old_curves_style: Option<(bool, f32, [u8; 3])>,
+    pub show_curves: bool,
+    pub curve_width: f32,
+    pub curve_color: [u8; 3],
+    pub curves_path: String,
+    /// Set when the user asks to export the intersection curves.
+    pub export_curves: bool,
+    pub curves_status: String,
+
+    pub points_path: String,
+    /// Set when the user asks to load the point cloud.
+    pub load_points: bool,
+    /// Set when the user asks to fit an ellipsoid to the point cloud.
+    pub fit_points: bool,
+    pub fit_method: FitMethod,
+    pub points_status: String,
+
+    pub old_points_style: Option<(bool, f32, [u8; 3])>,
+    pub show_points: bool,
+    pub point_size: f32,
+    pub point_color: [u8; 3],
+
+    pub splats_path: String,
+    /// Set when the user asks to load the splat cloud.
+    pub load_splats: bool,
+    /// Set when the user asks to fit the splat cloud to the view again.
+    pub reset_splat_view: bool,
+    pub splats_status: String,
+
+    pub old_splat_view: SplatView,
+    pub splat_view: SplatView,
+
+    pub tensors_path: String,
+    /// Set when the user asks to load the tensor field.
+    pub load_tensors: bool,
+    /// Set when the user asks to remove the tensor field.
+    pub clear_tensors: bool,
+    pub tensors_status: String,
+    /// Smallest and largest trace in the loaded field, `None` without a field.
+    pub trace_limits: Option<(f32, f32)>,
+    pub glyph_count: usize,
+
+    pub old_glyph_options: GlyphOptions,
+    pub glyph_options: GlyphOptions,
+
+    pub mesh_path: String,
+    mesh_adaptive: bool,
+    mesh_latitudes: u32,
+    mesh_longitudes: u32,
+    mesh_tolerance: f32,
+    /// Set when the user asks to export the objects as a triangle mesh.
+    pub export_mesh: bool,
+    pub mesh_status: String,
+
+    pub svg_path: String,
+    pub svg_options: SvgOptions,
+    /// Set when the user asks to export the view as SVG.
+    pub export_svg: bool,
+    pub svg_status: String,
+
+    /// Copy of the scene shapes shown in the CSG tree.
+    pub shapes: Vec<CsgNode>,
+    pub csg_commands: Vec<CsgCommand>,
+    combine_first: usize,
+    combine_second: usize,
+    combine_operation: CsgOperation,
+
+    pub scene_path: String,
+    /// Set when the user asks to save the scene.
+    pub save_scene: bool,
+    /// Set when the user asks to load the scene.
+    pub load_scene: bool,
+    pub scene_status: String,
+
+    /// What is under the mouse cursor.
+    pub hover: Option<PickResult>,
 }
 
 impl Gui {
@@ -167,6 +394,12 @@ impl GuiState {
         Self {
             window_open: true,
 
+            old_selected: 0,
+            selected: 0,
+            object_count: 1,
+            add_object: false,
+            remove_object: false,
+
             old_a: 0.0,
             a: 2.0,
 
@@ -176,14 +409,254 @@ impl GuiState {
             old_c: 0.0,
             c: 3.0,
 
-            old_m: 0.0,
-            m: 1.0,
-
             old_scale: 0.0,
             scale: 1.0,
 
+            old_density: f32::NAN,
+            density: 0.5,
+
+            old_absorption: [0, 0, 0],
+            absorption: [255, 255, 255],
+
+            old_material: 0,
+            material: 0,
+
+            old_materials: Vec::new(),
+            materials: Vec::new(),
+            remove_material: None,
+            texture_path: String::from("texture.png"),
+            load_texture: None,
+            material_status: String::new(),
+
+            old_max_depth: 4,
+            max_depth: 4,
+
+            old_exposure: f32::NAN,
+            exposure: 0.0,
+
+            old_primitives: Vec::new(),
+            primitives: Vec::new(),
+            implicit_expression: String::from("smooth_union(0.5, sphere(0, 0, 0, 1), twist(1, box(1.5, 0, 0, 0.5, 1.5, 0.5)))"),
+            implicit_status: String::new(),
+
+            old_lights: Vec::new(),
+            lights: Vec::new(),
+
+            old_shadow_mode: ShadowMode::Hard,
+            shadow_mode: ShadowMode::Hard,
+            shadow_samples: 16,
+
+            old_ambient: f32::NAN,
+            ambient: 1.0,
+
+            old_tone_mapping: ToneMapping::Clamp,
+            tone_mapping: ToneMapping::Clamp,
+
+            old_shading_mode: ShadingMode::Light,
+            shading_mode: ShadingMode::Light,
+
+            old_colormap: Colormap::Viridis,
+            colormap: Colormap::Viridis,
+
+            old_curvature_min: f32::NAN,
+            curvature_min: 0.0,
+
+            old_curvature_max: f32::NAN,
+            curvature_max: 1.0,
+
+            fit_curvature_range: false,
+
             old_max_block_size: 0,
             max_block_size: 27,
+
+            old_near: f32::NAN,
+            near: 0.0,
+
+            old_far: f32::NAN,
+            far: 100.0,
+
+            old_cut_mode: CutMode::Solid,
+            cut_mode: CutMode::Solid,
+
+            old_clip_planes: None,
+            clip_planes: Vec::new(),
+
+            old_background: None,
+            background_mode: BackgroundMode::Solid,
+            background_top: [120, 120, 120],
+            background_bottom: [40, 40, 40],
+
+            old_environment: None,
+            environment_mode: EnvironmentMode::Off,
+            sky_colors: [[90, 140, 220], [215, 225, 235], [90, 80, 70]],
+            environment_path: String::from("environment.hdr"),
+            load_environment: false,
+            environment_status: String::new(),
+
+            old_environment_rotation: f32::NAN,
+            environment_rotation: 0.0,
+
+            old_environment_intensity: f32::NAN,
+            environment_intensity: 1.0,
+
+            export_path: String::from("render.png"),
+            save_png: false,
+            export_status: String::new(),
+
+            mean: [0.0; 3],
+            covariance: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            confidence: 0.95,
+            covariance_text: String::new(),
+            apply_covariance: false,
+            covariance_status: String::new(),
+
+            old_highlight_overlaps: false,
+            highlight_overlaps: false,
+            contacts: Vec::new(),
+
+            old_curves_style: None,
+            show_curves: false,
+            curve_width: 2.0,
+            curve_color: [255, 255, 255],
+            curves_path: String::from("intersections.csv"),
+            export_curves: false,
+            curves_status: String::new(),
+
+            points_path: String::from("points.csv"),
+            load_points: false,
+            fit_points: false,
+            fit_method: FitMethod::LeastSquares,
+            points_status: String::new(),
+
+            old_points_style: None,
+            show_points: true,
+            point_size: 2.0,
+            point_color: [230, 40, 40],
+
+            splats_path: String::from("splats.ply"),
+            load_splats: false,
+            reset_splat_view: false,
+            splats_status: String::new(),
+
+            old_splat_view: SplatView::default(),
+            splat_view: SplatView::default(),
+
+            tensors_path: String::from("tensors.csv"),
+            load_tensors: false,
+            clear_tensors: false,
+            tensors_status: String::new(),
+            trace_limits: None,
+            glyph_count: 0,
+
+            old_glyph_options: GlyphOptions::default(),
+            glyph_options: GlyphOptions::default(),
+
+            mesh_path: String::from("ellipsoid.obj"),
+            mesh_adaptive: false,
+            mesh_latitudes: 32,
+            mesh_longitudes: 64,
+            mesh_tolerance: 0.01,
+            export_mesh: false,
+            mesh_status: String::new(),
+
+            svg_path: String::from("figure.svg"),
+            svg_options: SvgOptions::default(),
+            export_svg: false,
+            svg_status: String::new(),
+
+            shapes: Vec::new(),
+            csg_commands: Vec::new(),
+            combine_first: 0,
+            combine_second: 1,
+            combine_operation: CsgOperation::Union,
+
+            scene_path: String::from("scene.txt"),
+            save_scene: false,
+            load_scene: false,
+            scene_status: String::new(),
+
+            hover: None,
+        }
+    }
+
+    /// Shows parameters of `ellipse` without reporting them as changed.
+    pub fn load_object(&mut self, ellipse: &Ellipse) {
+        self.a = 1.0 / ellipse.a().sqrt();
+        self.b = 1.0 / ellipse.b().sqrt();
+        self.c = 1.0 / ellipse.c().sqrt();
+        self.scale = ellipse.scale;
+        self.density = ellipse.density;
+        self.absorption = [ellipse.absorption.red(), ellipse.absorption.green(), ellipse.absorption.blue()];
+        self.material = ellipse.material;
+
+        self.old_a = self.a;
+        self.old_b = self.b;
+        self.old_c = self.c;
+        self.old_scale = self.scale;
+        self.old_density = self.density;
+        self.old_absorption = self.absorption;
+        self.old_material = self.material;
+
+        self.mean = ellipse.center().coords.into();
+        self.covariance = ellipse.covariance(self.confidence).transpose().into();
+
+        self.clip_planes = ellipse.clip_planes.iter()
+            .map(|plane| ClipPlaneControls {
+                enabled: true,
+                azimuth: f32::atan2(plane.normal.x, -plane.normal.z).to_degrees(),
+                elevation: plane.normal.y.clamp(-1.0, 1.0).asin().to_degrees(),
+                offset: plane.offset,
+            })
+            .collect();
+        self.old_clip_planes = Some(self.clip_planes());
+    }
+
+    /// Enabled clipping planes.
+    pub fn clip_planes(&self) -> Vec<ClipPlane> {
+        self.clip_planes.iter()
+            .filter(|plane| plane.enabled)
+            .map(|plane| ClipPlane::from_angles(plane.azimuth.to_radians(), plane.elevation.to_radians(), plane.offset))
+            .collect()
+    }
+
+    /// Background selected in the GUI.
+    pub fn background(&self) -> Background {
+        let [r, g, b] = self.background_top;
+        let top = Color::from_rgb(r, g, b);
+
+        let [r, g, b] = self.background_bottom;
+        let bottom = Color::from_rgb(r, g, b);
+
+        match self.background_mode {
+            BackgroundMode::Solid => Background::Solid(top),
+            BackgroundMode::Gradient => Background::Gradient { top, bottom },
+            BackgroundMode::Transparent => Background::Transparent,
+        }
+    }
+
+    /// Shows a solid background of `color`, as chosen in the GUI.
+    pub fn set_background_color(&mut self, color: Color) {
+        self.background_mode = BackgroundMode::Solid;
+        self.background_top = [color.red(), color.green(), color.blue()];
+    }
+
+    /// Environment settings compared between frames.
+    pub fn environment(&self) -> (EnvironmentMode, [[u8; 3]; 3]) {
+        (self.environment_mode, self.sky_colors)
+    }
+
+    /// Procedural sky with the colors selected in the GUI.
+    pub fn sky(&self) -> Environment {
+        let [zenith, horizon, ground] = self.sky_colors.map(|[r, g, b]| Color::from_rgb(r, g, b));
+
+        Environment::sky(zenith, horizon, ground)
+    }
+
+    pub fn tessellation(&self) -> Tessellation {
+        if self.mesh_adaptive {
+            Tessellation::Adaptive { tolerance: self.mesh_tolerance }
+        } else {
+            Tessellation::Uniform { latitudes: self.mesh_latitudes, longitudes: self.mesh_longitudes }
         }
     }
 
@@ -193,16 +666,776 @@ impl GuiState {
             .open(&mut self.window_open)
             .show(ctx, |ui| {
 
-                ui.label("Ellipsoid parameters");
-                float_input("a:", &mut self.a, 0.0..=5.0, ui);
-                float_input("b:", &mut self.b, 0.0..=5.0, ui);
-                float_input("c:", &mut self.c, 0.0..=5.0, ui);
-                float_input("scale:", &mut self.scale, 0.01..=5.0, ui);
+                ui.label("Objects");
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("selected_object")
+                        .selected_text(format!("ellipsoid {}", self.selected + 1))
+                        .show_ui(ui, |ui| {
+                            for i in 0..self.object_count {
+                                ui.selectable_value(&mut self.selected, i, format!("ellipsoid {}", i + 1));
+                            }
+                        }
+                    );
+
+                    if ui.button("Add").clicked() {
+                        self.add_object = true;
+                    }
+
+                    if ui.button("Remove").clicked() {
+                        self.remove_object = true;
+                    }
+                });
+
+                ui.separator();
+
+                if self.shading_mode == ShadingMode::Splats {
+                    // The objects are hidden, their controls place the splat cloud instead.
+                    let view = &mut self.splat_view;
+
+                    ui.label("Splat view");
+                    float_input("scale:", &mut view.zoom, 0.01..=100.0, ui);
+                    float_input("x:", &mut view.offset.x, -100.0..=100.0, ui);
+                    float_input("y:", &mut view.offset.y, -100.0..=100.0, ui);
+                    float_input("z:", &mut view.offset.z, -100.0..=100.0, ui);
+
+                    let pi = std::f32::consts::PI;
+                    float_input("turn x:", &mut view.rotation.x, -pi..=pi, ui);
+                    float_input("turn y:", &mut view.rotation.y, -pi..=pi, ui);
+                    float_input("turn z:", &mut view.rotation.z, -pi..=pi, ui);
+                } else {
+                    ui.label("Ellipsoid parameters");
+                    float_input("a:", &mut self.a, 0.0..=50.0, ui);
+                    float_input("b:", &mut self.b, 0.0..=50.0, ui);
+                    float_input("c:", &mut self.c, 0.0..=50.0, ui);
+                    float_input("scale:", &mut self.scale, 0.01..=5.0, ui);
+
+                    if self.shading_mode == ShadingMode::XRay {
+                        float_input("density:", &mut self.density, 0.0..=10.0, ui);
+                        ui.horizontal(|ui| {
+                            ui.label("absorption:");
+                            ui.color_edit_button_srgb(&mut self.absorption);
+                        });
+                    } else {
+                        ui.horizontal(|ui| {
+                            ui.label("material:");
+                            egui::ComboBox::from_id_source("object_material")
+                                .selected_text(self.materials.get(self.material).map_or("", |material| material.name.as_str()))
+                                .show_ui(ui, |ui| {
+                                    for (i, material) in self.materials.iter().enumerate() {
+                                        ui.selectable_value(&mut self.material, i, &material.name);
+                                    }
+                                }
+                            );
+                        });
+                    }
+                }
+
+                ui.collapsing("Covariance", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("mean:");
+                        for value in &mut self.mean {
+                            ui.add(egui::DragValue::new(value).speed(0.01));
+                        }
+                    });
+
+                    egui::Grid::new("covariance").show(ui, |ui| {
+                        for i in 0..3 {
+                            for j in 0..3 {
+                                if ui.add(egui::DragValue::new(&mut self.covariance[i][j]).speed(0.01)).changed() {
+                                    self.covariance[j][i] = self.covariance[i][j];
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                    ui.add(egui::Slider::new(&mut self.confidence, 0.01..=0.999).text("confidence"));
+
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.covariance_text);
+                        if ui.button("Paste").clicked() {
+                            match parse_covariance(&self.covariance_text) {
+                                Some(covariance) => {
+                                    self.covariance = covariance;
+                                    self.covariance_status.clear();
+                                }
+                                None => self.covariance_status = String::from("Expected 9 numbers or the 6 of the upper triangle"),
+                            }
+                        }
+                    });
+
+                    if ui.button("Apply").clicked() {
+                        self.apply_covariance = true;
+                    }
+
+                    if !self.covariance_status.is_empty() {
+                        ui.label(&self.covariance_status);
+                    }
+                });
+
+                ui.collapsing("Overlaps", |ui| {
+                    ui.checkbox(&mut self.highlight_overlaps, "highlight overlapping objects");
+
+                    for (first, second, contact) in &self.contacts {
+                        let p = contact.point;
+                        ui.label(if contact.overlapping {
+                            format!("{} and {}: overlap near ({:.3}, {:.3}, {:.3})", first + 1, second + 1, p.x, p.y, p.z)
+                        } else {
+                            format!("{} and {}: distance {:.3}, closest near ({:.3}, {:.3}, {:.3})", first + 1, second + 1, contact.distance, p.x, p.y, p.z)
+                        });
+                    }
+                });
+
+                ui.collapsing("Intersection curves", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.show_curves, "show curves");
+                        ui.color_edit_button_srgb(&mut self.curve_color);
+                    });
+                    ui.add(egui::Slider::new(&mut self.curve_width, 0.5..=10.0).text("line width"));
+
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.curves_path);
+                        if ui.button("Export").clicked() {
+                            self.export_curves = true;
+                        }
+                    });
+
+                    if !self.curves_status.is_empty() {
+                        ui.label(&self.curves_status);
+                    }
+                });
+
+                ui.separator();
+
+                ui.collapsing("Point cloud", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.points_path);
+                        if ui.button("Load").clicked() {
+                            self.load_points = true;
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("fit_method")
+                            .selected_text(self.fit_method.name())
+                            .show_ui(ui, |ui| {
+                                for method in FitMethod::ALL {
+                                    ui.selectable_value(&mut self.fit_method, method, method.name());
+                                }
+                            }
+                        );
+
+                        if ui.button("Fit ellipsoid").clicked() {
+                            self.fit_points = true;
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.show_points, "show points");
+                        ui.color_edit_button_srgb(&mut self.point_color);
+                    });
+                    ui.add(egui::Slider::new(&mut self.point_size, 0.5..=10.0).text("point size"));
+
+                    if !self.points_status.is_empty() {
+                        ui.label(&self.points_status);
+                    }
+                });
+
+                ui.separator();
+
+                ui.collapsing("Gaussian splats", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.splats_path);
+                        if ui.button("Load").clicked() {
+                            self.load_splats = true;
+                        }
+                    });
+
+                    if ui.button("Reset view").clicked() {
+                        self.reset_splat_view = true;
+                    }
+
+                    if !self.splats_status.is_empty() {
+                        ui.label(&self.splats_status);
+                    }
+                });
+
+                ui.separator();
+
+                ui.collapsing("Tensor glyphs", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.tensors_path);
+                        if ui.button("Load").clicked() {
+                            self.load_tensors = true;
+                        }
+                        if ui.button("Clear").clicked() {
+                            self.clear_tensors = true;
+                        }
+                    });
+
+                    let options = &mut self.glyph_options;
+
+                    egui::ComboBox::from_id_source("glyph_coloring")
+                        .selected_text(options.coloring.name())
+                        .show_ui(ui, |ui| {
+                            for coloring in GlyphColoring::ALL {
+                                ui.selectable_value(&mut options.coloring, coloring, coloring.name());
+                            }
+                        }
+                    );
+
+                    if options.coloring != GlyphColoring::PrincipalDirection {
+                        egui::ComboBox::from_id_source("glyph_colormap")
+                            .selected_text(options.colormap.name())
+                            .show_ui(ui, |ui| {
+                                for colormap in Colormap::ALL {
+                                    ui.selectable_value(&mut options.colormap, colormap, colormap.name());
+                                }
+                            }
+                        );
+                    }
+
+                    ui.add(egui::Slider::new(&mut options.scale, 1e-4..=1e4).logarithmic(true).text("scale"));
+
+                    ui.label("Fractional anisotropy");
+                    ui.add(egui::Slider::new(&mut options.anisotropy_range.0, 0.0..=1.0).text("min"));
+                    ui.add(egui::Slider::new(&mut options.anisotropy_range.1, 0.0..=1.0).text("max"));
+
+                    if let Some((low, high)) = self.trace_limits {
+                        ui.label("Trace");
+                        ui.add(egui::Slider::new(&mut options.trace_range.0, low..=high).text("min"));
+                        ui.add(egui::Slider::new(&mut options.trace_range.1, low..=high).text("max"));
+                    }
+
+                    if options.coloring != GlyphColoring::PrincipalDirection {
+                        let (min, max) = match options.coloring {
+                            GlyphColoring::Trace => options.trace_range,
+                            _ => (0.0, 1.0),
+                        };
+                        colormap_legend(options.colormap, min, max, ui);
+                    }
+
+                    if self.trace_limits.is_some() {
+                        ui.label(format!("{} glyphs", self.glyph_count));
+                    }
+
+                    if !self.tensors_status.is_empty() {
+                        ui.label(&self.tensors_status);
+                    }
+                });
+
+                ui.separator();
+
+                ui.collapsing("Primitives", |ui| {
+                    let mut removed = None;
+                    for (i, primitive) in self.primitives.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{} {}", primitive.kind.name(), i + 1));
+
+                            egui::ComboBox::from_id_source(("primitive_material", i))
+                                .selected_text(self.materials.get(primitive.material).map_or("", |material| material.name.as_str()))
+                                .show_ui(ui, |ui| {
+                                    for (j, material) in self.materials.iter().enumerate() {
+                                        ui.selectable_value(&mut primitive.material, j, &material.name);
+                                    }
+                                }
+                            );
+
+                            if ui.button("Remove").clicked() {
+                                removed = Some(i);
+                            }
+                        });
+
+                        match &mut primitive.kind {
+                            PrimitiveKind::Plane { point, normal, half_size } => {
+                                vector_input("point:", &mut point.coords, ui);
+                                vector_input("normal:", normal, ui);
+
+                                let mut bounded = half_size.is_some();
+                                ui.horizontal(|ui| {
+                                    ui.checkbox(&mut bounded, "bounded");
+                                    if let Some(half_size) = half_size {
+                                        ui.add(egui::DragValue::new(half_size).speed(0.05).clamp_range(0.01..=1000.0));
+                                    }
+                                });
+                                *half_size = bounded.then_some(half_size.unwrap_or(5.0));
+                            }
+                            PrimitiveKind::Sphere { center, radius } => {
+                                vector_input("center:", &mut center.coords, ui);
+                                float_input("radius:", radius, 0.01..=100.0, ui);
+                            }
+                            PrimitiveKind::Box { min, max } => {
+                                vector_input("min:", &mut min.coords, ui);
+                                vector_input("max:", &mut max.coords, ui);
+                            }
+                            PrimitiveKind::Disc { center, normal, radius } => {
+                                vector_input("center:", &mut center.coords, ui);
+                                vector_input("normal:", normal, ui);
+                                float_input("radius:", radius, 0.01..=100.0, ui);
+                            }
+                            PrimitiveKind::Torus { center, axis, major_radius, minor_radius } => {
+                                vector_input("center:", &mut center.coords, ui);
+                                vector_input("axis:", axis, ui);
+                                float_input("major radius:", major_radius, 0.01..=100.0, ui);
+                                float_input("minor radius:", minor_radius, 0.01..=100.0, ui);
+                            }
+                            PrimitiveKind::Superellipsoid { center, radii, vertical, horizontal } => {
+                                vector_input("center:", &mut center.coords, ui);
+                                vector_input("radii:", radii, ui);
+                                *radii = radii.map(|radius| radius.max(0.01));
+                                ui.add(egui::Slider::new(vertical, 0.05..=4.0).text("vertical exponent"));
+                                ui.add(egui::Slider::new(horizontal, 0.05..=4.0).text("horizontal exponent"));
+                            }
+                            PrimitiveKind::Implicit(root) => sdf_input(root, ui),
+                        }
+
+                        if self.shading_mode == ShadingMode::XRay {
+                            float_input("density:", &mut primitive.density, 0.0..=10.0, ui);
+                            ui.horizontal(|ui| {
+                                ui.label("absorption:");
+                                let absorption = &mut primitive.absorption;
+                                let mut color = [absorption.red(), absorption.green(), absorption.blue()];
+                                if ui.color_edit_button_srgb(&mut color).changed() {
+                                    *absorption = Color::from_rgb(color[0], color[1], color[2]);
+                                }
+                            });
+                        }
+                    }
+
+                    if let Some(i) = removed {
+                        self.primitives.remove(i);
+                    }
+
+                    ui.horizontal_wrapped(|ui| {
+                        if ui.button("Add ground plane").clicked() {
+                            self.primitives.push(Primitive::ground(-3.0, 0));
+                        }
+
+                        let origin = na::Point3::origin();
+                        let added = [
+                            ("Add sphere", PrimitiveKind::Sphere { center: origin, radius: 1.0 }),
+                            ("Add box", PrimitiveKind::Box { min: na::Point3::new(-1.0, -1.0, -1.0), max: na::Point3::new(1.0, 1.0, 1.0) }),
+                            ("Add disc", PrimitiveKind::Disc { center: origin, normal: -na::Vector3::z(), radius: 1.0 }),
+                            ("Add torus", PrimitiveKind::Torus { center: origin, axis: na::Vector3::new(0.0, 1.0, -1.0), major_radius: 1.0, minor_radius: 0.3 }),
+                            ("Add superellipsoid", PrimitiveKind::Superellipsoid { center: origin, radii: na::Vector3::repeat(1.0), vertical: 0.5, horizontal: 0.5 }),
+                        ];
+
+                        for (label, kind) in added {
+                            if ui.button(label).clicked() {
+                                self.primitives.push(Primitive::new(kind, 0));
+                            }
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.implicit_expression);
+                        if ui.button("Add implicit").clicked() {
+                            match self.implicit_expression.parse::<SdfNode>() {
+                                Ok(root) => {
+                                    self.primitives.push(Primitive::new(PrimitiveKind::Implicit(root), 0));
+                                    self.implicit_status.clear();
+                                }
+                                Err(err) => self.implicit_status = format!("Invalid expression: {}", err),
+                            }
+                        }
+                    });
+
+                    if !self.implicit_status.is_empty() {
+                        ui.label(&self.implicit_status);
+                    }
+                });
+
+                ui.separator();
+
+                ui.collapsing("CSG shapes", |ui| {
+                    for (i, shape) in self.shapes.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("shape {}", i + 1));
+                            if matches!(shape, CsgNode::Operation { .. }) && ui.button("Ungroup").clicked() {
+                                self.csg_commands.push(CsgCommand::Ungroup(i));
+                            }
+                        });
+
+                        ui.indent(("shape", i), |ui| {
+                            csg_node_ui(shape, i, &mut Vec::new(), &mut self.csg_commands, ui);
+                        });
+                    }
+
+                    if self.shapes.len() > 1 {
+                        ui.horizontal(|ui| {
+                            shape_selector("combine_first", &mut self.combine_first, self.shapes.len(), ui);
+                            operation_selector("combine_operation", &mut self.combine_operation, ui);
+                            shape_selector("combine_second", &mut self.combine_second, self.shapes.len(), ui);
+                        });
+
+                        if ui.button("Combine").clicked() {
+                            self.csg_commands.push(CsgCommand::Combine {
+                                first: self.combine_first,
+                                second: self.combine_second,
+                                operation: self.combine_operation,
+                            });
+                        }
+                    }
+                });
+
+                ui.separator();
+
+                ui.collapsing("Materials", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("texture:");
+                        ui.text_edit_singleline(&mut self.texture_path);
+                    });
+
+                    for (i, material) in self.materials.iter_mut().enumerate() {
+                        egui::CollapsingHeader::new(&material.name)
+                            .id_source(("material", i))
+                            .show(ui, |ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label("name:");
+                                    ui.text_edit_singleline(&mut material.name);
+                                });
+
+                                ui.horizontal(|ui| {
+                                    color_input("color:", &mut material.color, ui);
+
+                                    if ui.button("Load texture").clicked() {
+                                        self.load_texture = Some(i);
+                                    }
+
+                                    if material.texture.is_some() && ui.button("Clear texture").clicked() {
+                                        material.texture = None;
+                                    }
+                                });
+
+                                if let Some(texture) = &material.texture {
+                                    ui.label(format!("texture {}", texture.path));
+                                }
+
+                                float_input("ambient:", &mut material.ambient, 0.0..=1.0, ui);
+                                float_input("diffuse:", &mut material.diffuse, 0.0..=1.0, ui);
+                                float_input("specular:", &mut material.specular, 0.0..=1.0, ui);
+                                float_input("shininess:", &mut material.shininess, 1.0..=512.0, ui);
+                                ui.horizontal(|ui| color_input("emissive:", &mut material.emissive, ui));
+                                float_input("opacity:", &mut material.opacity, 0.0..=1.0, ui);
+                                float_input("reflectivity:", &mut material.reflectivity, 0.0..=1.0, ui);
+                                float_input("refractive index:", &mut material.refractive_index, 1.0..=3.0, ui);
+
+                                if ui.button("Remove").clicked() {
+                                    self.remove_material = Some(i);
+                                }
+                            });
+                    }
+
+                    if ui.button("Add material").clicked() {
+                        let name = format!("material {}", self.materials.len() + 1);
+                        self.materials.push(Material::new(&name, Color::from_rgb(255, 255, 255)));
+                    }
+
+                    if !self.material_status.is_empty() {
+                        ui.label(&self.material_status);
+                    }
+                });
 
                 ui.separator();
 
                 ui.label("Light options");
-                float_input("m: ", &mut self.m, 0.0..=20.0, ui);
+                float_input("exposure:", &mut self.exposure, -10.0..=10.0, ui);
+
+                egui::ComboBox::from_id_source("tone_mapping")
+                    .selected_text(self.tone_mapping.name())
+                    .show_ui(ui, |ui| {
+                        for tone_mapping in ToneMapping::ALL {
+                            ui.selectable_value(&mut self.tone_mapping, tone_mapping, tone_mapping.name());
+                        }
+                    }
+                );
+
+                ui.add(egui::Slider::new(&mut self.max_depth, 0..=10).text("ray depth"));
+
+                ui.collapsing("Lights", |ui| {
+                    float_input("ambient:", &mut self.ambient, 0.0..=1.0, ui);
+
+                    ui.horizontal(|ui| {
+                        ui.label("shadows:");
+                        egui::ComboBox::from_id_source("shadow_mode")
+                            .selected_text(self.shadow_mode.name())
+                            .show_ui(ui, |ui| {
+                                for mode in [ShadowMode::Off, ShadowMode::Hard, ShadowMode::Soft { samples: self.shadow_samples }] {
+                                    ui.selectable_value(&mut self.shadow_mode, mode, mode.name());
+                                }
+                            }
+                        );
+                    });
+
+                    if let ShadowMode::Soft { samples } = &mut self.shadow_mode {
+                        ui.add(egui::Slider::new(samples, 2..=64).text("samples"));
+                        self.shadow_samples = *samples;
+                    }
+
+                    let mut removed = None;
+                    for (i, light) in self.lights.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("light {}", i + 1));
+
+                            let mut color = [light.color.red(), light.color.green(), light.color.blue()];
+                            if ui.color_edit_button_srgb(&mut color).changed() {
+                                light.color = Color::from_rgb(color[0], color[1], color[2]);
+                            }
+
+                            if ui.button("Remove").clicked() {
+                                removed = Some(i);
+                            }
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.label("position:");
+                            for coordinate in light.position.coords.iter_mut() {
+                                ui.add(egui::DragValue::new(coordinate).speed(0.05));
+                            }
+                        });
+
+                        float_input("intensity:", &mut light.intensity, 0.0..=10.0, ui);
+                        float_input("radius:", &mut light.radius, 0.0..=20.0, ui);
+                    }
+
+                    if let Some(i) = removed {
+                        self.lights.remove(i);
+                    }
+
+                    if ui.button("Add light").clicked() {
+                        self.lights.push(Light::new(&na::Point3::new(5.0, 5.0, -10.0), Color::from_rgb(255, 255, 255), 1.0, 0.0));
+                    }
+                });
+
+                ui.separator();
+
+                ui.label("Shading");
+                egui::ComboBox::from_id_source("shading_mode")
+                    .selected_text(shading_mode_name(self.shading_mode))
+                    .show_ui(ui, |ui| {
+                        for mode in [ShadingMode::Light, ShadingMode::GaussianCurvature, ShadingMode::MeanCurvature, ShadingMode::XRay, ShadingMode::Splats] {
+                            ui.selectable_value(&mut self.shading_mode, mode, shading_mode_name(mode));
+                        }
+                    }
+                );
+
+                if matches!(self.shading_mode, ShadingMode::GaussianCurvature | ShadingMode::MeanCurvature) {
+                    egui::ComboBox::from_id_source("colormap")
+                        .selected_text(self.colormap.name())
+                        .show_ui(ui, |ui| {
+                            for colormap in Colormap::ALL {
+                                ui.selectable_value(&mut self.colormap, colormap, colormap.name());
+                            }
+                        }
+                    );
+
+                    float_input("min:", &mut self.curvature_min, -100.0..=100.0, ui);
+                    float_input("max:", &mut self.curvature_max, -100.0..=100.0, ui);
+
+                    if ui.button("Fit to view").clicked() {
+                        self.fit_curvature_range = true;
+                    }
+
+                    colormap_legend(self.colormap, self.curvature_min, self.curvature_max, ui);
+                }
+
+                ui.separator();
+
+                ui.label("Clipping");
+                float_input("near:", &mut self.near, 0.0..=100.0, ui);
+                float_input("far:", &mut self.far, 0.0..=100.0, ui);
+
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.cut_mode, CutMode::Solid, "Solid");
+                    ui.selectable_value(&mut self.cut_mode, CutMode::Hollow, "Hollow");
+                });
+
+                let mut removed = None;
+                for (i, plane) in self.clip_planes.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut plane.enabled, format!("plane {}", i + 1));
+                        if ui.button("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                    ui.add(egui::Slider::new(&mut plane.azimuth, -180.0..=180.0).text("azimuth"));
+                    ui.add(egui::Slider::new(&mut plane.elevation, -90.0..=90.0).text("elevation"));
+                    ui.add(egui::Slider::new(&mut plane.offset, -5.0..=5.0).text("offset"));
+                }
+
+                if let Some(i) = removed {
+                    self.clip_planes.remove(i);
+                }
+
+                if ui.button("Add clipping plane").clicked() {
+                    self.clip_planes.push(ClipPlaneControls { enabled: true, azimuth: 0.0, elevation: 0.0, offset: 0.0 });
+                }
+
+                ui.separator();
+
+                ui.label("Background");
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.background_mode, BackgroundMode::Solid, "Solid");
+                    ui.selectable_value(&mut self.background_mode, BackgroundMode::Gradient, "Gradient");
+                    ui.selectable_value(&mut self.background_mode, BackgroundMode::Transparent, "Transparent");
+                });
+
+                match self.background_mode {
+                    BackgroundMode::Solid => {
+                        ui.color_edit_button_srgb(&mut self.background_top);
+                    }
+                    BackgroundMode::Gradient => {
+                        ui.horizontal(|ui| {
+                            ui.label("top:");
+                            ui.color_edit_button_srgb(&mut self.background_top);
+                            ui.label("bottom:");
+                            ui.color_edit_button_srgb(&mut self.background_bottom);
+                        });
+                    }
+                    BackgroundMode::Transparent => ()
+                }
+
+                ui.collapsing("Environment", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut self.environment_mode, EnvironmentMode::Off, "Off");
+                        ui.selectable_value(&mut self.environment_mode, EnvironmentMode::Sky, "Sky");
+                        ui.selectable_value(&mut self.environment_mode, EnvironmentMode::Image, "Image");
+                    });
+
+                    match self.environment_mode {
+                        EnvironmentMode::Off => (),
+                        EnvironmentMode::Sky => {
+                            ui.horizontal(|ui| {
+                                for (label, color) in ["zenith:", "horizon:", "ground:"].into_iter().zip(&mut self.sky_colors) {
+                                    ui.label(label);
+                                    ui.color_edit_button_srgb(color);
+                                }
+                            });
+                        }
+                        EnvironmentMode::Image => {
+                            ui.horizontal(|ui| {
+                                ui.text_edit_singleline(&mut self.environment_path);
+                                if ui.button("Load").clicked() {
+                                    self.load_environment = true;
+                                }
+                            });
+                        }
+                    }
+
+                    if self.environment_mode != EnvironmentMode::Off {
+                        ui.add(egui::Slider::new(&mut self.environment_rotation, -180.0..=180.0).text("rotation"));
+                        float_input("intensity:", &mut self.environment_intensity, 0.0..=10.0, ui);
+                    }
+
+                    if !self.environment_status.is_empty() {
+                        ui.label(&self.environment_status);
+                    }
+                });
+
+                ui.separator();
+
+                ui.label("Scene file");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.scene_path);
+                    if ui.button("Save").clicked() {
+                        self.save_scene = true;
+                    }
+                    if ui.button("Load").clicked() {
+                        self.load_scene = true;
+                    }
+                });
+
+                if !self.scene_status.is_empty() {
+                    ui.label(&self.scene_status);
+                }
+
+                ui.separator();
+
+                ui.label("Export");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.export_path);
+                    if ui.button("Save PNG").clicked() {
+                        self.save_png = true;
+                    }
+                });
+
+                if !self.export_status.is_empty() {
+                    ui.label(&self.export_status);
+                }
+
+                ui.separator();
+
+                ui.label("Mesh export (.obj, .stl, .ply)");
+                ui.small("Ellipsoids, spheres, discs and bounded planes, written whole: shapes and clip planes are not applied.");
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.mesh_adaptive, false, "Uniform");
+                    ui.selectable_value(&mut self.mesh_adaptive, true, "Adaptive");
+                });
+
+                if self.mesh_adaptive {
+                    ui.add(egui::Slider::new(&mut self.mesh_tolerance, 0.0005..=0.1).logarithmic(true).text("tolerance"));
+                } else {
+                    ui.add(egui::Slider::new(&mut self.mesh_latitudes, 2..=256).text("latitudes"));
+                    ui.add(egui::Slider::new(&mut self.mesh_longitudes, 3..=512).text("longitudes"));
+                }
+
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.mesh_path);
+                    if ui.button("Export mesh").clicked() {
+                        self.export_mesh = true;
+                    }
+                });
+
+                if !self.mesh_status.is_empty() {
+                    ui.label(&self.mesh_status);
+                }
+
+                ui.separator();
+
+                ui.label("Vector export (.svg)");
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.svg_options.outlines, "outlines");
+                    ui.checkbox(&mut self.svg_options.cross_sections, "cross-sections");
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.svg_options.hidden_line_removal, "remove hidden lines");
+                    ui.checkbox(&mut self.svg_options.raster, "include image");
+                });
+                ui.add(egui::Slider::new(&mut self.svg_options.latitudes, 0..=32).text("latitude lines"));
+                ui.add(egui::Slider::new(&mut self.svg_options.longitudes, 0..=64).text("longitude lines"));
+                ui.add(egui::Slider::new(&mut self.svg_options.stroke_width, 0.25..=8.0).text("line width"));
+
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.svg_path);
+                    if ui.button("Export SVG").clicked() {
+                        self.export_svg = true;
+                    }
+                });
+
+                if !self.svg_status.is_empty() {
+                    ui.label(&self.svg_status);
+                }
+
+                ui.separator();
+
+                ui.collapsing("Under cursor", |ui| {
+                    match &self.hover {
+                        Some(pick) => {
+                            ui.label(match pick.object {
+                                ObjectId::Ellipse(index) => format!("object: ellipsoid {}", index + 1),
+                                ObjectId::Primitive(index) => format!("object: primitive {}", index + 1),
+                                ObjectId::Glyph(index) => format!("object: glyph {}", index + 1),
+                            });
+                            ui.label(format!("position: ({:.3}, {:.3}, {:.3})", pick.position.x, pick.position.y, pick.position.z));
+                            ui.label(format!("depth: {:.3}", pick.depth));
+                            ui.label(format!("normal: ({:.3}, {:.3}, {:.3})", pick.normal.x, pick.normal.y, pick.normal.z));
+                            ui.label(format!("uv: ({:.3}, {:.3})", pick.uv.0, pick.uv.1));
+                        }
+                        None => {
+                            ui.label("background");
+                        }
+                    }
+                });
 
                 ui.separator();
 
@@ -219,6 +1452,173 @@ impl GuiState {
                 );
             });
 
+        fn csg_node_ui(node: &CsgNode, shape: usize, path: &mut Vec<usize>, commands: &mut Vec<CsgCommand>, ui: &mut Ui) {
+            match node {
+                CsgNode::Object(index) => {
+                    ui.label(format!("ellipsoid {}", index + 1));
+                }
+
+                CsgNode::Operation { operation, left, right } => {
+                    ui.horizontal(|ui| {
+                        let mut selected = *operation;
+                        operation_selector(("operation", shape, path.clone()), &mut selected, ui);
+
+                        if selected != *operation {
+                            commands.push(CsgCommand::SetOperation { shape, path: path.clone(), operation: selected });
+                        }
+
+                        if ui.button("Swap").clicked() {
+                            commands.push(CsgCommand::Swap { shape, path: path.clone() });
+                        }
+                    });
+
+                    ui.indent(("operands", shape, path.clone()), |ui| {
+                        for (step, operand) in [left, right].into_iter().enumerate() {
+                            path.push(step);
+                            csg_node_ui(operand, shape, path, commands, ui);
+                            path.pop();
+                        }
+                    });
+                }
+            }
+        }
+
+        fn operation_selector(id: impl std::hash::Hash, operation: &mut CsgOperation, ui: &mut Ui) {
+            egui::ComboBox::from_id_source(id)
+                .selected_text(operation.name())
+                .show_ui(ui, |ui| {
+                    for value in CsgOperation::ALL {
+                        ui.selectable_value(operation, value, value.name());
+                    }
+                }
+            );
+        }
+
+        fn shape_selector(id: &str, shape: &mut usize, count: usize, ui: &mut Ui) {
+            egui::ComboBox::from_id_source(id)
+                .selected_text(format!("shape {}", *shape + 1))
+                .show_ui(ui, |ui| {
+                    for i in 0..count {
+                        ui.selectable_value(shape, i, format!("shape {}", i + 1));
+                    }
+                }
+            );
+        }
+
+        fn shading_mode_name(mode: ShadingMode) -> &'static str {
+            match mode {
+                ShadingMode::Light => "Light",
+                ShadingMode::GaussianCurvature => "Gaussian curvature",
+                ShadingMode::MeanCurvature => "Mean curvature",
+                ShadingMode::XRay => "X-ray",
+                ShadingMode::Splats => "Gaussian splats",
+            }
+        }
+
+        fn colormap_legend(colormap: Colormap, min: f32, max: f32, ui: &mut Ui) {
+            const SEGMENTS: usize = 32;
+
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(200.0, 16.0), egui::Sense::hover());
+            let mut mesh = egui::Mesh::default();
+
+            for i in 0..=SEGMENTS {
+                let t = i as f32 / SEGMENTS as f32;
+                let color = colormap.sample(t);
+                let color = Color32::from_rgb(color.red(), color.green(), color.blue());
+                let x = rect.left() + t * rect.width();
+
+                mesh.colored_vertex(egui::pos2(x, rect.top()), color);
+                mesh.colored_vertex(egui::pos2(x, rect.bottom()), color);
+
+                if i > 0 {
+                    let idx = (2 * i) as u32;
+                    mesh.add_triangle(idx - 2, idx - 1, idx);
+                    mesh.add_triangle(idx - 1, idx + 1, idx);
+                }
+            }
+
+            ui.painter().add(egui::Shape::mesh(mesh));
+
+            ui.horizontal(|ui| {
+                ui.label(format!("{:.3}", min));
+                ui.add_space(rect.width() - 90.0);
+                ui.label(format!("{:.3}", max));
+            });
+        }
+
+        /// Reads a full row-major matrix or the upper triangle `xx xy xz yy yz zz`,
+        /// numbers may be separated by whitespace, commas, semicolons and brackets.
+        fn parse_covariance(text: &str) -> Option<[[f32; 3]; 3]> {
+            let values = text.split(|c: char| c.is_whitespace() || ",;[]()".contains(c))
+                .filter(|value| !value.is_empty())
+                .map(|value| value.parse::<f32>().ok())
+                .collect::<Option<Vec<f32>>>()?;
+
+            match values[..] {
+                [xx, xy, xz, yx, yy, yz, zx, zy, zz] => Some([[xx, xy, xz], [yx, yy, yz], [zx, zy, zz]]),
+                [xx, xy, xz, yy, yz, zz] => Some([[xx, xy, xz], [xy, yy, yz], [xz, yz, zz]]),
+                _ => None
+            }
+        }
+
+        fn color_input(label: &str, color: &mut Color, ui: &mut Ui) {
+            let mut rgb = [color.red(), color.green(), color.blue()];
+
+            ui.label(label);
+            if ui.color_edit_button_srgb(&mut rgb).changed() {
+                *color = Color::from_rgb(rgb[0], rgb[1], rgb[2]);
+            }
+        }
+
+        fn vector_input(label: &str, vector: &mut na::Vector3<f32>, ui: &mut Ui) {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                for coordinate in vector.iter_mut() {
+                    ui.add(egui::DragValue::new(coordinate).speed(0.05));
+                }
+            });
+        }
+
+        /// Parameters of every node of the distance function, children indented below their parent.
+        fn sdf_input(node: &mut SdfNode, ui: &mut Ui) {
+            ui.label(node.name());
+
+            match node {
+                SdfNode::Sphere { center, radius } => {
+                    vector_input("center:", &mut center.coords, ui);
+                    float_input("radius:", radius, 0.01..=100.0, ui);
+                }
+                SdfNode::Box { center, half_size } => {
+                    vector_input("center:", &mut center.coords, ui);
+                    vector_input("half size:", half_size, ui);
+                }
+                SdfNode::Torus { center, major_radius, minor_radius } => {
+                    vector_input("center:", &mut center.coords, ui);
+                    float_input("major radius:", major_radius, 0.01..=100.0, ui);
+                    float_input("minor radius:", minor_radius, 0.01..=100.0, ui);
+                }
+                SdfNode::SmoothUnion { left, right, smoothness } => {
+                    float_input("smoothness:", smoothness, 0.0..=10.0, ui);
+                    ui.indent("left", |ui| sdf_input(left, ui));
+                    ui.indent("right", |ui| sdf_input(right, ui));
+                }
+                SdfNode::Blend { left, right, amount } => {
+                    ui.add(egui::Slider::new(amount, 0.0..=1.0).text("amount"));
+                    ui.indent("left", |ui| sdf_input(left, ui));
+                    ui.indent("right", |ui| sdf_input(right, ui));
+                }
+                SdfNode::Twist { child, rate } => {
+                    float_input("rate:", rate, -10.0..=10.0, ui);
+                    ui.indent("child", |ui| sdf_input(child, ui));
+                }
+                SdfNode::Displace { child, amplitude, frequency } => {
+                    float_input("amplitude:", amplitude, -1.0..=1.0, ui);
+                    float_input("frequency:", frequency, 0.0..=100.0, ui);
+                    ui.indent("child", |ui| sdf_input(child, ui));
+                }
+            }
+        }
+
         fn float_input(label: &str, value: &mut f32, range: RangeInclusive<f32>, ui: &mut Ui) {
             ui.horizontal(|ui| {
                 ui.label(label);